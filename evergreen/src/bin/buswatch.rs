@@ -1,18 +1,101 @@
 //! Watch the message bus for stale messages and apply a TTL value
 //! so they may be removed.
+//!
+//! Also provides on-call debugging modes for capturing bus messages
+//! and displaying queue depths.
 use eg::date;
+use eg::osrf::addr::BusAddress;
 use eg::osrf::bus;
 use eg::osrf::conf;
+use eg::osrf::message::{Payload, TransportMessage};
+use eg::osrf::signing;
+use eg::util;
 use eg::EgResult;
 use eg::EgValue;
 use evergreen as eg;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::thread;
 use std::time::Duration;
 
 // The 'watch' account requires permissions:
 // +keys +ttl +expire +llen
+//
+// Capture mode additionally requires +lrange
+
+const HELP_TEXT: &str = r#"
+Watch the message bus.
+
+With no mode options, buswatch periodically applies a TTL to stale
+bus keys so they may be removed.
+
+Modes
+
+    --capture
+        Peek at the messages queued at the selected bus addresses
+        and print each new message as a line of JSON.  Messages are
+        never removed from the bus.  Peeking is best-effort: messages
+        which are queued and consumed between polls are not seen.
+
+    --depth
+        Display the depth and TTL of each selected queue, refreshed
+        every --interval seconds.
+
+Options
+
+    --address <pattern>
+        Bus address pattern to watch, e.g. opensrf:service:*  May be
+        repeated.  Defaults to opensrf:*
+
+    --interval <seconds>
+        How often to poll the bus.  Defaults to 1.
+
+    --service <service>
+        Only capture requests sent to this service and the replies
+        to those requests.
+
+    --method <method-prefix>
+        Only capture requests for methods starting with this prefix
+        and the replies to those requests.
+
+    --thread <thread>
+        Only capture messages for this session thread.
+
+    --log-trace <xid>
+        Only capture messages carrying this log trace.
+
+    --out-file <path>
+        Append captured messages to this file instead of STDOUT.
+
+    --count <n>
+        Exit after capturing this many messages.
+
+    --peek-limit <n>
+        Look at no more than this many messages per queue per poll.
+        Defaults to 1000.
+
+    --no-redact
+        Show protected parameters and replies in clear text.
+
+Parameters for methods matching the log_protect configuration, and
+replies to those methods, are redacted.  Since capture may miss the
+request which started a session, when log_protect is configured,
+replies are also redacted unless their request was captured and is
+not protected.
+
+Standard OpenSRF environment variables (e.g. OSRF_CONFIG) are
+also supported.  OSRF_BUSWATCH_TTL sets the TTL applied to stale keys.
+"#;
+
+const DEFAULT_PEEK_LIMIT: isize = 1000;
+
+/// Forget the methods for this many request threads at a time.
+const MAX_TRACKED_THREADS: usize = 10000;
 
 /// How often we wake and check for stale keys.
 const DEFAULT_WAIT_TIME: u64 = 60; // 1 minute
@@ -105,10 +188,334 @@ impl BusWatch {
     }
 }
 
+/// Which messages we want to see.
+#[derive(Default)]
+struct CaptureFilter {
+    service: Option<String>,
+    method: Option<String>,
+    thread: Option<String>,
+    log_trace: Option<String>,
+}
+
+/// Peeks at queued bus messages and records the ones we care about.
+struct Capture {
+    bus: bus::Bus,
+    addresses: Vec<String>,
+    interval: u64,
+    peek_limit: isize,
+    filter: CaptureFilter,
+
+    /// Hashes of the messages seen in each queue on the last poll.
+    seen: HashMap<String, HashSet<u64>>,
+
+    /// Method name of each captured request by session thread, so we
+    /// can match and redact the replies.
+    threads: HashMap<String, String>,
+
+    /// Hide log-protected params and replies.
+    redact: bool,
+
+    out: Box<dyn Write>,
+    count: usize,
+    max_count: Option<usize>,
+}
+
+impl Capture {
+    fn capture(&mut self) -> EgResult<()> {
+        loop {
+            let keys = watched_keys(&mut self.bus, &self.addresses)?;
+
+            let mut seen = HashMap::new();
+
+            for key in keys {
+                // The key pattern may match keys which are not lists.
+                let values = match self.bus.lrange(&key, 0, self.peek_limit - 1) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::warn!("Cannot read queue {key}: {e}");
+                        continue;
+                    }
+                };
+
+                let mut hashes = HashSet::new();
+
+                for value in values {
+                    let mut hasher = DefaultHasher::new();
+                    value.hash(&mut hasher);
+                    let hash = hasher.finish();
+
+                    let is_new = !self
+                        .seen
+                        .get(&key)
+                        .map(|h| h.contains(&hash))
+                        .unwrap_or(false);
+
+                    if is_new && hashes.insert(hash) {
+                        self.record(&key, &value)?;
+
+                        if self.max_count.map(|c| self.count >= c).unwrap_or(false) {
+                            return Ok(());
+                        }
+                    } else {
+                        hashes.insert(hash);
+                    }
+                }
+
+                seen.insert(key, hashes);
+            }
+
+            self.seen = seen;
+
+            thread::sleep(Duration::from_secs(self.interval));
+        }
+    }
+
+    /// Decode a raw bus message and write it out if it passes our filters.
+    fn record(&mut self, key: &str, value: &str) -> EgResult<()> {
+        let mut json_val = match json::parse(value) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Cannot parse message at {key}: {e} {value}");
+                return Ok(());
+            }
+        };
+
+        let mut verify_error = None;
+
+        if let Some(security) = conf::config().client().message_security() {
+            if let Err(e) = signing::open(&mut json_val, security) {
+                verify_error = Some(e.to_string());
+            }
+        }
+
+        let mut tmsg = match TransportMessage::from_json_value(json_val, true) {
+            Ok(t) => t,
+            Err(e) => {
+                log::warn!("Cannot decode message at {key}: {e}");
+                return Ok(());
+            }
+        };
+
+        if !self.matches(&tmsg) {
+            return Ok(());
+        }
+
+        self.redact(&mut tmsg);
+
+        let mut obj = eg::hash! {
+            "time": date::epoch_secs_str(),
+            "queue": key,
+        };
+
+        obj["message"] = EgValue::from_json_value_plain(tmsg.into_json_value());
+
+        if let Some(e) = verify_error {
+            obj["verify_error"] = EgValue::from(e);
+        }
+
+        writeln!(self.out, "{}", obj.dump())
+            .map_err(|e| format!("Cannot write captured message: {e}"))?;
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// True if the message passes our filters.
+    ///
+    /// Also tracks the threads of matching requests so their replies
+    /// match as well.
+    fn matches(&mut self, tmsg: &TransportMessage) -> bool {
+        let filter = &self.filter;
+
+        if let Some(thread) = filter.thread.as_ref() {
+            if tmsg.thread() != thread {
+                return false;
+            }
+        }
+
+        if let Some(xid) = filter.log_trace.as_ref() {
+            if tmsg.osrf_xid() != xid {
+                return false;
+            }
+        }
+
+        let service = BusAddress::from_str(tmsg.to())
+            .ok()
+            .and_then(|a| a.service().map(|s| s.to_string()));
+
+        let mut request_match = None;
+
+        for msg in tmsg.body().iter() {
+            if let Payload::Method(method) = msg.payload() {
+                let service_ok = match filter.service.as_ref() {
+                    Some(s) => service.as_deref() == Some(s.as_str()),
+                    None => true,
+                };
+
+                let method_ok = match filter.method.as_ref() {
+                    Some(m) => method.method().starts_with(m.as_str()),
+                    None => true,
+                };
+
+                if service_ok && method_ok {
+                    request_match = Some(method.method().to_string());
+                }
+            }
+        }
+
+        if let Some(method) = request_match {
+            if self.threads.len() >= MAX_TRACKED_THREADS {
+                self.threads.clear();
+            }
+            self.threads.insert(tmsg.thread().to_string(), method);
+            return true;
+        }
+
+        if filter.service.is_none() && filter.method.is_none() {
+            return true;
+        }
+
+        // Replies to requests we captured.
+        tmsg.body()
+            .iter()
+            .any(|m| !matches!(m.payload(), Payload::Method(_)))
+            && self.threads.contains_key(tmsg.thread())
+    }
+
+    /// Replace the parameters of log-protected methods and the
+    /// content of replies to log-protected methods.
+    ///
+    /// Replies whose request we did not see are assumed to be protected.
+    fn redact(&self, tmsg: &mut TransportMessage) {
+        if !self.redact {
+            return;
+        }
+
+        let log_protect = conf::config().log_protect();
+
+        let protected = |method: &str| log_protect.iter().any(|m| method.starts_with(m.as_str()));
+
+        let reply_protected = match self.threads.get(tmsg.thread()) {
+            Some(method) => protected(method),
+            None => !log_protect.is_empty(),
+        };
+
+        for msg in tmsg.body_mut().iter_mut() {
+            match msg.payload_mut() {
+                Payload::Method(method) if protected(method.method()) => {
+                    method.set_params(vec![EgValue::from(util::REDACTED_PARAMS_STR)]);
+                }
+                Payload::Result(result) if reply_protected => {
+                    result.set_content(EgValue::from(util::REDACTED_PARAMS_STR));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Bus keys matching any of the address patterns.
+fn watched_keys(bus: &mut bus::Bus, addresses: &[String]) -> EgResult<Vec<String>> {
+    let mut keys = Vec::new();
+
+    for pattern in addresses {
+        for key in bus.keys(pattern)? {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    keys.sort();
+
+    Ok(keys)
+}
+
+/// Print the depth of each watched queue, deepest first.
+fn depth_dashboard(bus: &mut bus::Bus, addresses: &[String], interval: u64) -> EgResult<()> {
+    let interactive = atty::is(atty::Stream::Stdout);
+
+    loop {
+        let mut rows = Vec::new();
+
+        for key in watched_keys(bus, addresses)? {
+            let depth = bus.llen(&key)?;
+
+            // The list may have cleared since we called keys().
+            if depth > 0 {
+                rows.push((key.to_string(), depth, bus.ttl(&key)?));
+            }
+        }
+
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        if interactive {
+            // Clear the screen and move the cursor to the top.
+            print!("\x1B[2J\x1B[H");
+        }
+
+        let total: i32 = rows.iter().map(|r| r.1).sum();
+
+        println!(
+            "{} queues={} messages={total}",
+            date::epoch_secs_str(),
+            rows.len()
+        );
+        println!("{:>8} {:>8}  QUEUE", "DEPTH", "TTL");
+
+        for (key, depth, ttl) in rows {
+            println!("{depth:>8} {ttl:>8}  {key}");
+        }
+
+        if !interactive {
+            println!();
+        }
+
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
 fn main() {
+    let mut options = getopts::Options::new();
+
+    options.optflag("", "help", "Show this message");
+    options.optflag("", "capture", "");
+    options.optflag("", "depth", "");
+    options.optflag("", "no-redact", "");
+    options.optmulti("", "address", "", "");
+    options.optopt("", "interval", "", "");
+    options.optopt("", "service", "", "");
+    options.optopt("", "method", "", "");
+    options.optopt("", "thread", "", "");
+    options.optopt("", "log-trace", "", "");
+    options.optopt("", "out-file", "", "");
+    options.optopt("", "count", "", "");
+    options.optopt("", "peek-limit", "", "");
+
+    let args: Vec<String> = env::args().collect();
+
+    let params = match options.parse(&args[1..]) {
+        Ok(p) => p,
+        Err(e) => panic!("Error parsing params: {e}\n{HELP_TEXT}"),
+    };
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return;
+    }
+
     eg::init().unwrap();
     let config = conf::config();
 
+    if params.opt_present("capture") || params.opt_present("depth") {
+        if let Err(e) = inspect(&params) {
+            eprintln!("buswatch failed: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     log::info!("Starting buswatch at {}", config.client().domain());
 
     let mut watcher = BusWatch::new();
@@ -125,3 +532,65 @@ fn main() {
         }
     }
 }
+
+/// Run one of the on-call inspection modes.
+fn inspect(params: &getopts::Matches) -> EgResult<()> {
+    let number = |name: &str, default: u64| -> EgResult<u64> {
+        match params.opt_str(name) {
+            Some(v) => v
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid --{name} value: {v} {e}").into()),
+            None => Ok(default),
+        }
+    };
+
+    let interval = number("interval", 1)?.max(1);
+
+    let mut addresses = params.opt_strs("address");
+    if addresses.is_empty() {
+        addresses.push("opensrf:*".to_string());
+    }
+
+    let mut bus = bus::Bus::new(conf::config().client())?;
+
+    if params.opt_present("depth") {
+        return depth_dashboard(&mut bus, &addresses, interval);
+    }
+
+    let out: Box<dyn Write> = match params.opt_str("out-file") {
+        Some(path) => Box::new(
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| format!("Cannot open file {path}: {e}"))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+
+    let max_count = match params.opt_str("count") {
+        Some(_) => Some(number("count", 0)? as usize),
+        None => None,
+    };
+
+    let mut capture = Capture {
+        bus,
+        addresses,
+        interval,
+        out,
+        max_count,
+        peek_limit: number("peek-limit", DEFAULT_PEEK_LIMIT as u64)?.max(1) as isize,
+        filter: CaptureFilter {
+            service: params.opt_str("service"),
+            method: params.opt_str("method"),
+            thread: params.opt_str("thread"),
+            log_trace: params.opt_str("log-trace"),
+        },
+        seen: HashMap::new(),
+        threads: HashMap::new(),
+        redact: !params.opt_present("no-redact"),
+        count: 0,
+    };
+
+    capture.capture()
+}
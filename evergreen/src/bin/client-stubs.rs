@@ -0,0 +1,83 @@
+use eg::init::InitOptions;
+use eg::osrf::stubs;
+use eg::result::EgResult;
+use eg::EgValue;
+use evergreen as eg;
+use getopts;
+use std::fs;
+
+const HELP_TEXT: &str = r#"
+Generate typed Rust client stubs for the methods published by a service.
+
+./eg-client-stubs --service open-ils.circ --out-file src/circ_stubs.rs
+
+Options
+
+    --service <service-name>
+        Service whose methods are retrieved via
+        opensrf.system.method.all.  Required.
+
+    --prefix <api-prefix>
+        Only generate stubs for methods whose names start with this
+        prefix.  Defaults to the service name.
+
+    --out-file <path>
+        Write the generated module to this file instead of STDOUT.
+
+    Standard OpenSRF environment variables (e.g. OSRF_CONFIG) are
+    also supported.
+"#;
+
+fn main() -> EgResult<()> {
+    let mut options = getopts::Options::new();
+
+    options.optflag("", "help", "Show this message");
+    options.optopt("", "service", "", "");
+    options.optopt("", "prefix", "", "");
+    options.optopt("", "out-file", "", "");
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let params = options
+        .parse(&args[1..])
+        .or_else(|e| Err(format!("Error parsing params: {e}")))?;
+
+    if params.opt_present("help") {
+        println!("{HELP_TEXT}");
+        return Ok(());
+    }
+
+    let service = params
+        .opt_str("service")
+        .ok_or_else(|| format!("--service is required.\n{HELP_TEXT}"))?;
+
+    let prefix = params.opt_str("prefix").unwrap_or(service.to_string());
+
+    let mut init_ops = InitOptions::new();
+    init_ops.skip_host_settings = true;
+    init_ops.appname = Some(String::from("eg-client-stubs"));
+
+    let client = eg::init::osrf_init(&init_ops)?;
+
+    let mut methods: Vec<EgValue> = Vec::new();
+
+    for resp in client.send_recv_iter(&service, "opensrf.system.method.all", prefix)? {
+        methods.push(resp?);
+    }
+
+    if methods.is_empty() {
+        return Err(format!("No methods found for service {service}").into());
+    }
+
+    let src = stubs::generate(&service, &methods)?;
+
+    match params.opt_str("out-file") {
+        Some(path) => {
+            fs::write(&path, src).map_err(|e| format!("Cannot write file {path}: {e}"))?;
+            eprintln!("Wrote stubs for {} methods to {path}", methods.len());
+        }
+        None => print!("{src}"),
+    }
+
+    Ok(())
+}
@@ -3,18 +3,18 @@ use eg::date;
 use eg::idl;
 use eg::osrf::conf;
 use eg::osrf::logging::Logger;
+use eg::osrf::metrics;
+use eg::util::HttpRequest;
 use eg::EgResult;
 use eg::EgValue;
 use evergreen as eg;
-use httparse;
 use mptc;
 use std::any::Any;
 use std::env;
-use std::io::{Read, Write};
+use std::io::Write;
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use url::Url;
 
-const BUFSIZE: usize = 1024;
 const DEFAULT_PORT: u16 = 9682;
 const DEFAULT_ADDRESS: &str = "127.0.0.1";
 const DUMMY_BASE_URL: &str = "http://localhost";
@@ -54,14 +54,6 @@ struct ParsedGatewayRequest {
     http_method: String,
 }
 
-/// Just the stuff we need.
-struct ParsedHttpRequest {
-    path: String,
-    method: String,
-    /// Only POST requests will have an HTTP body
-    body: Option<String>,
-}
-
 struct GatewayHandler {
     bus: Option<eg::osrf::bus::Bus>,
     partial_buffer: Option<String>,
@@ -138,6 +130,25 @@ impl GatewayHandler {
 
         log::debug!("[{}] Request duration: {:.3}s", request.address, millis);
 
+        let service = http_req.as_ref().map(|r| r.service.as_str()).unwrap_or("");
+        let service = metrics::service_label(service);
+        let status = if leader.contains("200") { "200" } else { "400" };
+        let labels = [("service", service), ("status", status)];
+
+        metrics::inc_counter(
+            "osrf_gateway_requests",
+            "HTTP gateway requests by service and response status",
+            &labels,
+            1,
+        );
+
+        metrics::inc_counter(
+            "osrf_gateway_request_milliseconds",
+            "Time spent handling HTTP gateway requests",
+            &labels,
+            duration.num_milliseconds().max(0) as u64,
+        );
+
         Ok(())
     }
 
@@ -179,6 +190,9 @@ impl GatewayHandler {
             let mut complete = false;
             let mut batch = self.extract_osrf_responses(&request.format, &mut complete, tm)?;
 
+            // We have a valid response, so the service exists.
+            metrics::add_known_service(&request.service);
+
             replies.append(&mut batch);
 
             if complete {
@@ -271,124 +285,13 @@ impl GatewayHandler {
         Ok(replies)
     }
 
-    /// Pulls the raw request content from the socket and returns it
-    /// as a String.
-    fn read_request(&mut self, request: &mut GatewayRequest) -> EgResult<ParsedHttpRequest> {
+    /// Pulls the raw request content from the socket.
+    fn read_request(&mut self, request: &mut GatewayRequest) -> EgResult<HttpRequest> {
         // It's assumed we don't need a timeout on the tcpstream for
         // any reads because we sit behind a proxy-like thing
         // (e.g. nginx) that applies reasonable read/write timeouts
         // for HTTP clients.
-
-        let mut header_byte_count = 0;
-        let mut parsed_req = None;
-        let mut content_length = 0;
-        let mut chars: Vec<u8> = Vec::new();
-
-        loop {
-            // Pull a chunk of bytes from the stream and see what we can
-            // do with it.
-            let mut buffer = [0u8; BUFSIZE];
-
-            let num_bytes = request
-                .stream
-                .read(&mut buffer)
-                .or_else(|e| Err(format!("Error reading HTTP stream: {e}")))?;
-
-            log::trace!("Read {num_bytes} from the TCP stream");
-
-            for c in buffer.iter() {
-                if *c == 0 {
-                    // Drop any trailing '\0' chars.
-                    break;
-                }
-                chars.push(*c);
-            }
-
-            if parsed_req.is_none() {
-                // Parse the headers and extract the values we care about.
-
-                let mut headers = [httparse::EMPTY_HEADER; 64];
-                let mut req = httparse::Request::new(&mut headers);
-
-                log::trace!(
-                    "Parsing chars: {}",
-                    String::from_utf8_lossy(chars.as_slice())
-                );
-
-                let res = req
-                    .parse(chars.as_slice())
-                    .or_else(|e| Err(format!("Error readong HTTP headers: {e}")))?;
-
-                if res.is_partial() {
-                    // We haven't read enough header data yet.
-                    // Go back to pulling bytes from the socket.
-                    continue;
-                }
-
-                // httparse::Result contains the byte count of the header
-                // once full parsed.
-                header_byte_count = res.unwrap();
-
-                for header in req.headers.iter() {
-                    if header.name.to_lowercase().as_str() == "content-length" {
-                        let len = String::from_utf8_lossy(&header.value);
-                        if let Ok(size) = len.parse::<usize>() {
-                            content_length = size;
-                            break;
-                        }
-                    }
-                }
-
-                let method = req
-                    .method
-                    .map(|v| v.to_string())
-                    .ok_or(format!("Invalid HTTP request"))?;
-
-                let path = req
-                    .path
-                    .map(|v| v.to_string())
-                    .ok_or(format!("Invalid HTTP request"))?;
-
-                parsed_req = Some(ParsedHttpRequest {
-                    method,
-                    path,
-                    body: None,
-                });
-            }
-
-            if chars.len() == header_byte_count {
-                // We have read zero bytes of body data.
-                // There may be none to read.
-
-                if content_length == 0 {
-                    return Ok(parsed_req.take().unwrap());
-                }
-
-                // We have a non-zero content-length.
-                // Keep reading data.
-                continue;
-            }
-
-            let body_bytes = &chars[header_byte_count..];
-            let body_byte_count = body_bytes.len();
-
-            log::trace!("Read {body_byte_count} body bytes, want {content_length}");
-
-            if body_byte_count == content_length {
-                // We've read all the body data.
-                let mut parsed_req = parsed_req.take().unwrap();
-
-                parsed_req.body = Some(String::from_utf8_lossy(body_bytes).to_string());
-
-                return Ok(parsed_req);
-            }
-
-            if body_byte_count > content_length {
-                return Err(format!("Content exceeds Content-Length header value").into());
-            }
-
-            // Keep reading data until body_byte_count >= content_length
-        }
+        eg::util::read_http_request(&mut request.stream)
     }
 
     /// Translate a raw gateway request String into a ParsedGatewayRequest.
@@ -396,7 +299,7 @@ impl GatewayHandler {
     /// * `request` - Full HTTP request text including headers, etc.
     ///
     /// Returns Err if the request cannot be translated.
-    fn parse_request(&self, http_req: ParsedHttpRequest) -> EgResult<ParsedGatewayRequest> {
+    fn parse_request(&self, http_req: HttpRequest) -> EgResult<ParsedGatewayRequest> {
         let url_params = match http_req.body {
             // POST params are in the body
             Some(b) => format!("{}?{}", DUMMY_BASE_URL, &b),
@@ -618,6 +521,8 @@ fn main() {
         .init()
         .expect("Logger Init");
 
+    metrics::start_listener(gateway_conf.metrics());
+
     let stream = GatewayStream::new(&address, port).expect("Build stream");
     let mut server = mptc::Server::new(Box::new(stream));
 
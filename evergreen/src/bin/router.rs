@@ -16,21 +16,34 @@ use eg::init;
 use eg::osrf::addr::BusAddress;
 use eg::osrf::bus::Bus;
 use eg::osrf::conf;
+use eg::osrf::conf::RoutingStrategy;
 use eg::osrf::logging::Logger;
 use eg::osrf::message;
-use eg::osrf::message::{Message, MessageStatus, MessageType, Payload, Status, TransportMessage};
+use eg::osrf::message::{
+    Message, MessageStatus, MessageType, MethodCall, Payload, Status, TransportMessage,
+};
+use eg::osrf::metrics;
+use eg::osrf::trace::{self, SpanKind};
+use eg::util;
 use eg::EgResult;
 use eg::EgValue;
 use evergreen as eg;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::fmt;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// How often do we wake from listening for messages and give shutdown
 /// signals a chance to propagate.
 const POLL_TIMEOUT: i32 = 5;
 
+/// How many minutes of per-service route counts we retain.
+const ROUTE_HISTORY_MINUTES: u64 = 60;
+
+/// How often, in seconds, we sample service instance queue depths.
+const QUEUE_SAMPLE_INTERVAL: u64 = 5;
+
 /// A service instance.
 ///
 /// This is what we traditionally call a "Listener" in OpenSRF.
@@ -55,9 +68,69 @@ struct ServiceInstance {
 
     /// When was this instance registered with the router.
     register_time: date::EgDate,
+
+    /// Relative capacity of this instance for weighted routing.
+    weight: u32,
+
+    /// Running score used for smooth weighted round-robin.
+    current_weight: i64,
+
+    /// Number of requests waiting in our listen queue as of the last
+    /// queue sample, plus requests routed to us since.
+    backlog: i32,
+
+    /// Value at the front of our listen queue as of the last health check.
+    queue_head: Option<String>,
+
+    /// When we first saw the current queue_head.
+    queue_head_since: Option<Instant>,
+
+    /// If true, no new requests are routed to this instance.
+    drained: bool,
+
+    /// Thread of the ping we sent because our queue stopped draining,
+    /// and when we sent it.
+    ping: Option<(String, Instant)>,
+
+    /// True if our queue stopped draining and we did not answer a ping.
+    ///
+    /// Suspended instances remain registered, but receive no new
+    /// requests while any other instance of the service is available.
+    suspended: bool,
 }
 
 impl ServiceInstance {
+    fn new(address: BusAddress, listen_address: BusAddress, weight: u32) -> Self {
+        ServiceInstance {
+            address,
+            listen_address,
+            route_count: 0,
+            register_time: date::now(),
+            weight,
+            current_weight: 0,
+            backlog: 0,
+            queue_head: None,
+            queue_head_since: None,
+            drained: false,
+            ping: None,
+            suspended: false,
+        }
+    }
+
+    /// True if new requests may be routed to this instance.
+    ///
+    /// Suspended instances are only skipped when 'skip_suspended' is set,
+    /// i.e. when a healthy instance is available instead.
+    fn routable(&self, skip_suspended: bool) -> bool {
+        !self.drained && (!skip_suspended || !self.suspended)
+    }
+
+    /// Forget any outstanding ping and start timing our queue anew.
+    fn mark_alive(&mut self) {
+        self.ping = None;
+        self.queue_head_since = self.queue_head.as_ref().map(|_| Instant::now());
+    }
+
     fn address(&self) -> &BusAddress {
         &self.address
     }
@@ -68,12 +141,105 @@ impl ServiceInstance {
         &self.register_time
     }
 
+    /// Returns true if our listen queue has gone at least 'timeout'
+    /// without draining, i.e. nothing is popping requests from it.
+    fn queue_is_stale(&mut self, bus: &mut Bus, timeout: Duration) -> bool {
+        let head = match bus.lrange(self.listen_address.as_str(), 0, 0) {
+            Ok(mut list) => list.pop(),
+            Err(e) => {
+                log::warn!("Cannot check queue for {}: {e}", self.listen_address);
+                return false;
+            }
+        };
+
+        let head = match head {
+            Some(h) => h,
+            None => {
+                // Empty queue.  All is well.
+                self.queue_head = None;
+                self.queue_head_since = None;
+                return false;
+            }
+        };
+
+        if self.queue_head.as_ref() != Some(&head) {
+            // The queue is moving.
+            self.queue_head = Some(head);
+            self.queue_head_since = Some(Instant::now());
+            return false;
+        }
+
+        self.queue_head_since
+            .map(|t| t.elapsed() >= timeout)
+            .unwrap_or(false)
+    }
+
     fn to_json_value(&self) -> json::JsonValue {
         json::object! {
             "route_count": self.route_count,
             "address": self.address().as_str(),
             "listen_address": self.listen_address().as_str(),
             "register_time": date::to_iso(self.register_time()),
+            "weight": self.weight,
+            "backlog": self.backlog,
+            "drained": self.drained,
+            "suspended": self.suspended,
+            "stalled_secs": self.queue_head_since.map(|t| t.elapsed().as_secs()).unwrap_or(0),
+        }
+    }
+}
+
+/// Per-minute route counts covering the last ROUTE_HISTORY_MINUTES.
+#[derive(Debug, Clone, Default)]
+struct RouteHistory {
+    /// (minutes since the epoch, routes during that minute), oldest first.
+    buckets: VecDeque<(u64, usize)>,
+}
+
+impl RouteHistory {
+    fn current_minute() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() / 60)
+            .unwrap_or(0)
+    }
+
+    /// Record one route.
+    fn add(&mut self) {
+        let minute = RouteHistory::current_minute();
+
+        match self.buckets.back_mut() {
+            Some((m, count)) if *m == minute => *count += 1,
+            _ => self.buckets.push_back((minute, 1)),
+        }
+
+        while let Some((m, _)) = self.buckets.front() {
+            if m + ROUTE_HISTORY_MINUTES <= minute {
+                self.buckets.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Number of routes within the last 'minutes' minutes, including
+    /// the current minute.
+    fn count(&self, minutes: u64) -> usize {
+        let minute = RouteHistory::current_minute();
+
+        self.buckets
+            .iter()
+            .filter(|(m, _)| m + minutes > minute)
+            .map(|(_, c)| c)
+            .sum()
+    }
+
+    fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            "1m": self.count(1),
+            "5m": self.count(5),
+            "15m": self.count(15),
+            "60m": self.count(60),
         }
     }
 }
@@ -95,6 +261,12 @@ struct ServiceEntry {
 
     /// How many API requests have been routed to this service.
     route_count: usize,
+
+    /// How we choose which instance receives each request.
+    strategy: RoutingStrategy,
+
+    /// Recent routing activity.
+    route_history: RouteHistory,
 }
 
 impl ServiceEntry {
@@ -106,23 +278,140 @@ impl ServiceEntry {
         &self.instances
     }
 
-    /// Returns the next round-robin service instanace and
-    /// increments our route count if we have an instance to return.
+    /// Returns the service instance which should receive the next
+    /// request and increments our route count if we have an instance
+    /// to return.
     fn next_instance(&mut self) -> Option<&ServiceInstance> {
-        if self.instance_index >= self.instances.len() {
-            self.instance_index = 0;
+        if !self.instances.iter().any(|i| i.routable(false)) {
+            return None;
         }
 
-        let instance = match self.instances.get_mut(self.instance_index) {
-            Some(i) => i,
-            None => return None,
+        // Prefer healthy instances, but queue requests on a suspended
+        // instance rather than reject them outright.
+        let skip = self.instances.iter().any(|i| i.routable(true));
+
+        let index = match self.strategy {
+            RoutingStrategy::RoundRobin => self.round_robin_index(skip),
+            RoutingStrategy::LeastLoaded => self.least_loaded_index(skip),
+            RoutingStrategy::Weighted => self.weighted_index(skip),
         };
 
+        let instance = self.instances.get_mut(index)?;
+
         instance.route_count += 1;
+
+        // Count the request as outstanding until the next queue sample.
+        instance.backlog += 1;
         self.route_count += 1;
+        self.route_history.add();
 
         // Now return the non-mut version
-        self.instances.get(self.instance_index)
+        self.instances.get(index)
+    }
+
+    /// Next routable instance in rotation.
+    ///
+    /// Requires at least one routable instance.
+    fn round_robin_index(&mut self, skip_suspended: bool) -> usize {
+        loop {
+            if self.instance_index >= self.instances.len() {
+                self.instance_index = 0;
+            }
+
+            let index = self.instance_index;
+            self.instance_index += 1;
+
+            if self.instances[index].routable(skip_suspended) {
+                return index;
+            }
+        }
+    }
+
+    /// Index of the instance with the shortest request queue, favoring
+    /// instances which have received fewer requests overall on ties.
+    ///
+    /// Queue lengths are sampled periodically instead of per request
+    /// to keep bus round trips off the routing path.
+    fn least_loaded_index(&mut self, skip_suspended: bool) -> usize {
+        self.instances
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.routable(skip_suspended))
+            .min_by_key(|(_, i)| (i.backlog, i.route_count))
+            .map(|(idx, _)| idx)
+            .unwrap_or(0)
+    }
+
+    /// Smooth weighted round-robin.
+    ///
+    /// Every instance gains its weight on each pass and the instance
+    /// with the highest running score is chosen, then penalized by
+    /// the sum of all weights.
+    fn weighted_index(&mut self, skip_suspended: bool) -> usize {
+        let mut total = 0;
+        let mut index: Option<usize> = None;
+
+        for instance in self.instances.iter_mut() {
+            if !instance.routable(skip_suspended) {
+                continue;
+            }
+
+            instance.current_weight += instance.weight as i64;
+            total += instance.weight as i64;
+        }
+
+        for (idx, instance) in self.instances.iter().enumerate() {
+            if !instance.routable(skip_suspended) {
+                continue;
+            }
+
+            let best = match index {
+                Some(i) => &self.instances[i],
+                None => {
+                    index = Some(idx);
+                    continue;
+                }
+            };
+
+            if instance.current_weight > best.current_weight {
+                index = Some(idx);
+            }
+        }
+
+        let index = index.unwrap_or(0);
+
+        self.instances[index].current_weight -= total;
+
+        index
+    }
+
+    /// Stop or resume routing requests to our instances.
+    ///
+    /// Applies to all instances unless an instance address is provided.
+    /// Returns the number of instances modified.
+    fn set_drained(&mut self, drained: bool, address: Option<&str>) -> usize {
+        let mut count = 0;
+
+        for instance in self.instances.iter_mut() {
+            if let Some(a) = address {
+                if instance.address.as_str() != a {
+                    continue;
+                }
+            }
+
+            if instance.drained != drained {
+                log::info!(
+                    "{} routing to service={} address={}",
+                    if drained { "Draining" } else { "Resuming" },
+                    self.name,
+                    instance.address
+                );
+                instance.drained = drained;
+                count += 1;
+            }
+        }
+
+        count
     }
 
     /// Remove a specific service instance from the set
@@ -152,6 +441,8 @@ impl ServiceEntry {
         json::object! {
             "name": self.name(),
             "route_count": self.route_count,
+            "strategy": <&str>::from(self.strategy),
+            "recent_route_counts": self.route_history.to_json_value(),
             "instances": self.instances()
                 .iter()
                 .map(|s| s.to_json_value())
@@ -218,18 +509,163 @@ impl RouterDomain {
         &self.services
     }
 
-    /// Get a service by service name and increment our route count.
-    fn get_service_mut(&mut self, name: &str) -> Option<&mut ServiceEntry> {
-        let has_any = self.services.iter().any(|s| s.name().eq(name));
+    fn has_service(&self, name: &str) -> bool {
+        self.services.iter().any(|s| s.name().eq(name))
+    }
 
-        if has_any {
-            self.route_count += 1;
-            self.services
-                .iter_mut()
-                .filter(|s| s.name().eq(name))
-                .next()
-        } else {
-            None
+    /// Choose an instance of the named service to receive a request
+    /// and increment our route count.
+    ///
+    /// Returns the listen address of the chosen instance.
+    fn next_instance(&mut self, name: &str) -> Option<BusAddress> {
+        let svc = self.services.iter_mut().find(|s| s.name().eq(name))?;
+        let instance = svc.next_instance()?;
+
+        self.route_count += 1;
+
+        Some(instance.listen_address().clone())
+    }
+
+    /// Suspend service instances whose listen queue has not drained
+    /// within 'timeout' and which do not answer a ping within another
+    /// 'timeout', and resume suspended instances which are draining
+    /// their queues again.
+    ///
+    /// Returns the service name, address, and new suspended state of
+    /// each instance whose state changed.
+    fn check_health(&mut self, timeout: Duration) -> Vec<(String, BusAddress, bool)> {
+        let mut changes = Vec::new();
+
+        // We only check domains we have communicated with.
+        let bus = match self.bus.as_mut() {
+            Some(b) => b,
+            None => return changes,
+        };
+
+        // Collect replies to pings we have sent.  Replies are sent to
+        // our domain-specific bus address.
+        let mut replies = HashSet::new();
+
+        loop {
+            match bus.recv(0, None) {
+                Ok(Some(tm)) => {
+                    replies.insert(tm.thread().to_string());
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Cannot read ping replies on {}: {e}", self.domain);
+                    break;
+                }
+            }
+        }
+
+        let from = bus.address().as_str().to_string();
+
+        for svc in self.services.iter_mut() {
+            for instance in svc.instances.iter_mut() {
+                let answered = instance
+                    .ping
+                    .as_ref()
+                    .map(|(thread, _)| replies.contains(thread))
+                    .unwrap_or(false);
+
+                if answered || !instance.queue_is_stale(bus, timeout) {
+                    // Alive, albeit possibly busy.
+                    if answered {
+                        instance.mark_alive();
+                    } else {
+                        instance.ping = None;
+                    }
+
+                    if instance.suspended {
+                        instance.suspended = false;
+                        changes.push((svc.name.to_string(), instance.address.clone(), false));
+                    }
+
+                    continue;
+                }
+
+                if instance.suspended {
+                    continue;
+                }
+
+                let sent = match instance.ping.as_ref() {
+                    Some((_, sent)) => *sent,
+                    None => {
+                        // The ping goes to the back of the queue.  A
+                        // reply means the instance is still working
+                        // its way through its requests.
+                        let thread = util::random_number(16);
+
+                        let method =
+                            MethodCall::new("opensrf.system.echo", vec![EgValue::from("ping")]);
+
+                        let tm = TransportMessage::with_body(
+                            instance.listen_address.as_str(),
+                            &from,
+                            &thread,
+                            Message::new(MessageType::Request, 1, Payload::Method(method)),
+                        );
+
+                        if let Err(e) = bus.send(tm) {
+                            log::warn!("Cannot ping {}: {e}", instance.listen_address);
+                            continue;
+                        }
+
+                        instance.ping = Some((thread, Instant::now()));
+
+                        continue;
+                    }
+                };
+
+                if sent.elapsed() >= timeout {
+                    instance.suspended = true;
+                    changes.push((svc.name.to_string(), instance.address.clone(), true));
+                }
+            }
+        }
+
+        changes
+    }
+
+    /// Refresh the backlog of each service instance and report the
+    /// queue depth and instance count of each service.
+    fn sample_queues(&mut self) {
+        // We only check domains we have communicated with.
+        let bus = match self.bus.as_mut() {
+            Some(b) => b,
+            None => return,
+        };
+
+        for svc in self.services.iter_mut() {
+            let mut queue_depth = 0;
+
+            for instance in svc.instances.iter_mut() {
+                match bus.llen(instance.listen_address.as_str()) {
+                    Ok(n) => instance.backlog = n,
+                    Err(e) => {
+                        log::warn!("Cannot read backlog for {}: {e}", instance.listen_address)
+                    }
+                }
+
+                queue_depth += instance.backlog;
+            }
+
+            let labels = [("domain", self.domain.as_str()), ("service", svc.name())];
+
+            metrics::set_gauge(
+                "osrf_router_queue_depth",
+                "Requests waiting in service instance queues",
+                &labels,
+                queue_depth as f64,
+            );
+
+            metrics::set_gauge(
+                "osrf_router_service_instances",
+                "Registered service instances",
+                &labels,
+                svc.instances.len() as f64,
+            );
         }
     }
 
@@ -250,6 +686,11 @@ impl RouterDomain {
                 if let Some(s_pos) = self.services.iter().position(|s| s.name().eq(service)) {
                     self.services.remove(s_pos);
                 }
+
+                // Stop reporting on the departed service.
+                let labels = [("domain", self.domain.as_str()), ("service", service)];
+                metrics::remove("osrf_router_queue_depth", &labels);
+                metrics::remove("osrf_router_service_instances", &labels);
             }
         }
     }
@@ -320,6 +761,31 @@ struct Router {
 
     /// Which domains can send requests our way.
     trusted_client_domains: Vec<String>,
+
+    /// Which domains can call our admin APIs.
+    admin_domains: Vec<String>,
+
+    /// Our configuration.
+    router_conf: conf::Router,
+
+    /// When we last checked the health of our service instances.
+    last_health_check: Instant,
+
+    /// When we last sampled service instance queue depths.
+    last_queue_sample: Instant,
+
+    /// How many health checks we have performed.
+    health_check_count: usize,
+
+    /// How many times we have suspended an unresponsive service instance.
+    suspension_count: usize,
+
+    /// How many requests we have bounced back to the caller because
+    /// no instance of the service was available, keyed on service.
+    ///
+    /// Bounces for services we have never seen are counted under
+    /// metrics::UNKNOWN_SERVICE.
+    bounce_counts: HashMap<String, usize>,
 }
 
 impl fmt::Display for Router {
@@ -342,6 +808,7 @@ impl Router {
 
         let tsd = router_conf.trusted_server_domains().clone();
         let tcd = router_conf.trusted_client_domains().clone();
+        let admin_domains = router_conf.admin_domains().clone();
 
         let busconf = router_conf.client();
 
@@ -354,8 +821,15 @@ impl Router {
             primary_domain,
             trusted_server_domains: tsd,
             trusted_client_domains: tcd,
+            admin_domains,
             listen_address: addr,
             remote_domains: Vec::new(),
+            router_conf: router_conf.clone(),
+            last_health_check: Instant::now(),
+            last_queue_sample: Instant::now(),
+            health_check_count: 0,
+            suspension_count: 0,
+            bounce_counts: HashMap::new(),
         }
     }
 
@@ -377,6 +851,9 @@ impl Router {
     fn to_json_value(&self) -> json::JsonValue {
         json::object! {
             "listen_address": self.listen_address.as_str(),
+            "health_check_count": self.health_check_count,
+            "suspension_count": self.suspension_count,
+            "bounce_counts": json::from(self.bounce_counts.clone()),
             "primary_domain": self.primary_domain().to_json_value(),
             "remote_domains": self.remote_domains()
                 .iter()
@@ -475,6 +952,11 @@ impl Router {
             .into());
         }
 
+        let strategy = self.router_conf.routing_strategy(service);
+        let weight = self.router_conf.domain_weight(domain);
+
+        metrics::add_known_service(service);
+
         let r_domain = self.find_or_create_domain(domain)?;
 
         // Where our new instance will listen for routed API calls.
@@ -491,6 +973,11 @@ impl Router {
                             "instance with address {} already registered for service {} and domain {}",
                             address.as_str(), service, domain
                         );
+
+                        // Registering again means the instance is alive.
+                        instance.mark_alive();
+                        instance.suspended = false;
+
                         return Ok(());
                     }
                 }
@@ -502,12 +989,8 @@ impl Router {
                     address.as_str()
                 );
 
-                svc.instances.push(ServiceInstance {
-                    address,
-                    listen_address,
-                    route_count: 0,
-                    register_time: date::now(),
-                });
+                svc.instances
+                    .push(ServiceInstance::new(address, listen_address, weight));
 
                 return Ok(());
             }
@@ -527,12 +1010,9 @@ impl Router {
             name: service.to_string(),
             route_count: 0,
             instance_index: 0,
-            instances: vec![ServiceInstance {
-                address,
-                listen_address,
-                route_count: 0,
-                register_time: date::now(),
-            }],
+            strategy,
+            route_history: RouteHistory::default(),
+            instances: vec![ServiceInstance::new(address, listen_address, weight)],
         });
 
         Ok(())
@@ -566,7 +1046,7 @@ impl Router {
         // domain and route accordingly.
 
         loop {
-            let tm = match self.recv_one() {
+            let tm_op = match self.recv_one() {
                 Ok(m) => m,
                 Err(s) => {
                     log::error!("Exiting. Error receiving data from primary connection: {s}");
@@ -574,9 +1054,78 @@ impl Router {
                 }
             };
 
-            if let Err(s) = self.route_message(tm) {
-                log::error!("Error routing message: {}", s);
+            if let Some(tm) = tm_op {
+                if let Err(s) = self.route_message(tm) {
+                    log::error!("Error routing message: {}", s);
+                }
             }
+
+            self.check_health();
+            self.sample_queues();
+        }
+    }
+
+    /// Sample the queue depths of our service instances every
+    /// QUEUE_SAMPLE_INTERVAL seconds.
+    fn sample_queues(&mut self) {
+        if self.last_queue_sample.elapsed() < Duration::from_secs(QUEUE_SAMPLE_INTERVAL) {
+            return;
+        }
+
+        self.last_queue_sample = Instant::now();
+
+        for r_domain in self.domains_mut() {
+            r_domain.sample_queues();
+        }
+    }
+
+    /// Stop routing to service instances which are no longer processing
+    /// requests.
+    ///
+    /// A service instance whose listen queue has not drained within the
+    /// configured timeout is sent an opensrf.system.echo ping.  If the
+    /// ping goes unanswered for another timeout, the instance is
+    /// suspended.  Suspended instances remain registered and are
+    /// resumed once their queue drains, they answer the ping, or they
+    /// register again.
+    ///
+    /// Disabled unless a health check interval is configured.
+    fn check_health(&mut self) {
+        let interval = self.router_conf.health_check_interval();
+
+        if interval == 0 || self.last_health_check.elapsed() < Duration::from_secs(interval) {
+            return;
+        }
+
+        self.last_health_check = Instant::now();
+        self.health_check_count += 1;
+
+        let timeout = Duration::from_secs(self.router_conf.stale_queue_timeout());
+
+        let mut changes = Vec::new();
+
+        for r_domain in self.domains_mut() {
+            changes.append(&mut r_domain.check_health(timeout));
+        }
+
+        for (service, address, suspended) in changes {
+            if !suspended {
+                log::info!("{self} resuming instance service={service} address={address}");
+                continue;
+            }
+
+            log::warn!(
+                "{self} suspending unresponsive instance service={service} address={address}"
+            );
+
+            self.suspension_count += 1;
+
+            metrics::inc_counter(
+                "osrf_router_suspensions",
+                "Unresponsive service instances suspended",
+                &[("service", service.as_str())],
+                1,
+            );
         }
     }
 
@@ -620,19 +1169,18 @@ impl Router {
         }
 
         let client_addr = BusAddress::from_str(tm.from())?;
-        let client_domain = client_addr.domain();
 
-        let trusted = self
-            .trusted_client_domains
-            .iter()
-            .any(|d| d == client_domain);
+        self.verify_trusted_client(&client_addr)?;
 
-        if !trusted {
-            return Err(format!(
-                r#"Domain {client_domain} is not a trusted client domain for this
-                router {client_addr} : {self}"#
-            )
-            .into());
+        // Record the forwarding hop as a child of the caller's span
+        // and pass our span along as the parent of the service call.
+        trace::set_remote_context(tm.traceparent());
+
+        let mut span = trace::Span::enter(&format!("osrf.route {service}"), SpanKind::Internal);
+        span.set_attr("osrf.service", service);
+
+        if let Some(tp) = span.traceparent() {
+            tm.set_traceparent(Some(&tp));
         }
 
         // The recipient address for a routed API call will not include
@@ -641,31 +1189,44 @@ impl Router {
         // instance destination below and use its listen_address as the
         // destination.
 
-        if let Some(svc) = self.primary_domain.get_service_mut(service) {
-            if let Some(instance) = svc.next_instance() {
-                tm.set_to(instance.listen_address().as_str());
-                return self.primary_domain.send_to_domain(tm);
-            }
+        if let Some(listen_address) = self.primary_domain.next_instance(service) {
+            count_request(service, "routed");
+            tm.set_to(listen_address.as_str());
+            return self.primary_domain.send_to_domain(tm);
         }
 
         for r_domain in &mut self.remote_domains {
-            let has_bus = r_domain.bus.is_some();
-
-            if let Some(svc) = r_domain.get_service_mut(service) {
-                if let Some(instance) = svc.next_instance() {
-                    tm.set_to(instance.listen_address().as_str());
+            if !r_domain.has_service(service) {
+                continue;
+            }
 
-                    if !has_bus {
-                        // We only connect to remote domains when it's
-                        // time to send them a message.
-                        r_domain.connect()?;
-                    }
+            // We only connect to remote domains when it's
+            // time to send them a message.
+            r_domain.connect()?;
 
-                    return r_domain.send_to_domain(tm);
-                }
+            if let Some(listen_address) = r_domain.next_instance(service) {
+                count_request(service, "routed");
+                tm.set_to(listen_address.as_str());
+                return r_domain.send_to_domain(tm);
             }
         }
 
+        // The service name comes from the caller.  Only label and
+        // count by name services which have registered at some point.
+        let label = metrics::service_label(service);
+
+        count_request(label, "not_found");
+        span.set_error("service not found");
+
+        *self.bounce_counts.entry(label.to_string()).or_insert(0) += 1;
+
+        metrics::inc_counter(
+            "osrf_router_bounces",
+            "Requests returned to the caller undelivered",
+            &[("service", label)],
+            1,
+        );
+
         log::error!(
             "Router at {} has no service instances for service {service}",
             self.primary_domain.domain()
@@ -703,6 +1264,47 @@ impl Router {
         self.primary_domain.send_to_domain(tm)
     }
 
+    /// Returns Err if the client's domain is not a trusted client domain.
+    fn verify_trusted_client(&self, client_addr: &BusAddress) -> EgResult<()> {
+        let client_domain = client_addr.domain();
+
+        let trusted = self
+            .trusted_client_domains
+            .iter()
+            .any(|d| d == client_domain);
+
+        if !trusted {
+            return Err(format!(
+                r#"Domain {client_domain} is not a trusted client domain for this
+                router {client_addr} : {self}"#
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Returns Err if the client's domain may not call the router
+    /// admin APIs.
+    ///
+    /// Any trusted client may route requests through us, so admin
+    /// access requires a domain configured as an admin domain, which
+    /// defaults to the trusted server domains.
+    fn verify_admin_client(&self, client_addr: &BusAddress) -> EgResult<()> {
+        let client_domain = client_addr.domain();
+
+        let admin = self.admin_domains.iter().any(|d| d == client_domain);
+
+        if !admin {
+            return Err(format!(
+                "Domain {client_domain} may not call admin APIs on this router {client_addr} : {self}"
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
     /// Some Router requests are packaged as method calls.  Handle those here.
     fn handle_router_api_request(&mut self, tm: TransportMessage) -> EgResult<()> {
         let from = tm.from();
@@ -719,7 +1321,7 @@ impl Router {
                 }
             };
 
-            let value = self.process_router_api_request(&method)?;
+            let value = self.process_router_api_request(from, &method)?;
 
             let reply = Message::new(
                 MessageType::Result,
@@ -755,7 +1357,18 @@ impl Router {
         Ok(())
     }
 
-    fn process_router_api_request(&mut self, m: &message::MethodCall) -> EgResult<json::JsonValue> {
+    fn process_router_api_request(
+        &mut self,
+        from: &str,
+        m: &message::MethodCall,
+    ) -> EgResult<json::JsonValue> {
+        if m.method().starts_with("opensrf.router.admin.") {
+            self.verify_admin_client(&BusAddress::from_str(from)?)?;
+        }
+
+        // Most APIs take a service name and/or instance address.
+        let param = |idx: usize| m.params().get(idx).and_then(|p| p.as_str());
+
         match m.method() {
             "opensrf.router.info.class.list" => {
                 // Caller wants a list of service names
@@ -770,10 +1383,120 @@ impl Router {
                 Ok(json::from(names))
             }
             "opensrf.router.info.summarize" => Ok(self.to_json_value()),
+            "opensrf.router.info.service" => {
+                let service = param(0).ok_or("Service name required")?;
+                Ok(self.service_details(service))
+            }
+            "opensrf.router.info.route_counts" => Ok(self.route_counts()),
+            "opensrf.router.admin.service.drain" => {
+                let service = param(0).ok_or("Service name required")?;
+                Ok(json::from(self.set_drained(service, param(1), true)))
+            }
+            "opensrf.router.admin.service.resume" => {
+                let service = param(0).ok_or("Service name required")?;
+                Ok(json::from(self.set_drained(service, param(1), false)))
+            }
+            "opensrf.router.admin.instance.unregister" => {
+                let service = param(0).ok_or("Service name required")?;
+                let address = param(1).ok_or("Instance address required")?;
+
+                log::info!("{self} unregistering {address} at the request of {from}");
+
+                self.handle_unregister(&BusAddress::from_str(address)?, service)?;
+
+                Ok(json::from(true))
+            }
+            "opensrf.router.admin.trusted_domains.reload" => {
+                self.reload_trusted_domains()?;
+
+                Ok(json::object! {
+                    "server": self.trusted_server_domains.clone(),
+                    "client": self.trusted_client_domains.clone(),
+                    "admin": self.admin_domains.clone(),
+                })
+            }
             _ => Err(format!("Router cannot handle api {}", m.method()).into()),
         }
     }
 
+    /// All domains we route for, primary domain first.
+    fn domains_mut(&mut self) -> impl Iterator<Item = &mut RouterDomain> {
+        std::iter::once(&mut self.primary_domain).chain(self.remote_domains.iter_mut())
+    }
+
+    /// Registration details for a service across all of our domains.
+    fn service_details(&self, service: &str) -> json::JsonValue {
+        let mut list = json::JsonValue::new_array();
+
+        for r_domain in std::iter::once(&self.primary_domain).chain(self.remote_domains.iter()) {
+            for svc in r_domain.services().iter().filter(|s| s.name() == service) {
+                let mut value = svc.to_json_value();
+                value["domain"] = json::from(r_domain.domain());
+                list.push(value).ok();
+            }
+        }
+
+        list
+    }
+
+    /// Recent route counts per domain and service.
+    fn route_counts(&self) -> json::JsonValue {
+        let mut counts = json::JsonValue::new_object();
+
+        for r_domain in std::iter::once(&self.primary_domain).chain(self.remote_domains.iter()) {
+            let mut services = json::JsonValue::new_object();
+
+            for svc in r_domain.services() {
+                services[svc.name()] = svc.route_history.to_json_value();
+            }
+
+            counts[r_domain.domain()] = services;
+        }
+
+        counts
+    }
+
+    /// Stop or resume routing requests to a service, or a single
+    /// instance of a service, across all of our domains.
+    ///
+    /// Returns the number of instances modified.
+    fn set_drained(&mut self, service: &str, address: Option<&str>, drained: bool) -> usize {
+        let mut count = 0;
+
+        for r_domain in self.domains_mut() {
+            for svc in r_domain.services.iter_mut().filter(|s| s.name() == service) {
+                count += svc.set_drained(drained, address);
+            }
+        }
+
+        count
+    }
+
+    /// Re-read our trusted server, client, and admin domain lists from
+    /// the OpenSRF config file.
+    fn reload_trusted_domains(&mut self) -> EgResult<()> {
+        let config = conf::ConfigBuilder::from_file(&init::osrf_config_file())?.build()?;
+
+        let domain = self.primary_domain.domain().to_string();
+
+        let router_conf = config
+            .get_router_conf(&domain)
+            .ok_or_else(|| format!("No router config for domain {domain}"))?;
+
+        self.trusted_server_domains = router_conf.trusted_server_domains().clone();
+        self.trusted_client_domains = router_conf.trusted_client_domains().clone();
+        self.admin_domains = router_conf.admin_domains().clone();
+
+        log::info!(
+            "{self} reloaded trusted domains server={:?} client={:?} admin={:?}",
+            self.trusted_server_domains,
+            self.trusted_client_domains,
+            self.admin_domains
+        );
+
+        Ok(())
+    }
+
     /// Register, Un-Register, etc. services
     fn handle_router_command(&mut self, tm: TransportMessage) -> EgResult<()> {
         let router_command = match tm.router_command() {
@@ -810,25 +1533,29 @@ impl Router {
     }
 
     /// Receive the next message destined for this router on this
-    /// domain, breaking periodically to check for shutdown, etc.
-    /// signals.
-    fn recv_one(&mut self) -> EgResult<TransportMessage> {
+    /// domain.
+    ///
+    /// Returns None after POLL_TIMEOUT seconds with no message so the
+    /// caller can perform periodic maintenance.
+    fn recv_one(&mut self) -> EgResult<Option<TransportMessage>> {
         let bus = self
             .primary_domain
             .bus_mut()
             .expect("We always maintain a connection on the primary domain");
 
-        loop {
-            // Break periodically
-            let tm_op = bus.recv(POLL_TIMEOUT, Some(self.listen_address.as_str()))?;
-
-            if let Some(tm) = tm_op {
-                return Ok(tm);
-            }
-        }
+        bus.recv(POLL_TIMEOUT, Some(self.listen_address.as_str()))
     }
 }
 
+fn count_request(service: &str, result: &str) {
+    metrics::inc_counter(
+        "osrf_router_requests",
+        "API requests received for each service",
+        &[("service", service), ("result", result)],
+        1,
+    );
+}
+
 fn main() {
     // Prefer router-specific logging to the default client logging
     let init_ops = init::InitOptions {
@@ -872,6 +1599,16 @@ fn main() {
         panic!("Error initializing logger: {}", e);
     }
 
+    metrics::start_listener(rconf.client().metrics());
+
+    // Router-specific tracing config applies when the default
+    // client config does not export spans.
+    if !trace::enabled() {
+        if let Err(e) = trace::init("router", rconf.client().trace_export()) {
+            panic!("Cannot start trace exporter: {e}");
+        }
+    }
+
     // A router for each specified domain runs within its own thread.
     let mut threads: Vec<thread::JoinHandle<()>> = Vec::new();
 
@@ -909,3 +1646,155 @@ fn start_one_domain(domain: String) -> thread::JoinHandle<()> {
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eg::osrf::app::{Application, ApplicationWorker, ApplicationWorkerFactory};
+    use eg::osrf::membus;
+    use eg::osrf::method::MethodDef;
+    use eg::osrf::sclient::HostSettings;
+    use eg::osrf::server::Server;
+    use eg::{Client, EgError};
+    use std::any::Any;
+    use std::sync::Arc;
+
+    const CONFIG_XML: &str = r#"
+<config>
+  <opensrf>
+    <domain>localhost</domain>
+    <username>opensrf</username>
+    <passwd>password</passwd>
+    <routers>
+      <router>
+        <name>router</name>
+        <domain>localhost</domain>
+      </router>
+    </routers>
+  </opensrf>
+  <routers>
+    <router>
+      <trusted_domains>
+        <server>localhost</server>
+        <client>localhost</client>
+      </trusted_domains>
+      <transport>
+        <domain>localhost</domain>
+        <username>router</username>
+        <password>password</password>
+      </transport>
+    </router>
+  </routers>
+</config>
+"#;
+
+    /// Service which publishes only the system methods.
+    struct TestApplication;
+
+    impl Application for TestApplication {
+        fn name(&self) -> &str {
+            "test.echo"
+        }
+
+        fn init(&mut self, _client: Client) -> EgResult<()> {
+            Ok(())
+        }
+
+        fn register_methods(&self, _client: Client) -> EgResult<Vec<MethodDef>> {
+            Ok(Vec::new())
+        }
+
+        fn worker_factory(&self) -> ApplicationWorkerFactory {
+            || Box::new(TestWorker { methods: None })
+        }
+    }
+
+    struct TestWorker {
+        methods: Option<Arc<HashMap<String, MethodDef>>>,
+    }
+
+    impl ApplicationWorker for TestWorker {
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn methods(&self) -> &Arc<HashMap<String, MethodDef>> {
+            self.methods.as_ref().unwrap()
+        }
+
+        fn worker_start(
+            &mut self,
+            _client: Client,
+            methods: Arc<HashMap<String, MethodDef>>,
+        ) -> EgResult<()> {
+            self.methods = Some(methods);
+            Ok(())
+        }
+
+        fn start_session(&mut self) -> EgResult<()> {
+            Ok(())
+        }
+
+        fn end_session(&mut self) -> EgResult<()> {
+            Ok(())
+        }
+
+        fn keepalive_timeout(&mut self) -> EgResult<()> {
+            Ok(())
+        }
+
+        fn api_call_error(&mut self, _request: &message::MethodCall, _error: EgError) {}
+
+        fn worker_idle_wake(&mut self, _connected: bool) -> EgResult<()> {
+            Ok(())
+        }
+
+        fn worker_end(&mut self) -> EgResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn route_echo_to_server() {
+        membus::install_global_hub();
+
+        conf::ConfigBuilder::from_xml_string(CONFIG_XML)
+            .unwrap()
+            .build()
+            .unwrap()
+            .store()
+            .unwrap();
+
+        // Every setting falls back to its default.
+        HostSettings::store(EgValue::new_object()).unwrap();
+
+        thread::spawn(|| {
+            let mut router = Router::new("localhost");
+            router.init().unwrap();
+            router.listen();
+        });
+
+        thread::spawn(|| {
+            let client = Client::connect().unwrap();
+            Server::start_with_client(Box::new(TestApplication), client).unwrap();
+        });
+
+        let client = Client::connect().unwrap();
+
+        // Requests are bounced until the server has registered.
+        let timer = util::Timer::new(10);
+
+        while !timer.done() {
+            if let Ok(Some(value)) =
+                client.send_recv_one("test.echo", "opensrf.system.echo", "hello")
+            {
+                assert_eq!(value.as_str(), Some("hello"));
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        panic!("No echo response received");
+    }
+}
@@ -4,6 +4,7 @@ use eg::osrf::bus::Bus;
 use eg::osrf::conf;
 use eg::osrf::logging::Logger;
 use eg::osrf::message;
+use eg::osrf::metrics;
 use eg::Client;
 use eg::EgResult;
 use evergreen as eg;
@@ -227,6 +228,10 @@ struct Session {
     /// we have already connected to.
     osrf_sessions: HashMap<String, String>,
 
+    /// Service name of each thread with requests awaiting a final
+    /// response, so completed requests may be counted by service.
+    thread_services: HashMap<String, String>,
+
     /// Number of inbound connects/requests that are currently
     /// awaiting a final response.
     reqs_in_flight: usize,
@@ -322,6 +327,7 @@ impl Session {
             shutdown,
             shutdown_session: shutdown_session,
             osrf_sessions: HashMap::new(),
+            thread_services: HashMap::new(),
             request_queue: VecDeque::new(),
         };
 
@@ -618,10 +624,14 @@ impl Session {
                     }
 
                     self.log_request(service, &msg)?;
+
+                    self.thread_services
+                        .insert(thread.to_string(), service.to_string());
                 }
                 message::MessageType::Disconnect => {
                     log::debug!("{self} WS removing session on DISCONNECT: {thread}");
                     self.osrf_sessions.remove(thread);
+                    self.thread_services.remove(thread);
                 }
                 _ => Err(format!(
                     "{self} WS received unexpected message type: {}",
@@ -675,7 +685,10 @@ impl Session {
             if let eg::osrf::message::Payload::Status(s) = msg.payload() {
                 let stat = *s.status();
                 match stat {
-                    message::MessageStatus::Complete => self.subtract_reqs(),
+                    message::MessageStatus::Complete => {
+                        self.subtract_reqs();
+                        self.count_request(tm.thread(), true);
+                    }
                     message::MessageStatus::Ok => {
                         self.subtract_reqs();
                         // Connection successful message.  Track the worker address.
@@ -687,6 +700,7 @@ impl Session {
                     _ => {
                         log::error!("{self} Request returned unexpected status: {:?}", msg);
                         self.subtract_reqs();
+                        self.count_request(tm.thread(), false);
                         self.osrf_sessions.remove(tm.thread());
 
                         if stat.is_4xx() {
@@ -742,6 +756,36 @@ impl Session {
         })
     }
 
+    /// Count a request which received its final response.
+    ///
+    /// A service which completes a request is known to exist.
+    /// Otherwise, the service name, which comes from the caller,
+    /// is only used as a label if the service is already known.
+    fn count_request(&mut self, thread: &str, completed: bool) {
+        let service = if self.osrf_sessions.contains_key(thread) && completed {
+            // Connected sessions may carry more requests.
+            self.thread_services.get(thread).cloned()
+        } else {
+            self.thread_services.remove(thread)
+        };
+
+        let service = match service {
+            Some(s) => s,
+            None => return,
+        };
+
+        if completed {
+            metrics::add_known_service(&service);
+        }
+
+        metrics::inc_counter(
+            "osrf_websocket_requests",
+            "API requests relayed from websocket clients",
+            &[("service", metrics::service_label(&service))],
+            1,
+        );
+    }
+
     /// Log an API call, honoring the log-protect configs.
     fn log_request(&self, service: &str, msg: &message::Message) -> Result<(), String> {
         let request = match msg.payload() {
@@ -936,6 +980,8 @@ fn main() {
         .init()
         .expect("Logger Init");
 
+    metrics::start_listener(gateway_conf.metrics());
+
     let max_parallel = match env::var("EG_WEBSOCKETS_MAX_PARALLEL") {
         Ok(v) => v.parse::<usize>().expect("Invalid max-parallel value"),
         _ => MAX_ACTIVE_REQUESTS,
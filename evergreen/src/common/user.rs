@@ -6,6 +6,7 @@ use eg::EgValue;
 use md5;
 
 pub const PW_TYPE_MAIN: &str = "main";
+pub const PW_TYPE_SIP2: &str = "sip2";
 
 /// Returns result of True if the password provides matches the user's password.
 ///
@@ -1,4 +1,5 @@
 //! Create, connect, and manage database connections.
+use crate::osrf::trace::{Span, SpanKind};
 use crate::result::EgResult;
 use getopts;
 use log::debug;
@@ -386,6 +387,17 @@ impl DatabaseConnection {
     }
 }
 
+/// Start a trace span for a single database query.
+///
+/// The span does not become the current trace context, so queries
+/// issued while processing the results are not nested beneath it.
+pub fn query_span(statement: &str) -> Span {
+    let mut span = Span::detached("db.query", SpanKind::Client);
+    span.set_attr("db.system", "postgresql");
+    span.set_attr("db.statement", statement);
+    span
+}
+
 /// Determine whether a string is potentially a valid SQL identifier.
 pub fn is_identifier(s: &str) -> bool {
     let s = s.trim();
@@ -506,13 +506,17 @@ impl Translator {
 
         log::debug!("create() executing query: {query}; params=[{param_list:?}]");
 
+        let mut span = db::query_span(&query);
         let query_res = self.db.borrow_mut().client().query(&query, &params);
 
         if let Err(ref e) = query_res {
+            span.set_error(&e.to_string());
             log::error!("DB Error: {e} query={query} param={params:?}");
             Err(format!("DB query failed. See error logs"))?;
         }
 
+        span.end();
+
         // Use the primary key values reported by PG to find the
         // newly created rows.
         let mut results: Vec<EgValue> = Vec::new();
@@ -598,6 +602,7 @@ impl Translator {
     fn execute_one(&self, query: &str, params: &[&(dyn ToSql + Sync)]) -> EgResult<u64> {
         log::debug!("update() executing query: {query}; params=[{params:?}]");
 
+        let mut span = db::query_span(query);
         let query_res = self.db.borrow_mut().client().execute(query, params);
 
         match query_res {
@@ -606,6 +611,7 @@ impl Translator {
                 Ok(v)
             }
             Err(e) => {
+                span.set_error(&e.to_string());
                 log::error!("DB Error: {e} query={query} param={params:?}");
                 Err(format!("DB query failed. See error logs").into())
             }
@@ -696,13 +702,17 @@ impl Translator {
             params.push(p);
         }
 
+        let mut span = db::query_span(&query);
         let query_res = self.db.borrow_mut().client().query(&query, &params);
 
         if let Err(ref e) = query_res {
+            span.set_error(&e.to_string());
             log::error!("DB Error: {e} query={query} param={params:?}");
             Err(format!("DB query failed. See error logs"))?;
         }
 
+        span.end();
+
         for row in query_res.unwrap() {
             let mut obj = self.row_to_idl(&class, &row)?;
             if let Some(flesh_def) = search.flesh.as_ref() {
@@ -3,6 +3,7 @@ use crate::idl;
 use crate::osrf::conf;
 use crate::osrf::logging;
 use crate::osrf::sclient::HostSettings;
+use crate::osrf::trace;
 use crate::Client;
 use crate::EgResult;
 use std::env;
@@ -39,14 +40,17 @@ pub fn init() -> EgResult<Client> {
     with_options(&InitOptions::new())
 }
 
+/// Path to the OpenSRF core config file.
+///
+/// Uses the OSRF_CONFIG environment variable when set.
+pub fn osrf_config_file() -> String {
+    env::var("OSRF_CONFIG").unwrap_or(DEFAULT_OSRF_CONFIG.to_string())
+}
+
 /// Parse the OpenSRF config file, connect to the message bus, and
 /// optionally fetch the host settings and initialize logging.
 pub fn osrf_init(options: &InitOptions) -> EgResult<Client> {
-    let builder = if let Ok(fname) = env::var("OSRF_CONFIG") {
-        conf::ConfigBuilder::from_file(&fname)?
-    } else {
-        conf::ConfigBuilder::from_file(DEFAULT_OSRF_CONFIG)?
-    };
+    let builder = conf::ConfigBuilder::from_file(&osrf_config_file())?;
 
     let mut config = builder.build()?;
 
@@ -116,6 +120,9 @@ pub fn osrf_init(options: &InitOptions) -> EgResult<Client> {
             .or_else(|e| Err(format!("Error initializing logger: {e}")))?;
     }
 
+    let appname = options.appname.as_deref().unwrap_or("opensrf");
+    trace::init(appname, config.client().trace_export())?;
+
     // Save the config as the one-true-global-osrf-config
     config.store()?;
 
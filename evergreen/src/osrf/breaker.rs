@@ -0,0 +1,117 @@
+//! Per-service circuit breakers for client API calls.
+//!
+//! After a service's ClientPolicy breaker_threshold consecutive
+//! request timeouts, the circuit for that service opens and new
+//! requests fail immediately instead of waiting on a service which
+//! is not responding.  Once breaker_reset seconds have passed, the
+//! circuit is half-open: a single trial request is allowed through
+//! while all others continue to fail.  A completed trial closes the
+//! circuit; a timed out trial opens it again.  A trial which never
+//! reports back is replaced by a new one once the policy request
+//! timeout has passed.
+//!
+//! Breaker state is shared by all clients within a process.
+use crate::osrf::conf::ClientPolicy;
+use crate::EgResult;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+static BREAKERS: OnceLock<Mutex<HashMap<String, Breaker>>> = OnceLock::new();
+
+#[derive(Debug, Default)]
+struct Breaker {
+    /// Consecutive timeouts.
+    timeouts: u32,
+
+    /// When the circuit opened, if it's open.
+    opened: Option<Instant>,
+
+    /// When the trial request was let through a half-open circuit.
+    trial: Option<Instant>,
+}
+
+fn breakers() -> MutexGuard<'static, HashMap<String, Breaker>> {
+    BREAKERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns Err if the circuit for this service is open.
+pub fn check(service: &str, policy: &ClientPolicy) -> EgResult<()> {
+    if policy.breaker_threshold() == 0 {
+        return Ok(());
+    }
+
+    let mut breakers = breakers();
+
+    let breaker = match breakers.get_mut(service) {
+        Some(b) => b,
+        None => return Ok(()),
+    };
+
+    let opened = match breaker.opened {
+        Some(o) => o,
+        None => return Ok(()),
+    };
+
+    let reset = Duration::from_secs(policy.breaker_reset());
+
+    if opened.elapsed() < reset {
+        return Err(format!(
+            "Circuit open for service {service} after {} consecutive timeouts",
+            breaker.timeouts
+        )
+        .into());
+    }
+
+    // Half-open.  Only one trial request at a time.
+    if let Some(trial) = breaker.trial {
+        let timeout = Duration::from_secs(policy.timeout().max(0) as u64);
+        if trial.elapsed() < timeout {
+            return Err(
+                format!("Circuit half-open for service {service}; awaiting trial request").into(),
+            );
+        }
+    }
+
+    log::info!("Circuit for service {service} is half-open; sending trial request");
+
+    breaker.trial = Some(Instant::now());
+
+    Ok(())
+}
+
+/// Record a request which timed out waiting for a response.
+pub fn record_timeout(service: &str, policy: &ClientPolicy) {
+    if policy.breaker_threshold() == 0 {
+        return;
+    }
+
+    let mut breakers = breakers();
+    let breaker = breakers.entry(service.to_string()).or_default();
+
+    breaker.timeouts += 1;
+
+    if breaker.trial.take().is_some() {
+        log::warn!("Trial request for service {service} timed out; re-opening circuit");
+        breaker.opened = Some(Instant::now());
+        return;
+    }
+
+    if breaker.timeouts >= policy.breaker_threshold() && breaker.opened.is_none() {
+        log::warn!(
+            "Opening circuit for service {service} after {} consecutive timeouts",
+            breaker.timeouts
+        );
+        breaker.opened = Some(Instant::now());
+    }
+}
+
+/// Record a request which completed.
+pub fn record_success(service: &str) {
+    if breakers().remove(service).is_some() {
+        log::debug!("Closing circuit for service {service}");
+    }
+}
@@ -1,34 +1,61 @@
 use crate::osrf::addr::BusAddress;
 use crate::osrf::conf;
 use crate::osrf::logging::Logger;
+use crate::osrf::membus;
 use crate::osrf::message::TransportMessage;
+use crate::osrf::metrics;
+use crate::osrf::signing;
+use crate::osrf::trace;
 use crate::util;
 use crate::EgResult;
 use redis::{Commands, ConnectionAddr, ConnectionInfo, RedisConnectionInfo};
 use std::fmt;
 
-/// Manages a Redis connection.
-pub struct Bus {
-    connection: redis::Connection,
+/// List operations a message bus backend must support.
+///
+/// Modeled on the Redis list commands used by OpenSRF.
+pub trait BusBackend: Send {
+    /// Remove and return the first value in the list at 'key'.
+    ///
+    /// Waits up to 'timeout' seconds for a value to arrive.  A
+    /// negative value means to block indefinitely.  0 means do not block.
+    fn pop(&mut self, key: &str, timeout: i32) -> EgResult<Option<String>>;
 
-    /// Every bus connection has a unique client address.
-    address: BusAddress,
+    /// Append a value to the list at 'key'.
+    fn push(&mut self, key: &str, value: String) -> EgResult<()>;
 
-    /// Name of the router running on our primary domain.
-    router_name: String,
+    /// Returns a list of keys that match the provided pattern.
+    fn keys(&mut self, pattern: &str) -> EgResult<Vec<String>>;
 
-    /// Some clients don't need the IDL and all its classes to function
-    /// (e.g. the router).  Using raw_data_mode allows for transport
-    /// messages to be parsed and serialized without concern for
-    /// IDL-classed information stored in the message.
-    raw_data_mode: bool,
+    /// Returns the length of the list at 'key'.
+    fn llen(&mut self, key: &str) -> EgResult<i32>;
+
+    /// Returns the time-to-live (in seconds) of the specified key.
+    ///
+    /// Return -1 if no expire time is set, -2 if no such key exists.
+    fn ttl(&mut self, key: &str) -> EgResult<i32>;
+
+    /// Returns a slice of the list at 'key'.
+    fn lrange(&mut self, key: &str, start: isize, stop: isize) -> EgResult<Vec<String>>;
+
+    /// Set the expire time on the specified key to 'timeout' seconds
+    /// from now.
+    fn expire(&mut self, key: &str, timeout: u64) -> EgResult<i32>;
+
+    /// Delete the specified key.
+    fn del(&mut self, key: &str) -> EgResult<i32>;
 }
 
-impl Bus {
+/// Bus backend which communicates with Redis.
+pub struct RedisBackend {
+    connection: redis::Connection,
+}
+
+impl RedisBackend {
     pub fn new(config: &conf::BusClient) -> EgResult<Self> {
-        let info = Bus::connection_info(config)?;
+        let info = RedisBackend::connection_info(config)?;
 
-        log::trace!("Bus::new() connecting to {:?}", info);
+        log::trace!("RedisBackend::new() connecting to {:?}", info);
 
         let client = redis::Client::open(info)
             .or_else(|e| Err(format!("Error opening Redis connection: {e}")))?;
@@ -37,22 +64,7 @@ impl Bus {
             .get_connection()
             .or_else(|e| Err(format!("Bus connect error: {e}")))?;
 
-        let username = config.username();
-        let domain = config.domain().name();
-        let addr = BusAddress::for_client(username, domain);
-
-        let bus = Bus {
-            connection,
-            raw_data_mode: false,
-            address: addr,
-            router_name: config.router_name().to_string(),
-        };
-
-        Ok(bus)
-    }
-
-    pub fn set_raw_data_mode(&mut self, on: bool) {
-        self.raw_data_mode = on;
+        Ok(RedisBackend { connection })
     }
 
     /// Generates the Redis connection Info
@@ -75,6 +87,167 @@ impl Bus {
         })
     }
 
+    pub fn connection(&mut self) -> &mut redis::Connection {
+        &mut self.connection
+    }
+}
+
+impl BusBackend for RedisBackend {
+    fn pop(&mut self, key: &str, mut timeout: i32) -> EgResult<Option<String>> {
+        if timeout == 0 {
+            // non-blocking
+
+            // LPOP returns a scalar response.
+            return match self.connection().lpop(key, None) {
+                Ok(c) => Ok(Some(c)),
+                Err(e) => match e.kind() {
+                    redis::ErrorKind::TypeError => {
+                        // Will read a Nil value on timeout.  That's OK.
+                        Ok(None)
+                    }
+                    _ => Err(format!("recv_one_chunk failed: {e}").into()),
+                },
+            };
+        }
+
+        // Blocking
+
+        if timeout < 0 {
+            // Timeout 0 means block indefinitely in Redis.
+            timeout = 0;
+        }
+
+        let mut resp: Vec<String> = self
+            .connection()
+            .blpop(key, timeout as usize)
+            .or_else(|e| Err(format!("Redis blpop error recipient={key} : {e}")))?;
+
+        if resp.len() > 1 {
+            // BLPOP returns the name of the popped list and the value.
+            // resp = [key, value]
+            Ok(Some(resp.remove(1)))
+        } else {
+            // No message received
+            Ok(None)
+        }
+    }
+
+    fn push(&mut self, key: &str, value: String) -> EgResult<()> {
+        let res: Result<i32, _> = self.connection().rpush(key, value);
+
+        if let Err(e) = res {
+            return Err(format!("Error in send() {e}").into());
+        }
+
+        Ok(())
+    }
+
+    fn keys(&mut self, pattern: &str) -> EgResult<Vec<String>> {
+        self.connection()
+            .keys(pattern)
+            .map_err(|e| format!("Error in keys(): {e}").into())
+    }
+
+    fn llen(&mut self, key: &str) -> EgResult<i32> {
+        self.connection()
+            .llen(key)
+            .map_err(|e| format!("Error in llen(): {e}").into())
+    }
+
+    fn ttl(&mut self, key: &str) -> EgResult<i32> {
+        self.connection()
+            .ttl(key)
+            .map_err(|e| format!("Error in ttl(): {e}").into())
+    }
+
+    fn lrange(&mut self, key: &str, start: isize, stop: isize) -> EgResult<Vec<String>> {
+        self.connection()
+            .lrange(key, start, stop)
+            .map_err(|e| format!("Error in lrange(): {e}").into())
+    }
+
+    fn expire(&mut self, key: &str, timeout: u64) -> EgResult<i32> {
+        self.connection()
+            .expire(key, timeout as usize)
+            .map_err(|e| format!("Error in set_key_timeout(): {e}").into())
+    }
+
+    fn del(&mut self, key: &str) -> EgResult<i32> {
+        self.connection()
+            .del(key)
+            .map_err(|e| format!("Error in del(): {e}").into())
+    }
+}
+
+/// Manages a message bus connection.
+///
+/// Connects to Redis unless an in-process bus has been installed
+/// (see osrf::membus).
+pub struct Bus {
+    connection: Box<dyn BusBackend>,
+
+    /// Every bus connection has a unique client address.
+    address: BusAddress,
+
+    /// Name of the router running on our primary domain.
+    router_name: String,
+
+    /// Some clients don't need the IDL and all its classes to function
+    /// (e.g. the router).  Using raw_data_mode allows for transport
+    /// messages to be parsed and serialized without concern for
+    /// IDL-classed information stored in the message.
+    raw_data_mode: bool,
+
+    /// Keys for signing outbound and verifying inbound messages.
+    message_security: Option<conf::MessageSecurity>,
+}
+
+impl Bus {
+    pub fn new(config: &conf::BusClient) -> EgResult<Self> {
+        let connection: Box<dyn BusBackend> = match membus::global_hub() {
+            Some(hub) => Box::new(hub.connect()),
+            None => Box::new(RedisBackend::new(config)?),
+        };
+
+        let mut bus = Bus::with_backend(
+            connection,
+            config.username(),
+            config.domain().name(),
+            config.router_name(),
+        );
+
+        bus.set_message_security(config.message_security().cloned());
+
+        Ok(bus)
+    }
+
+    /// Create a Bus which communicates via the provided backend.
+    pub fn with_backend(
+        connection: Box<dyn BusBackend>,
+        username: &str,
+        domain: &str,
+        router_name: &str,
+    ) -> Self {
+        Bus {
+            connection,
+            raw_data_mode: false,
+            message_security: None,
+            address: BusAddress::for_client(username, domain),
+            router_name: router_name.to_string(),
+        }
+    }
+
+    pub fn set_raw_data_mode(&mut self, on: bool) {
+        self.raw_data_mode = on;
+    }
+
+    /// Sign and verify messages using the provided keys.
+    ///
+    /// See osrf::signing.
+    pub fn set_message_security(&mut self, security: Option<conf::MessageSecurity>) {
+        self.message_security = security;
+    }
+
     /// The unique bus address for this bus connection.
     pub fn address(&self) -> &BusAddress {
         &self.address
@@ -104,8 +277,8 @@ impl Bus {
         self.address().username()
     }
 
-    pub fn connection(&mut self) -> &mut redis::Connection {
-        &mut self.connection
+    pub fn connection(&mut self) -> &mut dyn BusBackend {
+        self.connection.as_mut()
     }
 
     /// Returns at most one String pulled from the queue or None if the
@@ -114,7 +287,7 @@ impl Bus {
     /// The string will be whole, unparsed JSON string.
     fn recv_one_chunk(
         &mut self,
-        mut timeout: i32,
+        timeout: i32,
         recipient: Option<&str>,
     ) -> EgResult<Option<String>> {
         let recipient = match recipient {
@@ -122,44 +295,14 @@ impl Bus {
             None => self.address().as_str().to_string(),
         };
 
-        let value: String;
-
-        if timeout == 0 {
-            // non-blocking
-
-            // LPOP returns a scalar response.
-            value = match self.connection().lpop(&recipient, None) {
-                Ok(c) => c,
-                Err(e) => match e.kind() {
-                    redis::ErrorKind::TypeError => {
-                        // Will read a Nil value on timeout.  That's OK.
-                        return Ok(None);
-                    }
-                    _ => return Err(format!("recv_one_chunk failed: {e}").into()),
-                },
-            };
-        } else {
-            // Blocking
-
-            if timeout < 0 {
-                // Timeout 0 means block indefinitely in Redis.
-                timeout = 0;
-            }
-
-            let mut resp: Vec<String> = self
-                .connection()
-                .blpop(&recipient, timeout as usize)
-                .or_else(|e| Err(format!("Redis blpop error recipient={recipient} : {e}")))?;
-
-            if resp.len() > 1 {
-                // BLPOP returns the name of the popped list and the value.
-                // resp = [key, value]
-                value = resp.remove(1);
-            } else {
-                // No message received
-                return Ok(None);
+        let value = match self.connection().pop(&recipient, timeout) {
+            Ok(Some(v)) => v,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                count_bus_error("recv");
+                return Err(e);
             }
-        }
+        };
 
         log::trace!("recv_one_value() pulled from bus: {}", value);
 
@@ -267,7 +410,16 @@ impl Bus {
     ) -> EgResult<Option<TransportMessage>> {
         let json_op = self.recv_json_value(timeout, recipient)?;
 
-        if let Some(jv) = json_op {
+        if let Some(mut jv) = json_op {
+            if let Some(security) = self.message_security.as_ref() {
+                if let Err(e) = signing::open(&mut jv, security) {
+                    // Treat like any other invalid message.
+                    log::error!("Discarding unverified message: {e}");
+                    count_bus_error("verify");
+                    return Ok(None);
+                }
+            }
+
             match TransportMessage::from_json_value(jv, self.raw_data_mode) {
                 Ok(v) => return Ok(Some(v)),
                 Err(e) => {
@@ -301,6 +453,18 @@ impl Bus {
         // to worry about it.
         json_val["osrf_xid"] = json::from(Logger::get_log_trace());
 
+        // Likewise for the trace context, unless the caller has
+        // already attached a more specific one.
+        if json_val["traceparent"].is_null() {
+            if let Some(tp) = trace::current_traceparent() {
+                json_val["traceparent"] = json::from(tp);
+            }
+        }
+
+        if let Some(security) = self.message_security.as_ref() {
+            signing::seal(&mut json_val, security)?;
+        }
+
         // Similarly, this allows us to avoid an unnecessary clone
         // on the recipient if it resides in the now-moved source message.
         // json_val["to"].as_str() is guaranteed here, because it's a
@@ -311,86 +475,61 @@ impl Bus {
 
         log::trace!("send() writing chunk to={}: {}", recipient, json_str);
 
-        let res: Result<i32, _> = self.connection().rpush(recipient, json_str);
+        let result = self.connection().push(recipient, json_str);
 
-        if let Err(e) = res {
-            return Err(format!("Error in send() {e}").into());
+        if result.is_err() {
+            count_bus_error("send");
         }
 
-        Ok(())
+        result
     }
 
     /// Returns a list of keys that match the provided pattern.
     pub fn keys(&mut self, pattern: &str) -> EgResult<Vec<String>> {
-        let res: Result<Vec<String>, _> = self.connection().keys(pattern);
-
-        if let Err(e) = res {
-            return Err(format!("Error in keys(): {e}").into());
-        }
-
-        Ok(res.unwrap())
+        self.connection().keys(pattern)
     }
 
     /// Returns the length of the array specified by 'key'.
     pub fn llen(&mut self, key: &str) -> EgResult<i32> {
-        let res: Result<i32, _> = self.connection().llen(key);
-
-        if let Err(e) = res {
-            return Err(format!("Error in llen(): {e}").into());
-        }
-
-        Ok(res.unwrap())
+        self.connection().llen(key)
     }
 
     /// Returns the time-to-live (in seconds) of the specified key.
     ///
     /// Return -1 if no expire time is set, -2 if no such key exists.
     pub fn ttl(&mut self, key: &str) -> EgResult<i32> {
-        let res: Result<i32, _> = self.connection().ttl(key);
-
-        if let Err(e) = res {
-            return Err(format!("Error in ttl(): {e}").into());
-        }
-
-        Ok(res.unwrap())
+        self.connection().ttl(key)
     }
 
     /// Returns an array slice as a Vec of Strings.
     pub fn lrange(&mut self, key: &str, start: isize, stop: isize) -> EgResult<Vec<String>> {
-        let res: Result<Vec<String>, _> = self.connection().lrange(key, start, stop);
-
-        if let Err(e) = res {
-            return Err(format!("Error in lrange(): {e}").into());
-        }
-
-        Ok(res.unwrap())
+        self.connection().lrange(key, start, stop)
     }
 
     /// Set the expire time on the specified key to 'timeout' seconds from now.
     pub fn set_key_timeout(&mut self, key: &str, timeout: u64) -> EgResult<i32> {
-        let res: Result<i32, _> = self.connection().expire(key, timeout as usize);
-
-        if let Err(ref e) = res {
-            Err(format!("Error in set_key_timeout(): {e}"))?;
-        }
-
-        let val = res.unwrap();
-        Ok(val)
+        self.connection().expire(key, timeout)
     }
 
     /// Remove all pending data from the recipient queue.
     pub fn clear_bus(&mut self) -> EgResult<()> {
         let stream = self.address().as_str().to_string(); // mut borrow
-        let res: Result<i32, _> = self.connection().del(stream);
-
-        if let Err(e) = res {
-            return Err(format!("Error in queue clear(): {e}").into());
-        }
-
-        Ok(())
+        self.connection()
+            .del(&stream)
+            .map(|_| ())
+            .map_err(|e| format!("Error in queue clear(): {e}").into())
     }
 }
 
+fn count_bus_error(operation: &str) {
+    metrics::inc_counter(
+        "osrf_bus_errors",
+        "Message bus send and receive failures",
+        &[("operation", operation)],
+        1,
+    );
+}
+
 /// Good for debugging / logging
 impl fmt::Display for Bus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -405,7 +544,6 @@ impl Drop for Bus {
     /// Similar to clear_bus but avoids any logging / error reporting.
     fn drop(&mut self) {
         let stream = self.address().as_str().to_string();
-        let res: Result<i32, _> = self.connection().del(&stream);
-        res.ok();
+        self.connection().del(&stream).ok();
     }
 }
@@ -1,43 +1,364 @@
+use crate as eg;
 use crate::osrf::sclient::HostSettings;
 use crate::EgResult;
 use crate::EgValue;
+use base64::Engine as _;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use memcache;
+use redis::{Commands, ConnectionAddr, ConnectionInfo, RedisConnectionInfo};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
 
 thread_local! {
     static CACHE_CONNECTIONS: RefCell<HashMap<String, CacheConnection>> = RefCell::new(HashMap::new());
 }
 
+/// Usage counters for each cache, shared by all threads within a process.
+static CACHE_STATS: OnceLock<Mutex<BTreeMap<String, CacheStats>>> = OnceLock::new();
+
+/// In-memory caches are shared by all threads within a process.
+static MEMORY_CACHES: OnceLock<Mutex<HashMap<String, Arc<Mutex<LruStore>>>>> = OnceLock::new();
+
 const DEFAULT_MAX_CACHE_TIME: u32 = 86400;
 const DEFAULT_MAX_CACHE_SIZE: u32 = 100000000; // ~100M
+const DEFAULT_COMPRESS_THRESHOLD: usize = 0; // disabled
+const DEFAULT_MAX_MEMORY_ENTRIES: usize = 10000;
+const DEFAULT_REDIS_PORT: u16 = 6379;
 const GLOBAL_CACHE_NAME: &str = "global";
 const ANON_CACHE_NAME: &str = "anon";
 
+/// Prefix applied to compressed values.
+///
+/// Cached values are otherwise JSON strings, which never start with "~".
+///
+/// Only the Rust cache client understands compressed values.  Leave
+/// compression disabled for caches shared with Perl or C OpenSRF code.
+const COMPRESSED_PREFIX: &str = "~deflate:";
+
 /*
 <cache>
   <global>
+    <!-- memcache (default), redis, or memory -->
+    <backend>memcache</backend>
     <servers>
       <server>127.0.0.1:11211</server>
     </servers>
     <max_cache_time>86400</max_cache_time>
+    <!-- compress values larger than this many bytes; 0 (default)
+         disables.  Compressed values are only readable by Rust
+         clients, so leave this off for caches shared with Perl/C. -->
+    <compress_threshold>10240</compress_threshold>
   </global>
   <anon>
+    <backend>redis</backend>
     <servers>
-      <server>127.0.0.1:11211</server>
+      <server>127.0.0.1:6379</server>
     </servers>
+    <username>opensrf</username>
+    <password>demo123</password>
     <max_cache_time>1800</max_cache_time>
     <max_cache_size>102400</max_cache_size>
   </anon>
+  <!--
+  <global>
+    <backend>memory</backend>
+    <max_entries>10000</max_entries>
+  </global>
+  -->
 </cache>
 */
 
+/// Storage operations a cache backend must support.
+///
+/// Values are serialized JSON strings.  Timeouts are in seconds.
+pub trait CacheBackend {
+    fn set(&mut self, key: &str, value: &str, timeout: u32) -> EgResult<()>;
+    fn get(&mut self, key: &str) -> EgResult<Option<String>>;
+    fn del(&mut self, key: &str) -> EgResult<()>;
+}
+
+/// Cache backend which stores values in memcache.
+pub struct MemcacheBackend {
+    memcache: memcache::Client,
+}
+
+impl MemcacheBackend {
+    /// Connect to memcache.
+    ///
+    /// Servers are "host:port" strings.
+    pub fn connect(servers: &[String]) -> EgResult<Self> {
+        let urls: Vec<String> = servers.iter().map(|s| format!("memcache://{s}")).collect();
+
+        log::info!("Connecting to memcache servers: {urls:?}");
+
+        let memcache = memcache::connect(urls)
+            .map_err(|e| format!("Cannot connect to memcache servers {servers:?}: {e}"))?;
+
+        Ok(MemcacheBackend { memcache })
+    }
+}
+
+impl CacheBackend for MemcacheBackend {
+    fn set(&mut self, key: &str, value: &str, timeout: u32) -> EgResult<()> {
+        self.memcache
+            .set(key, value, timeout)
+            .map_err(|e| format!("memcache set key={key} failed: {e}").into())
+    }
+
+    fn get(&mut self, key: &str) -> EgResult<Option<String>> {
+        self.memcache
+            .get(key)
+            .map_err(|e| format!("memcache get key={key} failed: {e}").into())
+    }
+
+    fn del(&mut self, key: &str) -> EgResult<()> {
+        self.memcache
+            .delete(key)
+            .map(|_| ())
+            .map_err(|e| format!("memcache del key={key} failed: {e}").into())
+    }
+}
+
+/// Cache backend which stores values in Redis using SET EX.
+pub struct RedisCacheBackend {
+    connection: redis::Connection,
+}
+
+impl RedisCacheBackend {
+    /// Connect to Redis.
+    ///
+    /// The server is a "host:port" or "host" string.
+    pub fn connect(server: &str, username: Option<&str>, password: Option<&str>) -> EgResult<Self> {
+        let (host, port) = match server.split_once(':') {
+            Some((h, p)) => (
+                h,
+                p.parse::<u16>()
+                    .map_err(|e| format!("Invalid Redis cache port: {server} {e}"))?,
+            ),
+            None => (server, DEFAULT_REDIS_PORT),
+        };
+
+        let info = ConnectionInfo {
+            addr: ConnectionAddr::Tcp(host.to_string(), port),
+            redis: RedisConnectionInfo {
+                db: 0,
+                username: username.map(|u| u.to_string()),
+                password: password.map(|p| p.to_string()),
+            },
+        };
+
+        log::info!("Connecting to Redis cache server: {host}:{port}");
+
+        let client = redis::Client::open(info)
+            .map_err(|e| format!("Error opening Redis cache connection: {e}"))?;
+
+        let connection = client
+            .get_connection()
+            .map_err(|e| format!("Redis cache connect error: {e}"))?;
+
+        Ok(RedisCacheBackend { connection })
+    }
+}
+
+impl CacheBackend for RedisCacheBackend {
+    fn set(&mut self, key: &str, value: &str, timeout: u32) -> EgResult<()> {
+        self.connection
+            .set_ex(key, value, timeout as usize)
+            .map_err(|e| format!("Redis cache set key={key} failed: {e}").into())
+    }
+
+    fn get(&mut self, key: &str) -> EgResult<Option<String>> {
+        self.connection
+            .get(key)
+            .map_err(|e| format!("Redis cache get key={key} failed: {e}").into())
+    }
+
+    fn del(&mut self, key: &str) -> EgResult<()> {
+        self.connection
+            .del(key)
+            .map(|_: i32| ())
+            .map_err(|e| format!("Redis cache del key={key} failed: {e}").into())
+    }
+}
+
+struct LruEntry {
+    value: String,
+    expires: Instant,
+    /// Value of LruStore::clock as of the last time this entry was used.
+    used: u64,
+}
+
+/// Key/value store which discards the least recently used entry
+/// when full.
+struct LruStore {
+    max_entries: usize,
+    entries: HashMap<String, LruEntry>,
+    /// Maps LruEntry.used values to their keys, oldest first.
+    usage: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+impl LruStore {
+    fn new(max_entries: usize) -> Self {
+        LruStore {
+            max_entries: max_entries.max(1),
+            entries: HashMap::new(),
+            usage: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn set(&mut self, key: &str, value: &str, timeout: u32) {
+        self.remove(key);
+
+        let used = self.tick();
+        let entry = LruEntry {
+            value: value.to_string(),
+            expires: Instant::now() + Duration::from_secs(timeout as u64),
+            used,
+        };
+
+        self.entries.insert(key.to_string(), entry);
+        self.usage.insert(used, key.to_string());
+
+        while self.entries.len() > self.max_entries {
+            if let Some((_, oldest)) = self.usage.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<String> {
+        let expired = self.entries.get(key)?.expires <= Instant::now();
+
+        if expired {
+            self.remove(key);
+            return None;
+        }
+
+        let used = self.tick();
+        let entry = self.entries.get_mut(key)?;
+
+        self.usage.remove(&entry.used);
+        self.usage.insert(used, key.to_string());
+        entry.used = used;
+
+        Some(entry.value.clone())
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.usage.remove(&entry.used);
+        }
+    }
+}
+
+/// Cache backend which stores values in process memory.
+///
+/// Suitable for tests and single-host installs.  Values are not
+/// shared with other processes.
+pub struct MemoryCacheBackend {
+    store: Arc<Mutex<LruStore>>,
+}
+
+impl MemoryCacheBackend {
+    /// Create a private cache holding at most 'max_entries' values.
+    pub fn new(max_entries: usize) -> Self {
+        MemoryCacheBackend {
+            store: Arc::new(Mutex::new(LruStore::new(max_entries))),
+        }
+    }
+
+    /// Returns a handle to the process-wide cache with the provided
+    /// name, creating it if necessary.
+    ///
+    /// 'max_entries' only applies when the cache is first created.
+    pub fn shared(name: &str, max_entries: usize) -> Self {
+        let mut caches = MEMORY_CACHES
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+
+        let store = caches
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(LruStore::new(max_entries))))
+            .clone();
+
+        MemoryCacheBackend { store }
+    }
+
+    fn store(&self) -> std::sync::MutexGuard<'_, LruStore> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CacheBackend for MemoryCacheBackend {
+    fn set(&mut self, key: &str, value: &str, timeout: u32) -> EgResult<()> {
+        self.store().set(key, value, timeout);
+        Ok(())
+    }
+
+    fn get(&mut self, key: &str) -> EgResult<Option<String>> {
+        Ok(self.store().get(key))
+    }
+
+    fn del(&mut self, key: &str) -> EgResult<()> {
+        self.store().remove(key);
+        Ok(())
+    }
+}
+
+fn cache_stats() -> MutexGuard<'static, BTreeMap<String, CacheStats>> {
+    CACHE_STATS
+        .get_or_init(|| Mutex::new(BTreeMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Usage counters for a single cache, summed across all threads.
+#[derive(Debug, Default, Clone)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub sets: u64,
+    pub compressed_sets: u64,
+    /// Size of compressed values before compression.
+    pub uncompressed_bytes: u64,
+    /// Size of compressed values after compression.
+    pub compressed_bytes: u64,
+}
+
+impl CacheStats {
+    pub fn to_eg_value(&self) -> EgValue {
+        eg::hash! {
+            "hits": self.hits,
+            "misses": self.misses,
+            "sets": self.sets,
+            "compressed_sets": self.compressed_sets,
+            "uncompressed_bytes": self.uncompressed_bytes,
+            "compressed_bytes": self.compressed_bytes,
+        }
+    }
+}
+
 pub struct CacheConnection {
     name: String,
-    memcache: memcache::Client,
+    backend: Box<dyn CacheBackend>,
     max_cache_time: u32,
     max_cache_size: u32,
+    /// Values larger than this many bytes are compressed.  0 disables.
+    compress_threshold: usize,
 }
 
 impl fmt::Display for CacheConnection {
@@ -47,12 +368,34 @@ impl fmt::Display for CacheConnection {
 }
 
 impl CacheConnection {
+    /// Apply an update to the process-wide usage counters for our cache.
+    fn record(&self, update: impl FnOnce(&mut CacheStats)) {
+        update(cache_stats().entry(self.name.clone()).or_default());
+    }
+
     /// Store a value in the cache with the provided timeout.
     ///
     /// If the timeout is 0, the default timeout for the connection type is used.
-    fn set(&self, key: &str, value: EgValue, mut timeout: u32) -> EgResult<()> {
-        let value = value.into_json_value().dump();
-        let byte_count = value.as_bytes().len();
+    fn set(&mut self, key: &str, value: EgValue, mut timeout: u32) -> EgResult<()> {
+        let mut value = value.into_json_value().dump();
+
+        // Size of the value before compression, if compressed.
+        let mut uncompressed_size = None;
+
+        if self.compress_threshold > 0 && value.len() > self.compress_threshold {
+            let compressed = self.compress(&value)?;
+
+            log::debug!(
+                "{self} compressed key={key} from {} to {} bytes",
+                value.len(),
+                compressed.len()
+            );
+
+            uncompressed_size = Some(value.len());
+            value = compressed;
+        }
+
+        let byte_count = value.len();
 
         log::debug!("{self} caching {byte_count} bytes at key={key}");
 
@@ -68,18 +411,36 @@ impl CacheConnection {
             timeout = self.max_cache_time;
         }
 
-        self.memcache
+        self.backend
             .set(key, &value, timeout)
-            .map_err(|e| format!("{self} set key={key} failed: {e}").into())
+            .map_err(|e| format!("{self} {e}"))?;
+
+        self.record(|stats| {
+            stats.sets += 1;
+
+            if let Some(size) = uncompressed_size {
+                stats.compressed_sets += 1;
+                stats.uncompressed_bytes += size as u64;
+                stats.compressed_bytes += byte_count as u64;
+            }
+        });
+
+        Ok(())
     }
 
-    fn get(&self, key: &str) -> EgResult<Option<EgValue>> {
-        let result: Option<String> = match self.memcache.get(key) {
+    fn get(&mut self, key: &str) -> EgResult<Option<EgValue>> {
+        let result = match self.backend.get(key) {
             Ok(r) => r,
-            Err(e) => return Err(format!("{self} get key={key} failed: {e}").into()),
+            Err(e) => return Err(format!("{self} {e}").into()),
         };
 
-        if let Some(value) = result {
+        if let Some(mut value) = result {
+            self.record(|stats| stats.hits += 1);
+
+            if let Some(encoded) = value.strip_prefix(COMPRESSED_PREFIX) {
+                value = self.decompress(key, encoded)?;
+            }
+
             let obj = json::parse(&value).or_else(|e| {
                 Err(format!(
                     "Cached JSON parse failure on key {key}: {e} [{value}]"
@@ -91,14 +452,45 @@ impl CacheConnection {
             return Ok(Some(v));
         }
 
+        self.record(|stats| stats.misses += 1);
+
         Ok(None)
     }
 
-    fn del(&self, key: &str) -> EgResult<()> {
-        self.memcache
-            .delete(key)
-            .map(|_| ())
-            .map_err(|e| format!("{self} del key={key} failed: {e}").into())
+    fn del(&mut self, key: &str) -> EgResult<()> {
+        self.backend
+            .del(key)
+            .map_err(|e| format!("{self} {e}").into())
+    }
+
+    /// Deflate and base64-encode a value, adding our compression prefix.
+    fn compress(&self, value: &str) -> EgResult<String> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+
+        let bytes = encoder
+            .write_all(value.as_bytes())
+            .and_then(|_| encoder.finish())
+            .map_err(|e| format!("{self} compression failed: {e}"))?;
+
+        Ok(format!(
+            "{COMPRESSED_PREFIX}{}",
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        ))
+    }
+
+    /// Reverse of compress(), minus the prefix.
+    fn decompress(&self, key: &str, encoded: &str) -> EgResult<String> {
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("{self} key={key} has invalid compressed data: {e}"))?;
+
+        let mut value = String::new();
+
+        DeflateDecoder::new(bytes.as_slice())
+            .read_to_string(&mut value)
+            .map_err(|e| format!("{self} key={key} decompression failed: {e}"))?;
+
+        Ok(value)
     }
 }
 
@@ -127,10 +519,12 @@ impl Cache {
 
         let mut servers = Vec::new();
         if let Some(server) = config["servers"]["server"].as_str() {
-            servers.push(format!("memcache://{server}"));
+            servers.push(server.to_string());
         } else {
             for server in config["servers"]["server"].members() {
-                servers.push(format!("memcache://{server}"));
+                if let Some(s) = server.as_str() {
+                    servers.push(s.to_string());
+                }
             }
         }
 
@@ -144,29 +538,80 @@ impl Cache {
             .map(|n| n as u32)
             .unwrap_or(DEFAULT_MAX_CACHE_SIZE);
 
-        log::info!("Connecting to cache servers: {servers:?}");
+        let compress_threshold = config["compress_threshold"]
+            .as_int()
+            .map(|n| n as usize)
+            .unwrap_or(DEFAULT_COMPRESS_THRESHOLD);
+
+        let backend_name = config["backend"].as_str().unwrap_or("memcache");
+
+        let backend: Box<dyn CacheBackend> = match backend_name {
+            "memcache" => Box::new(MemcacheBackend::connect(&servers)?),
+            "redis" => {
+                let server = servers
+                    .first()
+                    .ok_or_else(|| format!("Cache {cache_name} has no Redis server"))?;
+
+                Box::new(RedisCacheBackend::connect(
+                    server,
+                    config["username"].as_str(),
+                    config["password"].as_str(),
+                )?)
+            }
+            "memory" => {
+                let max_entries = config["max_entries"]
+                    .as_int()
+                    .map(|n| n as usize)
+                    .unwrap_or(DEFAULT_MAX_MEMORY_ENTRIES);
 
-        let mc = match memcache::connect(servers) {
-            Ok(mc) => mc,
-            Err(e) => {
-                return Err(format!(
-                    "Cannot connect to memcache with config: {} : {e}",
-                    config.clone().into_json_value().dump()
-                )
-                .into());
+                Box::new(MemoryCacheBackend::shared(cache_name, max_entries))
             }
+            _ => return Err(format!("Invalid cache backend: {backend_name}").into()),
         };
 
+        Cache::init_cache_with(
+            cache_name,
+            backend,
+            cache_time,
+            cache_size,
+            compress_threshold,
+        );
+
+        Ok(())
+    }
+
+    /// Initialize a cache using the provided backend instead of
+    /// the host settings configuration.
+    ///
+    /// Replaces any existing connection for the named cache.
+    pub fn init_cache_with(
+        cache_name: &str,
+        backend: Box<dyn CacheBackend>,
+        max_cache_time: u32,
+        max_cache_size: u32,
+        compress_threshold: usize,
+    ) {
         let cache = CacheConnection {
-            name: GLOBAL_CACHE_NAME.to_string(),
-            memcache: mc,
-            max_cache_time: cache_time,
-            max_cache_size: cache_size,
+            name: cache_name.to_string(),
+            backend,
+            max_cache_time,
+            max_cache_size,
+            compress_threshold,
         };
 
-        CACHE_CONNECTIONS.with(|c| c.borrow_mut().insert(GLOBAL_CACHE_NAME.to_string(), cache));
+        CACHE_CONNECTIONS.with(|c| c.borrow_mut().insert(cache_name.to_string(), cache));
+    }
 
-        Ok(())
+    /// Usage counters for the specified cache across all threads
+    /// in this process.
+    pub fn stats(cache_name: &str) -> CacheStats {
+        cache_stats().get(cache_name).cloned().unwrap_or_default()
+    }
+
+    /// Usage counters for every cache used in this process, keyed
+    /// on cache name.
+    pub fn all_stats() -> BTreeMap<String, CacheStats> {
+        cache_stats().clone()
     }
 
     /// Remove a thing from the cache.
@@ -174,7 +619,7 @@ impl Cache {
         Cache::verify_cache(cache_name)?;
 
         let mut result = Ok(());
-        CACHE_CONNECTIONS.with(|c| result = c.borrow().get(cache_name).unwrap().del(key));
+        CACHE_CONNECTIONS.with(|c| result = c.borrow_mut().get_mut(cache_name).unwrap().del(key));
         result
     }
 
@@ -191,7 +636,7 @@ impl Cache {
     pub fn get(cache_name: &str, key: &str) -> EgResult<Option<EgValue>> {
         Cache::verify_cache(cache_name)?;
         let mut result = Ok(None);
-        CACHE_CONNECTIONS.with(|c| result = c.borrow().get(cache_name).unwrap().get(key));
+        CACHE_CONNECTIONS.with(|c| result = c.borrow_mut().get_mut(cache_name).unwrap().get(key));
         result
     }
 
@@ -210,8 +655,13 @@ impl Cache {
         Cache::verify_cache(cache_name)?;
 
         let mut result = Ok(());
-        CACHE_CONNECTIONS
-            .with(|c| result = c.borrow().get(cache_name).unwrap().set(key, value, timeout));
+        CACHE_CONNECTIONS.with(|c| {
+            result = c
+                .borrow_mut()
+                .get_mut(cache_name)
+                .unwrap()
+                .set(key, value, timeout)
+        });
         result
     }
 
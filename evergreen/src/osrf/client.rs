@@ -4,15 +4,21 @@ use crate::osrf::conf;
 use crate::osrf::message;
 use crate::osrf::params::ApiParams;
 use crate::osrf::session::ClientSession;
+use crate::osrf::session::RequestGroup;
 use crate::osrf::session::ResponseIterator;
 use crate::util;
 use crate::{EgResult, EgValue};
 use log::info;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt;
 use std::rc::Rc;
 
+/// How many cancelled session threads we remember so that late
+/// replies to them can be discarded.
+const MAX_CANCELLED_THREADS: usize = 256;
+
 /// Generally speaking, we only need 1 ClientSingleton per thread (hence
 /// the name).  This manages one bus connection per domain and stores
 /// messages pulled from the bus that have not yet been processed by
@@ -31,6 +37,9 @@ pub struct ClientSingleton {
     /// Queue of receieved transport messages that have yet to be
     /// processed by any sessions.
     backlog: Vec<message::TransportMessage>,
+
+    /// Threads whose replies we no longer care about.
+    cancelled: VecDeque<String>,
 }
 
 impl ClientSingleton {
@@ -41,12 +50,13 @@ impl ClientSingleton {
 
     /// Create a new singleton instance from a previously setup Bus.
     fn from_bus(bus: bus::Bus) -> ClientSingleton {
-        let domain = conf::config().client().domain().name();
+        let domain = bus.domain().to_string();
 
         ClientSingleton {
-            domain: domain.to_string(),
+            domain,
             bus: Some(bus),
             backlog: Vec::new(),
+            cancelled: VecDeque::new(),
             remote_bus_map: HashMap::new(),
         }
     }
@@ -57,6 +67,27 @@ impl ClientSingleton {
         self.backlog.clear();
     }
 
+    /// Discard any queued messages for the session thread and drop
+    /// any replies for the thread which arrive later.
+    pub fn cancel_thread(&mut self, thread: &str) {
+        self.backlog.retain(|tm| tm.thread() != thread);
+
+        if self.cancelled.len() >= MAX_CANCELLED_THREADS {
+            self.cancelled.pop_front();
+        }
+
+        self.cancelled.push_back(thread.to_string());
+    }
+
+    /// Add a message to our backlog unless its thread was cancelled.
+    fn push_backlog(&mut self, tm: message::TransportMessage) {
+        if self.cancelled.iter().any(|t| t == tm.thread()) {
+            log::debug!("Discarding reply for cancelled thread {}", tm.thread());
+        } else {
+            self.backlog.push(tm);
+        }
+    }
+
     /// Our full bus address as a string
     fn address(&self) -> &str {
         self.bus().address().as_str()
@@ -163,14 +194,43 @@ impl ClientSingleton {
 
         while self.backlog.is_empty() && !timer.done() {
             if let Some(tm) = self.bus_mut().recv(timer.remaining(), None)? {
-                self.backlog.push(tm);
-                break;
+                self.push_backlog(tm);
             }
         }
 
         Ok(!self.backlog.is_empty())
     }
 
+    /// Returns true if any data for the provided session threads
+    /// exists in the backlog within the timeout provided.
+    ///
+    /// Unlike wait(), messages for other sessions do not end the wait.
+    /// A negative timeout waits indefinitely.
+    pub fn wait_for_threads(&mut self, threads: &[&str], timeout: i32) -> EgResult<bool> {
+        let found = |backlog: &Vec<message::TransportMessage>| {
+            backlog.iter().any(|tm| threads.contains(&tm.thread()))
+        };
+
+        if found(&self.backlog) {
+            return Ok(true);
+        }
+
+        let timer = util::Timer::new(timeout);
+
+        while timeout < 0 || !timer.done() {
+            let remaining = if timeout < 0 { -1 } else { timer.remaining() };
+
+            if let Some(tm) = self.bus_mut().recv(remaining, None)? {
+                self.push_backlog(tm);
+                if found(&self.backlog) {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
     /// Receive up to one message destined for the specified session.
     pub fn recv_session(
         &mut self,
@@ -190,7 +250,7 @@ impl ClientSingleton {
             // See what we can pull from the message bus
 
             if let Some(tm) = self.bus_mut().recv(timer.remaining(), None)? {
-                self.backlog.push(tm);
+                self.push_backlog(tm);
             }
 
             // Loop back around and see if we can pull a transport
@@ -330,6 +390,12 @@ impl Client {
         ClientSession::new(self.clone(), service)
     }
 
+    /// Create a new request group for sending requests to any number
+    /// of services in parallel.
+    pub fn request_group(&self) -> RequestGroup {
+        RequestGroup::new(self.clone())
+    }
+
     /// Discard any unprocessed messages from our backlog and clear our
     /// stream of pending messages on the bus.
     pub fn clear(&self) -> EgResult<()> {
@@ -353,7 +419,7 @@ impl Client {
     /// Send a request and receive a ResponseIterator for iterating
     /// the responses to the method.
     ///
-    /// Uses the default request timeout DEFAULT_REQUEST_TIMEOUT.
+    /// Uses the request timeout from the client policy for the service.
     pub fn send_recv_iter(
         &self,
         service: &str,
@@ -1,5 +1,7 @@
+use crate::osrf::session::DEFAULT_REQUEST_TIMEOUT;
 use gethostname::gethostname;
 use roxmltree;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs;
 use std::str::FromStr;
@@ -8,6 +10,12 @@ use syslog;
 
 static GLOBAL_OSRF_CONFIG: OnceLock<Config> = OnceLock::new();
 
+// Health checks are opt-in.  See Router::health_check_interval().
+const DEFAULT_HEALTH_CHECK_INTERVAL: u64 = 0;
+const DEFAULT_STALE_QUEUE_TIMEOUT: u64 = 120;
+const DEFAULT_BREAKER_RESET: u64 = 30;
+const DEFAULT_SIGNATURE_MAX_AGE: u64 = 300;
+
 /// Returns a ref to the globab OpenSRF config.
 ///
 /// Panics if no configuration has been loaded.
@@ -26,6 +34,31 @@ const DEFAULT_BUS_PORT: u16 = 6379;
 pub enum LogFile {
     Syslog,
     Filename(String),
+    /// Plain lines written to STDERR.
+    Stderr,
+    /// Lines written to STDERR with a syslog-style "<N>" severity
+    /// prefix, which the systemd journal uses as the log priority.
+    Journald,
+}
+
+/// Format of each log line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Traditional OpenSRF syslog-style text.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl TryFrom<&str> for LogFormat {
+    type Error = String;
+    fn try_from(s: &str) -> Result<LogFormat, Self::Error> {
+        match s {
+            "text" | "syslog" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Invalid log format: {s}")),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -34,9 +67,22 @@ pub struct LogOptions {
     log_file: Option<LogFile>,
     syslog_facility: Option<syslog::Facility>,
     activity_log_facility: Option<syslog::Facility>,
+    log_format: Option<LogFormat>,
 }
 
 impl LogOptions {
+    /// Options for logging to syslog with default level and facility,
+    /// for applications which run without an OpenSRF configuration.
+    pub fn syslog() -> LogOptions {
+        LogOptions {
+            log_level: None,
+            log_file: Some(LogFile::Syslog),
+            syslog_facility: None,
+            activity_log_facility: None,
+            log_format: None,
+        }
+    }
+
     pub fn syslog_facility(&self) -> Option<syslog::Facility> {
         self.syslog_facility
     }
@@ -55,6 +101,12 @@ impl LogOptions {
     pub fn log_file(&self) -> &Option<LogFile> {
         &self.log_file
     }
+    pub fn log_format(&self) -> Option<LogFormat> {
+        self.log_format
+    }
+    pub fn set_log_format(&mut self, format: LogFormat) {
+        self.log_format = Some(format);
+    }
     pub fn log_level(&self) -> &Option<log::LevelFilter> {
         &self.log_level
     }
@@ -100,6 +152,192 @@ impl fmt::Display for BusDomain {
     }
 }
 
+/// Where to serve OpenMetrics data over HTTP.
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    address: String,
+    port: u16,
+}
+
+impl MetricsConfig {
+    pub fn new(address: &str, port: u16) -> Self {
+        MetricsConfig {
+            address: address.to_string(),
+            port,
+        }
+    }
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+/// Per-domain shared keys for signing and encrypting bus messages.
+///
+/// See osrf::signing.
+#[derive(Clone)]
+pub struct MessageSecurity {
+    /// Shared secret per bus domain.
+    keys: HashMap<String, String>,
+    require_signatures: bool,
+    encrypt: bool,
+    max_age: u64,
+}
+
+impl Default for MessageSecurity {
+    fn default() -> Self {
+        MessageSecurity {
+            keys: HashMap::new(),
+            require_signatures: false,
+            encrypt: false,
+            max_age: DEFAULT_SIGNATURE_MAX_AGE,
+        }
+    }
+}
+
+impl fmt::Debug for MessageSecurity {
+    /// Avoid leaking keys into the logs.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut domains: Vec<&String> = self.keys.keys().collect();
+        domains.sort();
+
+        f.debug_struct("MessageSecurity")
+            .field("domains", &domains)
+            .field("require_signatures", &self.require_signatures)
+            .field("encrypt", &self.encrypt)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
+
+impl MessageSecurity {
+    /// Shared key for messages sent from the provided domain.
+    pub fn key(&self, domain: &str) -> Option<&str> {
+        self.keys.get(domain).map(|k| k.as_str())
+    }
+    pub fn set_key(&mut self, domain: &str, key: &str) {
+        self.keys.insert(domain.to_string(), key.to_string());
+    }
+    /// If true, unsigned messages are rejected even when they come
+    /// from a domain we have no key for.
+    pub fn require_signatures(&self) -> bool {
+        self.require_signatures
+    }
+    pub fn set_require_signatures(&mut self, require: bool) {
+        self.require_signatures = require;
+    }
+    /// If true, message bodies are encrypted when we have a key for
+    /// the sending domain.
+    pub fn encrypt(&self) -> bool {
+        self.encrypt
+    }
+    pub fn set_encrypt(&mut self, encrypt: bool) {
+        self.encrypt = encrypt;
+    }
+    /// Signed messages older than this many seconds are rejected.
+    pub fn max_age(&self) -> u64 {
+        self.max_age
+    }
+    pub fn set_max_age(&mut self, seconds: u64) {
+        self.max_age = seconds;
+    }
+}
+
+/// Client-side handling of API calls to a service.
+#[derive(Debug, Clone)]
+pub struct ClientPolicy {
+    timeout: i32,
+    retries: u32,
+    retry_not_found: bool,
+    idempotent: Vec<String>,
+    breaker_threshold: u32,
+    breaker_reset: u64,
+}
+
+impl Default for ClientPolicy {
+    fn default() -> Self {
+        ClientPolicy {
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            retries: 0,
+            retry_not_found: false,
+            idempotent: Vec::new(),
+            breaker_threshold: 0,
+            breaker_reset: DEFAULT_BREAKER_RESET,
+        }
+    }
+}
+
+impl ClientPolicy {
+    /// Default number of seconds to wait for each response.
+    pub fn timeout(&self) -> i32 {
+        self.timeout
+    }
+    pub fn set_timeout(&mut self, timeout: i32) {
+        self.timeout = timeout;
+    }
+    /// How many times an idempotent request is resent after a
+    /// timeout, or any request is resent after a service-not-found
+    /// bounce when retry_not_found is set.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+    pub fn set_retries(&mut self, retries: u32) {
+        self.retries = retries;
+    }
+    pub fn retry_not_found(&self) -> bool {
+        self.retry_not_found
+    }
+    pub fn set_retry_not_found(&mut self, retry: bool) {
+        self.retry_not_found = retry;
+    }
+    /// Open the circuit breaker after this many consecutive timeouts.
+    ///
+    /// Zero disables the circuit breaker.
+    pub fn breaker_threshold(&self) -> u32 {
+        self.breaker_threshold
+    }
+    pub fn set_breaker_threshold(&mut self, threshold: u32) {
+        self.breaker_threshold = threshold;
+    }
+    /// Seconds an open circuit waits before allowing a trial request.
+    pub fn breaker_reset(&self) -> u64 {
+        self.breaker_reset
+    }
+    pub fn set_breaker_reset(&mut self, seconds: u64) {
+        self.breaker_reset = seconds;
+    }
+    /// API names, or prefixes ending in "*", which are safe to resend.
+    pub fn idempotent(&self) -> &Vec<String> {
+        &self.idempotent
+    }
+    pub fn add_idempotent(&mut self, pattern: &str) {
+        self.idempotent.push(pattern.to_string());
+    }
+
+    /// True if the method may be resent after a timeout.
+    ///
+    /// ```
+    /// use evergreen::osrf::conf::ClientPolicy;
+    ///
+    /// let mut policy = ClientPolicy::default();
+    /// policy.add_idempotent("open-ils.search.*");
+    /// policy.add_idempotent("open-ils.actor.user.retrieve");
+    ///
+    /// assert!(policy.is_idempotent("open-ils.search.biblio.record.mods_slim.retrieve"));
+    /// assert!(policy.is_idempotent("open-ils.actor.user.retrieve"));
+    /// assert!(!policy.is_idempotent("open-ils.actor.user.retrieve.atomic"));
+    /// assert!(!policy.is_idempotent("open-ils.circ.checkout"));
+    /// ```
+    pub fn is_idempotent(&self, method: &str) -> bool {
+        self.idempotent.iter().any(|p| match p.strip_suffix('*') {
+            Some(prefix) => method.starts_with(prefix),
+            None => method == p,
+        })
+    }
+}
+
 /// A set of bus login credentials
 #[derive(Debug, Clone)]
 pub struct BusClient {
@@ -110,6 +348,11 @@ pub struct BusClient {
     logging: LogOptions,
     settings_config: Option<String>,
     routers: Vec<ClientRouter>,
+    metrics: Option<MetricsConfig>,
+    trace_export: Option<String>,
+    message_security: Option<MessageSecurity>,
+    default_policy: ClientPolicy,
+    policies: HashMap<String, ClientPolicy>,
 }
 
 impl BusClient {
@@ -138,6 +381,21 @@ impl BusClient {
     pub fn routers(&self) -> &Vec<ClientRouter> {
         &self.routers
     }
+    pub fn metrics(&self) -> Option<&MetricsConfig> {
+        self.metrics.as_ref()
+    }
+    /// Where to export trace spans.  See osrf::trace.
+    pub fn trace_export(&self) -> Option<&str> {
+        self.trace_export.as_deref()
+    }
+    /// Keys for signing and verifying bus messages, if configured.
+    pub fn message_security(&self) -> Option<&MessageSecurity> {
+        self.message_security.as_ref()
+    }
+    /// Request handling policy for API calls to the provided service.
+    pub fn client_policy(&self, service: &str) -> &ClientPolicy {
+        self.policies.get(service).unwrap_or(&self.default_policy)
+    }
     pub fn set_domain(&mut self, domain: &str) {
         // Assumes other aspects of the domain are identical
         self.domain.name = domain.to_string();
@@ -169,11 +427,51 @@ impl ClientRouter {
     }
 }
 
+/// How a router chooses among the registered instances of a service.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoutingStrategy {
+    /// Rotate through instances in registration order.
+    RoundRobin,
+    /// Choose the instance with the fewest requests waiting in its
+    /// service queue.
+    LeastLoaded,
+    /// Rotate through instances in proportion to their domain weights.
+    Weighted,
+}
+
+impl TryFrom<&str> for RoutingStrategy {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "round-robin" => Ok(Self::RoundRobin),
+            "least-loaded" => Ok(Self::LeastLoaded),
+            "weighted" => Ok(Self::Weighted),
+            _ => Err(format!("Invalid routing strategy: {s}")),
+        }
+    }
+}
+
+impl From<RoutingStrategy> for &'static str {
+    fn from(s: RoutingStrategy) -> &'static str {
+        match s {
+            RoutingStrategy::RoundRobin => "round-robin",
+            RoutingStrategy::LeastLoaded => "least-loaded",
+            RoutingStrategy::Weighted => "weighted",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Router {
     client: BusClient,
     trusted_server_domains: Vec<String>,
     trusted_client_domains: Vec<String>,
+    admin_domains: Vec<String>,
+    routing_strategy: RoutingStrategy,
+    service_routing: HashMap<String, RoutingStrategy>,
+    domain_weights: HashMap<String, u32>,
+    health_check_interval: u64,
+    stale_queue_timeout: u64,
 }
 
 impl Router {
@@ -189,6 +487,54 @@ impl Router {
     pub fn trusted_client_domains(&self) -> &Vec<String> {
         &self.trusted_client_domains
     }
+
+    /// Domains whose clients may call the opensrf.router.admin.* APIs.
+    ///
+    /// Defaults to the trusted server domains when none are configured.
+    pub fn admin_domains(&self) -> &Vec<String> {
+        if self.admin_domains.is_empty() {
+            &self.trusted_server_domains
+        } else {
+            &self.admin_domains
+        }
+    }
+
+    /// True if clients on the provided domain may call the router
+    /// admin APIs.
+    pub fn is_admin_domain(&self, domain: &str) -> bool {
+        self.admin_domains().iter().any(|d| d == domain)
+    }
+
+    /// Routing strategy for the named service.
+    pub fn routing_strategy(&self, service: &str) -> RoutingStrategy {
+        self.service_routing
+            .get(service)
+            .copied()
+            .unwrap_or(self.routing_strategy)
+    }
+
+    /// Relative capacity of service instances running on the provided
+    /// domain for weighted routing.  Defaults to 1.
+    pub fn domain_weight(&self, domain: &str) -> u32 {
+        self.domain_weights.get(domain).copied().unwrap_or(1)
+    }
+
+    /// How often, in seconds, the router checks the health of its
+    /// registered service instances.  0, the default, disables health
+    /// checks.
+    ///
+    /// Unresponsive instances are suspended rather than removed, and
+    /// resume receiving requests once they start draining their queue.
+    pub fn health_check_interval(&self) -> u64 {
+        self.health_check_interval
+    }
+
+    /// How long, in seconds, a service instance's request queue may go
+    /// without draining before the instance is pinged, and how long
+    /// the ping may go unanswered before the instance is suspended.
+    pub fn stale_queue_timeout(&self) -> u64 {
+        self.stale_queue_timeout
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -303,12 +649,52 @@ impl ConfigBuilder {
             // transport node.
             client.logging = self.unpack_logging_node(&rnode)?;
 
+            // As may the metrics and tracing configs.
+            if let Some(metrics) = self.unpack_metrics_node(&rnode)? {
+                client.metrics = Some(metrics);
+            }
+
+            if let Some(export) = self.unpack_tracing_node(&rnode) {
+                client.trace_export = Some(export);
+            }
+
+            if let Some(security) = self.unpack_message_security_node(&rnode)? {
+                client.message_security = Some(security);
+            }
+
             let mut router = Router {
                 client,
                 trusted_server_domains: Vec::new(),
                 trusted_client_domains: Vec::new(),
+                admin_domains: Vec::new(),
+                routing_strategy: RoutingStrategy::RoundRobin,
+                service_routing: HashMap::new(),
+                domain_weights: HashMap::new(),
+                health_check_interval: DEFAULT_HEALTH_CHECK_INTERVAL,
+                stale_queue_timeout: DEFAULT_STALE_QUEUE_TIMEOUT,
             };
 
+            if let Some(routing) = rnode.children().find(|c| c.has_tag_name("routing")) {
+                self.unpack_routing_node(&mut router, &routing)?;
+            }
+
+            // <health_check>
+            //   <interval>30</interval>
+            //   <stale_timeout>120</stale_timeout>
+            // </health_check>
+            if let Some(hc) = rnode.children().find(|c| c.has_tag_name("health_check")) {
+                if let Some(t) = self.child_node_text(&hc, "interval") {
+                    router.health_check_interval = t
+                        .parse::<u64>()
+                        .map_err(|e| format!("Invalid health check interval: {t} {e}"))?;
+                }
+                if let Some(t) = self.child_node_text(&hc, "stale_timeout") {
+                    router.stale_queue_timeout = t
+                        .parse::<u64>()
+                        .map_err(|e| format!("Invalid health check stale timeout: {t} {e}"))?;
+                }
+            }
+
             for tdnode in rnode
                 .children()
                 .filter(|d| d.has_tag_name("trusted_domains"))
@@ -323,6 +709,11 @@ impl ConfigBuilder {
                         router.trusted_client_domains.push(domain.to_string());
                     }
                 }
+                for anode in tdnode.children().filter(|d| d.has_tag_name("admin")) {
+                    if let Some(domain) = anode.text() {
+                        router.admin_domains.push(domain.to_string());
+                    }
+                }
             }
 
             self.routers.push(router);
@@ -331,6 +722,54 @@ impl ConfigBuilder {
         Ok(())
     }
 
+    /// Unpack a router's routing preferences.
+    ///
+    /// <routing>
+    ///   <strategy>least-loaded</strategy>
+    ///   <services>
+    ///     <service name="open-ils.search">weighted</service>
+    ///   </services>
+    ///   <weights>
+    ///     <domain name="private.host2">3</domain>
+    ///   </weights>
+    /// </routing>
+    fn unpack_routing_node(
+        &mut self,
+        router: &mut Router,
+        node: &roxmltree::Node,
+    ) -> Result<(), String> {
+        if let Some(strategy) = self.child_node_text(node, "strategy") {
+            router.routing_strategy = RoutingStrategy::try_from(strategy.as_str())?;
+        }
+
+        for snode in node.descendants().filter(|n| n.has_tag_name("service")) {
+            let name = snode
+                .attribute("name")
+                .ok_or_else(|| format!("Routing service node has no name: {snode:?}"))?;
+
+            let strategy = RoutingStrategy::try_from(snode.text().unwrap_or(""))?;
+
+            router.service_routing.insert(name.to_string(), strategy);
+        }
+
+        for dnode in node.descendants().filter(|n| n.has_tag_name("domain")) {
+            let name = dnode
+                .attribute("name")
+                .ok_or_else(|| format!("Routing weight node has no name: {dnode:?}"))?;
+
+            let weight = dnode
+                .text()
+                .and_then(|t| t.parse::<u32>().ok())
+                .ok_or_else(|| format!("Invalid routing weight for domain {name}"))?;
+
+            router
+                .domain_weights
+                .insert(name.to_string(), weight.max(1));
+        }
+
+        Ok(())
+    }
+
     fn child_node_text(&self, node: &roxmltree::Node, name: &str) -> Option<String> {
         if let Some(tnode) = node.children().filter(|n| n.has_tag_name(name)).next() {
             if let Some(text) = tnode.text() {
@@ -396,6 +835,173 @@ impl ConfigBuilder {
         Ok(())
     }
 
+    /// <metrics>
+    ///   <address>127.0.0.1</address>
+    ///   <port>9100</port>
+    /// </metrics>
+    fn unpack_metrics_node(
+        &mut self,
+        node: &roxmltree::Node,
+    ) -> Result<Option<MetricsConfig>, String> {
+        let mnode = match node.children().find(|c| c.has_tag_name("metrics")) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+
+        let port = match self.child_node_text(&mnode, "port") {
+            Some(p) => p
+                .parse::<u16>()
+                .map_err(|e| format!("Invalid metrics port: {p} {e}"))?,
+            None => return Err("Metrics config requires a port".to_string()),
+        };
+
+        let address = self
+            .child_node_text(&mnode, "address")
+            .unwrap_or("127.0.0.1".to_string());
+
+        Ok(Some(MetricsConfig { address, port }))
+    }
+
+    /// <tracing>
+    ///   <export>file:/var/log/osrf/spans.jsonl</export>
+    /// </tracing>
+    fn unpack_tracing_node(&self, node: &roxmltree::Node) -> Option<String> {
+        let tnode = node.children().find(|c| c.has_tag_name("tracing"))?;
+        self.child_node_text(&tnode, "export")
+    }
+
+    /// <message_security>
+    ///   <key domain="private.localhost">secret1</key>
+    ///   <key domain="public.localhost" file="/openils/conf/public.key"/>
+    ///   <require_signatures>false</require_signatures>
+    ///   <encrypt>false</encrypt>
+    ///   <max_age>300</max_age>
+    /// </message_security>
+    fn unpack_message_security_node(
+        &self,
+        node: &roxmltree::Node,
+    ) -> Result<Option<MessageSecurity>, String> {
+        let snode = match node.children().find(|c| c.has_tag_name("message_security")) {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+
+        let mut security = MessageSecurity::default();
+
+        for child in snode.children().filter(|c| c.is_element()) {
+            let name = child.tag_name().name();
+            let value = child.text().unwrap_or("").trim();
+
+            match name {
+                "key" => {
+                    let domain = child
+                        .attribute("domain")
+                        .ok_or("Message security keys require a domain")?;
+
+                    let key = match child.attribute("file") {
+                        Some(f) => fs::read_to_string(f)
+                            .map_err(|e| format!("Cannot read key file {f}: {e}"))?
+                            .trim()
+                            .to_string(),
+                        None => value.to_string(),
+                    };
+
+                    if key.is_empty() {
+                        return Err(format!("Empty message security key for {domain}"));
+                    }
+
+                    security.keys.insert(domain.to_string(), key);
+                }
+                "require_signatures" => security.require_signatures = value == "true",
+                "encrypt" => security.encrypt = value == "true",
+                "max_age" => {
+                    security.max_age = value
+                        .parse::<u64>()
+                        .map_err(|e| format!("Invalid message security max_age: {value} {e}"))?
+                }
+                _ => log::warn!("Unknown message security setting: {name}"),
+            }
+        }
+
+        Ok(Some(security))
+    }
+
+    /// <client_policies>
+    ///   <policy>
+    ///     <timeout>60</timeout>
+    ///     <retries>2</retries>
+    ///     <retry_not_found>true</retry_not_found>
+    ///     <idempotent>open-ils.search.*</idempotent>
+    ///     <breaker_threshold>5</breaker_threshold>
+    ///     <breaker_reset>30</breaker_reset>
+    ///   </policy>
+    ///   <policy service="open-ils.sip2">
+    ///     <timeout>30</timeout>
+    ///   </policy>
+    /// </client_policies>
+    ///
+    /// A policy without a service applies to all services.  Values
+    /// not set in a service policy are taken from the default policy.
+    fn unpack_client_policies(
+        &self,
+        node: &roxmltree::Node,
+    ) -> Result<(ClientPolicy, HashMap<String, ClientPolicy>), String> {
+        let mut default_policy = ClientPolicy::default();
+        let mut policies = HashMap::new();
+
+        let pnode = match node.children().find(|c| c.has_tag_name("client_policies")) {
+            Some(n) => n,
+            None => return Ok((default_policy, policies)),
+        };
+
+        let policy_nodes = || pnode.children().filter(|c| c.has_tag_name("policy"));
+
+        // Apply the default policy first so service policies may
+        // inherit from it regardless of the order in the file.
+        for policy_node in policy_nodes().filter(|n| n.attribute("service").is_none()) {
+            self.unpack_client_policy(&policy_node, &mut default_policy)?;
+        }
+
+        for policy_node in policy_nodes() {
+            if let Some(service) = policy_node.attribute("service") {
+                let mut policy = default_policy.clone();
+                self.unpack_client_policy(&policy_node, &mut policy)?;
+                policies.insert(service.to_string(), policy);
+            }
+        }
+
+        Ok((default_policy, policies))
+    }
+
+    fn unpack_client_policy(
+        &self,
+        node: &roxmltree::Node,
+        policy: &mut ClientPolicy,
+    ) -> Result<(), String> {
+        let number = |name: &str, value: &str| {
+            value
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid client policy {name}: {value} {e}"))
+        };
+
+        for child in node.children().filter(|c| c.is_element()) {
+            let name = child.tag_name().name();
+            let value = child.text().unwrap_or("").trim();
+
+            match name {
+                "timeout" => policy.timeout = number(name, value)? as i32,
+                "retries" => policy.retries = number(name, value)? as u32,
+                "retry_not_found" => policy.retry_not_found = value == "true",
+                "idempotent" => policy.idempotent.push(value.to_string()),
+                "breaker_threshold" => policy.breaker_threshold = number(name, value)? as u32,
+                "breaker_reset" => policy.breaker_reset = number(name, value)?,
+                _ => log::warn!("Unknown client policy setting: {name}"),
+            }
+        }
+
+        Ok(())
+    }
+
     fn unpack_client_node(&mut self, node: &roxmltree::Node) -> Result<BusClient, String> {
         let logging = self.unpack_logging_node(node)?;
         let domain = self.unpack_domain_node(node)?;
@@ -431,10 +1037,20 @@ impl ConfigBuilder {
             }
         }
 
+        let metrics = self.unpack_metrics_node(node)?;
+        let trace_export = self.unpack_tracing_node(node);
+        let message_security = self.unpack_message_security_node(node)?;
+        let (default_policy, policies) = self.unpack_client_policies(node)?;
+
         Ok(BusClient {
             domain,
             logging,
             settings_config,
+            metrics,
+            trace_export,
+            message_security,
+            default_policy,
+            policies,
             routers: Vec::new(),
             username: username.to_string(),
             password: password.to_string(),
@@ -478,17 +1094,19 @@ impl ConfigBuilder {
             log_file: None,
             syslog_facility: None,
             activity_log_facility: None,
+            log_format: None,
         };
 
         for child in node.children() {
             match child.tag_name().name() {
                 "logfile" => {
                     if let Some(filename) = child.text() {
-                        if filename.eq("syslog") {
-                            ops.log_file = Some(LogFile::Syslog);
-                        } else {
-                            ops.log_file = Some(LogFile::Filename(filename.to_string()))
-                        }
+                        ops.log_file = Some(match filename {
+                            "syslog" => LogFile::Syslog,
+                            "stderr" => LogFile::Stderr,
+                            "journald" => LogFile::Journald,
+                            _ => LogFile::Filename(filename.to_string()),
+                        });
                     }
                 }
                 "syslog" => {
@@ -505,6 +1123,11 @@ impl ConfigBuilder {
                         }
                     }
                 }
+                "logformat" => {
+                    if let Some(f) = child.text() {
+                        ops.log_format = Some(LogFormat::try_from(f)?);
+                    }
+                }
                 "loglevel" => {
                     if let Some(level_num) = child.text() {
                         ops.log_level = Some(LogOptions::log_level_from_str(level_num));
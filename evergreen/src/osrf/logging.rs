@@ -29,6 +29,7 @@ pub struct Logger {
     activity_facility: syslog::Facility,
     writer: Option<UnixDatagram>,
     application: String,
+    format: conf::LogFormat,
 }
 
 impl Logger {
@@ -59,6 +60,7 @@ impl Logger {
             activity_facility: act_facility.clone(),
             writer: None,
             application: Logger::find_app_name(),
+            format: options.log_format().unwrap_or(conf::LogFormat::Text),
         })
     }
 
@@ -87,6 +89,10 @@ impl Logger {
         self.facility = facility;
     }
 
+    pub fn set_format(&mut self, format: conf::LogFormat) {
+        self.format = format;
+    }
+
     /// Setup our global log handler.
     ///
     /// Attempts to connect to syslog unix socket if possible.
@@ -113,6 +119,7 @@ impl Logger {
                     return Err(err);
                 }
             }
+            conf::LogFile::Stderr | conf::LogFile::Journald => {}
         }
 
         log::set_max_level(self.loglevel);
@@ -220,26 +227,46 @@ impl log::Log for Logger {
             })
         };
 
-        let mut message = format!(
-            "{}{} [{}:{}:{}:{}",
-            match self.writer.is_some() {
-                true => format!("<{}>", severity),
-                _ => format!("{} ", date::epoch_secs()),
-            },
-            &self.application,
-            levelname,
-            process::id(),
-            target,
-            match record.line() {
-                Some(l) => l,
-                _ => 0,
+        let line = record.line().unwrap_or(0);
+
+        let body = match self.format {
+            conf::LogFormat::Text => format!(
+                "{} [{}:{}:{}:{}:{}] {}",
+                &self.application,
+                levelname,
+                process::id(),
+                target,
+                line,
+                Logger::get_log_trace(),
+                logmsg
+            ),
+            conf::LogFormat::Json => json::object! {
+                "timestamp": date::to_iso_millis(&date::now()),
+                "level": levelname.as_str(),
+                "application": self.application.as_str(),
+                "pid": process::id(),
+                "thread": util::thread_id(),
+                "trace": Logger::get_log_trace(),
+                "module": target,
+                "line": line,
+                "message": logmsg,
             }
-        );
+            .dump(),
+        };
 
-        // Add the thread-local log trace
-        THREAD_LOCAL_LOG_TRACE.with(|tr| message += &format!(":{}] ", *tr.borrow()));
+        let prefix = if self.writer.is_some() {
+            format!("<{severity}>")
+        } else if self.logfile == conf::LogFile::Journald {
+            // The journal only wants the severity portion.
+            format!("<{}>", severity & 0x07)
+        } else if self.format == conf::LogFormat::Text {
+            format!("{} ", date::epoch_secs())
+        } else {
+            // JSON entries contain their own timestamp.
+            String::new()
+        };
 
-        message += &logmsg;
+        let mut message = prefix + &body;
 
         if let Some(ref w) = self.writer {
             if w.send(message.as_bytes()).is_ok() {
@@ -257,6 +284,12 @@ impl log::Log for Logger {
                     return;
                 }
             }
+        } else if matches!(
+            self.logfile,
+            conf::LogFile::Stderr | conf::LogFile::Journald
+        ) {
+            eprintln!("{message}");
+            return;
         }
 
         // If all else fails, print the log message.
@@ -0,0 +1,238 @@
+//! In-process message bus.
+//!
+//! Provides the same list operations as Redis using shared in-memory
+//! queues so OpenSRF clients, services, and routers can communicate
+//! inside a single process, e.g. within `cargo test`, without a
+//! running Redis instance.
+use crate::osrf::bus::BusBackend;
+use crate::EgResult;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Process-wide hub used by Bus::new() once installed.
+static GLOBAL_HUB: OnceLock<Arc<MemoryHub>> = OnceLock::new();
+
+/// Install a process-wide MemoryHub and return it.
+///
+/// Once installed, every Bus created via Bus::new() in this process
+/// connects to the hub instead of Redis, so code which creates its
+/// own bus connections (e.g. osrf::server::Server, the router) can
+/// be exercised without Redis.
+pub fn install_global_hub() -> Arc<MemoryHub> {
+    GLOBAL_HUB
+        .get_or_init(|| Arc::new(MemoryHub::new()))
+        .clone()
+}
+
+/// The process-wide MemoryHub, if one has been installed.
+pub fn global_hub() -> Option<Arc<MemoryHub>> {
+    GLOBAL_HUB.get().cloned()
+}
+
+/// A single message queue.
+#[derive(Default)]
+struct Queue {
+    values: VecDeque<String>,
+    expires: Option<Instant>,
+}
+
+impl Queue {
+    fn expired(&self) -> bool {
+        self.expires.map(|e| e <= Instant::now()).unwrap_or(false)
+    }
+}
+
+/// Collection of named message queues shared by any number of
+/// MemoryBackend connections.
+#[derive(Default)]
+pub struct MemoryHub {
+    queues: Mutex<HashMap<String, Queue>>,
+
+    /// Wakes blocked receivers when new values arrive.
+    arrivals: Condvar,
+}
+
+impl MemoryHub {
+    pub fn new() -> MemoryHub {
+        Default::default()
+    }
+
+    /// Create a new connection to this hub.
+    pub fn connect(self: &Arc<Self>) -> MemoryBackend {
+        MemoryBackend { hub: self.clone() }
+    }
+
+    /// Lock our queues, removing any that have expired.
+    fn queues(&self) -> MutexGuard<'_, HashMap<String, Queue>> {
+        // A poisoned lock means another thread panicked mid-operation.
+        // Queue data remains usable.
+        let mut queues = self.queues.lock().unwrap_or_else(|e| e.into_inner());
+        queues.retain(|_, q| !q.expired());
+        queues
+    }
+}
+
+/// One connection to a MemoryHub.
+pub struct MemoryBackend {
+    hub: Arc<MemoryHub>,
+}
+
+impl BusBackend for MemoryBackend {
+    fn pop(&mut self, key: &str, timeout: i32) -> EgResult<Option<String>> {
+        let deadline = match timeout {
+            t if t > 0 => Some(Instant::now() + Duration::from_secs(t as u64)),
+            _ => None,
+        };
+
+        let mut queues = self.hub.queues();
+
+        loop {
+            if let Some(queue) = queues.get_mut(key) {
+                if let Some(value) = queue.values.pop_front() {
+                    // Like Redis, empty lists cease to exist.
+                    if queue.values.is_empty() {
+                        queues.remove(key);
+                    }
+                    return Ok(Some(value));
+                }
+            }
+
+            if timeout == 0 {
+                return Ok(None);
+            }
+
+            queues = match deadline {
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return Ok(None);
+                    }
+                    self.hub
+                        .arrivals
+                        .wait_timeout(queues, d - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .hub
+                    .arrivals
+                    .wait(queues)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+
+            queues.retain(|_, q| !q.expired());
+        }
+    }
+
+    fn push(&mut self, key: &str, value: String) -> EgResult<()> {
+        self.hub
+            .queues()
+            .entry(key.to_string())
+            .or_default()
+            .values
+            .push_back(value);
+
+        self.hub.arrivals.notify_all();
+
+        Ok(())
+    }
+
+    fn keys(&mut self, pattern: &str) -> EgResult<Vec<String>> {
+        Ok(self
+            .hub
+            .queues()
+            .keys()
+            .filter(|k| glob_match(pattern, k))
+            .cloned()
+            .collect())
+    }
+
+    fn llen(&mut self, key: &str) -> EgResult<i32> {
+        Ok(self
+            .hub
+            .queues()
+            .get(key)
+            .map(|q| q.values.len() as i32)
+            .unwrap_or(0))
+    }
+
+    fn ttl(&mut self, key: &str) -> EgResult<i32> {
+        let ttl = match self.hub.queues().get(key) {
+            Some(q) => match q.expires {
+                Some(e) => e.saturating_duration_since(Instant::now()).as_secs() as i32,
+                None => -1,
+            },
+            None => -2,
+        };
+
+        Ok(ttl)
+    }
+
+    fn lrange(&mut self, key: &str, start: isize, stop: isize) -> EgResult<Vec<String>> {
+        let queues = self.hub.queues();
+
+        let values = match queues.get(key) {
+            Some(q) => &q.values,
+            None => return Ok(Vec::new()),
+        };
+
+        // Negative indexes count from the end of the list.
+        let len = values.len() as isize;
+        let start = if start < 0 { len + start } else { start }.max(0);
+        let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+
+        if start > stop {
+            return Ok(Vec::new());
+        }
+
+        Ok(values
+            .range(start as usize..=stop as usize)
+            .cloned()
+            .collect())
+    }
+
+    fn expire(&mut self, key: &str, timeout: u64) -> EgResult<i32> {
+        match self.hub.queues().get_mut(key) {
+            Some(q) => {
+                q.expires = Some(Instant::now() + Duration::from_secs(timeout));
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn del(&mut self, key: &str) -> EgResult<i32> {
+        Ok(self.hub.queues().remove(key).map(|_| 1).unwrap_or(0))
+    }
+}
+
+/// Redis-style glob matching supporting '*' and '?' wildcards.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Position of the most recent '*' in the pattern and the text
+    // position it is currently matched up to.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut t) = (0, 0);
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the last '*' absorb one more character.
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -252,6 +252,8 @@ pub struct TransportMessage {
     router_command: Option<String>,
     router_class: Option<String>,
     router_reply: Option<String>,
+    /// W3C trace context.  See osrf::trace.
+    traceparent: Option<String>,
     body: Vec<Message>,
 }
 
@@ -265,6 +267,7 @@ impl TransportMessage {
             router_command: None,
             router_class: None,
             router_reply: None,
+            traceparent: None,
             body: Vec::new(),
         }
     }
@@ -345,6 +348,14 @@ impl TransportMessage {
         self.router_reply = Some(reply.to_string());
     }
 
+    pub fn traceparent(&self) -> Option<&str> {
+        self.traceparent.as_deref()
+    }
+
+    pub fn set_traceparent(&mut self, traceparent: Option<&str>) {
+        self.traceparent = traceparent.map(|t| t.to_string());
+    }
+
     /// Create a TransportMessage from a JSON object, consuming the JSON value.
     ///
     /// Returns None if the JSON value cannot be coerced into a TransportMessage.
@@ -373,6 +384,10 @@ impl TransportMessage {
             tmsg.set_router_reply(rc);
         }
 
+        if let Some(tp) = json_obj["traceparent"].as_str() {
+            tmsg.set_traceparent(Some(tp));
+        }
+
         let body = json_obj["body"].take();
 
         if let JsonValue::Array(arr) = body {
@@ -416,6 +431,10 @@ impl TransportMessage {
             obj["router_reply"] = rc.into();
         }
 
+        if let Some(tp) = self.traceparent() {
+            obj["traceparent"] = tp.into();
+        }
+
         obj
     }
 }
@@ -6,6 +6,9 @@ use crate::EgValue;
 use json::JsonValue;
 use std::fmt;
 
+/// Limit the size of invalid parameter values included in error messages.
+const MAX_PARAM_DISPLAY: usize = 256;
+
 pub type MethodHandler = fn(
     &mut Box<dyn app::ApplicationWorker>,
     &mut session::ServerSession,
@@ -77,6 +80,8 @@ pub enum ParamDataType {
     Object, // JsonValue::Object or other object-y thing
     Boolish,
     Scalar, // Not an Object or Array.
+    /// IDL object of the specified class, e.g. IdlObject("aou")
+    IdlObject(&'static str),
     Any,
 }
 
@@ -89,6 +94,7 @@ impl fmt::Display for ParamDataType {
             ParamDataType::Object => "Object",
             ParamDataType::Boolish => "Boolish",
             ParamDataType::Scalar => "Scalar",
+            ParamDataType::IdlObject(c) => return write!(f, "Object<{c}>"),
             ParamDataType::Any => "Any",
         };
         write!(f, "{s}")
@@ -112,9 +118,18 @@ impl ParamDataType {
             ParamDataType::Scalar => {
                 param.is_boolean() || param.is_number() || param.is_string() || param.is_null()
             }
+            ParamDataType::IdlObject(c) => param.classname() == Some(c),
             ParamDataType::Any => true,
         }
     }
+
+    /// IDL classname for IdlObject params.
+    pub fn classname(&self) -> Option<&'static str> {
+        match *self {
+            ParamDataType::IdlObject(c) => Some(c),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -136,6 +151,10 @@ impl Param {
         EgValue::from_json_value_plain(json::object! {
             "name": self.name.as_str(),
             "datatype": self.datatype.to_string(),
+            "class": match self.datatype.classname() {
+                Some(c) => c.into(),
+                _ => JsonValue::Null,
+            },
             "desc": match self.desc.as_ref() {
                 Some(d) => d.as_str().into(),
                 _ => JsonValue::Null,
@@ -144,6 +163,47 @@ impl Param {
     }
 }
 
+/// Authentication and permission checks applied by the worker before
+/// a method handler is called.
+///
+/// When the checks pass, the handler can collect the authenticated
+/// Editor via ServerSession::take_editor().  Otherwise, the caller
+/// receives the NO_SESSION or PERM_FAILURE event and the handler is
+/// never called.
+#[derive(Clone, Copy, Debug)]
+pub struct MethodAuth {
+    /// Position of the authtoken parameter.
+    pub authtoken_param: usize,
+
+    /// Permissions the requestor must have.  All are required.
+    pub perms: &'static [&'static str],
+
+    /// Position of the parameter containing the org unit ID where
+    /// permissions are checked.  If None, permissions are checked at
+    /// the requestor's workstation org unit, or their home org unit
+    /// when no workstation is in use.
+    pub org_param: Option<usize>,
+}
+
+impl MethodAuth {
+    pub fn to_eg_value(&self) -> EgValue {
+        let mut perms = EgValue::new_array();
+        for perm in self.perms {
+            perms.push(*perm).expect("Is Array");
+        }
+
+        let mut value = EgValue::new_object();
+        value["authtoken_param"] = EgValue::from(self.authtoken_param);
+        value["perms"] = perms;
+        value["org_param"] = match self.org_param {
+            Some(p) => EgValue::from(p),
+            None => EgValue::Null,
+        };
+
+        value
+    }
+}
+
 /// A variation of a Method that can be used when creating static
 /// method definitions.
 pub struct StaticMethodDef {
@@ -152,6 +212,7 @@ pub struct StaticMethodDef {
     pub param_count: ParamCount,
     pub handler: MethodHandler,
     pub params: &'static [StaticParam],
+    pub auth: Option<MethodAuth>,
 }
 
 impl StaticMethodDef {
@@ -197,6 +258,8 @@ impl StaticMethodDef {
             m.desc = Some(self.desc.to_string());
         }
 
+        m.auth = self.auth;
+
         m
     }
 }
@@ -208,6 +271,7 @@ pub struct MethodDef {
     pub param_count: ParamCount,
     pub handler: MethodHandler,
     pub params: Option<Vec<Param>>,
+    pub auth: Option<MethodAuth>,
 }
 
 impl MethodDef {
@@ -216,6 +280,7 @@ impl MethodDef {
             handler,
             param_count,
             params: None,
+            auth: None,
             desc: None,
             name: name.to_string(),
         }
@@ -241,6 +306,14 @@ impl MethodDef {
         self.params.as_ref()
     }
 
+    pub fn auth(&self) -> Option<&MethodAuth> {
+        self.auth.as_ref()
+    }
+
+    pub fn set_auth(&mut self, auth: MethodAuth) {
+        self.auth = Some(auth);
+    }
+
     pub fn desc(&self) -> Option<&str> {
         self.desc.as_deref()
     }
@@ -259,15 +332,109 @@ impl MethodDef {
         params.push(param);
     }
 
+    /// Returns true if the parameter at the provided position must
+    /// be supplied by the caller.
+    pub fn param_is_required(&self, index: usize) -> bool {
+        index < self.param_count.minimum() as usize
+    }
+
+    /// Verify the parameters sent by the caller match the parameter
+    /// count and any parameter definitions for this method.
+    ///
+    /// Returns a description of the first problem found.
+    ///
+    /// ```
+    /// use evergreen::osrf::method::*;
+    /// use evergreen::EgValue;
+    ///
+    /// let mut m = MethodDef::new("test.method", ParamCount::Range(1, 2), |_, _, _| Ok(()));
+    ///
+    /// m.add_param(Param {
+    ///     name: "name".to_string(),
+    ///     datatype: ParamDataType::String,
+    ///     desc: None,
+    /// });
+    ///
+    /// m.add_param(Param {
+    ///     name: "count".to_string(),
+    ///     datatype: ParamDataType::Number,
+    ///     desc: None,
+    /// });
+    ///
+    /// assert!(m.validate_params(&[EgValue::from("a")]).is_ok());
+    /// assert!(m.validate_params(&[EgValue::from("a"), EgValue::Null]).is_ok());
+    /// assert!(m.validate_params(&[]).is_err());
+    /// assert!(m.validate_params(&[EgValue::from(1)]).is_err());
+    /// assert!(m.validate_params(&[EgValue::from("a"), EgValue::from("b")]).is_err());
+    /// ```
+    pub fn validate_params(&self, params: &[EgValue]) -> Result<(), String> {
+        let count = params.len();
+
+        if count > u8::MAX as usize || !ParamCount::matches(&self.param_count, count as u8) {
+            let mut msg = format!(
+                "Invalid param count sent: method={} sent={count} needed={}",
+                self.name, self.param_count,
+            );
+
+            // Name the first missing required parameter if we can.
+            if let Some(param_defs) = self.params() {
+                if let Some(p) = param_defs.get(count) {
+                    if self.param_is_required(count) {
+                        msg += &format!(" missing={}", p.name);
+                    }
+                }
+            }
+
+            return Err(msg);
+        }
+
+        let param_defs = match self.params() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        // There may be more param defs than parameters if some
+        // params are optional.
+        for (idx, (param_def, value)) in param_defs.iter().zip(params.iter()).enumerate() {
+            if value.is_null() {
+                if self.param_is_required(idx) {
+                    // NULL placeholders are only allowed for
+                    // non-required parameters.
+                    return Err(format!(
+                        "Required parameter '{}' at position {idx} is NULL",
+                        param_def.name
+                    ));
+                }
+                continue;
+            }
+
+            if !param_def.datatype.matches(value) {
+                let mut got = value.clone().dump();
+                if got.len() > MAX_PARAM_DISPLAY {
+                    got = got.chars().take(MAX_PARAM_DISPLAY).collect::<String>() + "...";
+                }
+
+                return Err(format!(
+                    "Invalid value for parameter '{}' at position {idx}: wanted={} got={got}",
+                    param_def.name, param_def.datatype,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn to_eg_value(&self) -> EgValue {
         let mut pa = EgValue::new_array();
         if let Some(params) = self.params() {
-            for param in params {
-                pa.push(param.to_eg_value()).expect("Is Array");
+            for (idx, param) in params.iter().enumerate() {
+                let mut pv = param.to_eg_value();
+                pv["required"] = EgValue::from(self.param_is_required(idx));
+                pa.push(pv).expect("Is Array");
             }
         }
 
-        EgValue::from_json_value_plain(json::object! {
+        let mut value = EgValue::from_json_value_plain(json::object! {
             "api_name": self.name(),
             "argc": self.param_count().to_string(),
             "params": pa.into_json_value(),
@@ -277,7 +444,13 @@ impl MethodDef {
                 Some(d) => d.into(),
                 _ => JsonValue::Null,
             }
-        })
+        });
+
+        if let Some(auth) = self.auth() {
+            value["auth"] = auth.to_eg_value();
+        }
+
+        value
     }
 
     /// Produces e.g. "foo.bar.baz('param1', 'param2')"
@@ -292,7 +465,7 @@ impl MethodDef {
         if let Some(params) = self.params() {
             let minimum = self.param_count.minimum();
             for (idx, param) in params.iter().enumerate() {
-                let required = if idx <= minimum as usize {
+                let required = if idx < minimum as usize {
                     "*" // required
                 } else {
                     ""
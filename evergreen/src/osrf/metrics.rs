@@ -0,0 +1,390 @@
+//! OpenMetrics (Prometheus) reporting.
+//!
+//! Daemons record counters and gauges in a process-wide registry.
+//! When enabled, a lightweight HTTP listener serves the registry,
+//! plus any service request statistics (see osrf::stats), as
+//! OpenMetrics text.
+//!
+//! Cache usage counters (see osrf::cache) are included as well.
+//!
+//! The listener is configured with a <metrics> element in the
+//! relevant opensrf_core.xml client/router/gateway section, or
+//! with the OSRF_METRICS_PORT and OSRF_METRICS_ADDRESS
+//! environment variables, which take precedence.
+//!
+//! Only one daemon can listen on a given port.  Services running on
+//! the same host share the client config and environment, so each
+//! service needs its own port in the opensrf.xml host settings at
+//! apps/<service>/unix_config/metrics_port, which takes precedence
+//! over both.  Daemons which cannot bind their port log the error
+//! and run without metrics.
+use crate::osrf::cache::Cache;
+use crate::osrf::conf::MetricsConfig;
+use crate::osrf::stats;
+use crate::util;
+use crate::EgResult;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+
+static REGISTRY: OnceLock<Mutex<BTreeMap<String, Metric>>> = OnceLock::new();
+
+/// Services known to exist.  See service_label().
+static KNOWN_SERVICES: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+/// Label applied in place of the name of a service not known to exist.
+pub const UNKNOWN_SERVICE: &str = "unknown";
+
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Wake periodically from accept() so errors do not go unnoticed.
+const LISTEN_POLL_TIMEOUT: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    }
+}
+
+/// A metric family and its samples, keyed by formatted label set.
+#[derive(Debug)]
+struct Metric {
+    mtype: MetricType,
+    help: String,
+    samples: BTreeMap<String, f64>,
+}
+
+fn registry() -> MutexGuard<'static, BTreeMap<String, Metric>> {
+    REGISTRY
+        .get_or_init(|| Mutex::new(BTreeMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Format a set of labels, e.g. {service="open-ils.actor"}
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+
+    let parts: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| {
+            let v = v
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!(r#"{k}="{v}""#)
+        })
+        .collect();
+
+    format!("{{{}}}", parts.join(","))
+}
+
+fn update(
+    name: &str,
+    mtype: MetricType,
+    help: &str,
+    labels: &[(&str, &str)],
+    apply: impl FnOnce(&mut f64),
+) {
+    let mut reg = registry();
+
+    let metric = reg.entry(name.to_string()).or_insert_with(|| Metric {
+        mtype,
+        help: help.to_string(),
+        samples: BTreeMap::new(),
+    });
+
+    apply(metric.samples.entry(format_labels(labels)).or_insert(0.0));
+}
+
+/// Add to a counter.
+///
+/// The name should not include the "_total" suffix, which is
+/// added on output.
+pub fn inc_counter(name: &str, help: &str, labels: &[(&str, &str)], amount: u64) {
+    update(name, MetricType::Counter, help, labels, |v| {
+        *v += amount as f64
+    });
+}
+
+/// Set the current value of a gauge.
+pub fn set_gauge(name: &str, help: &str, labels: &[(&str, &str)], value: f64) {
+    update(name, MetricType::Gauge, help, labels, |v| *v = value);
+}
+
+/// Remove a sample, e.g. a gauge for a resource which no longer exists.
+pub fn remove(name: &str, labels: &[(&str, &str)]) {
+    if let Some(metric) = registry().get_mut(name) {
+        metric.samples.remove(&format_labels(labels));
+    }
+}
+
+/// Record that a service exists, e.g. because it registered with the
+/// router or answered a request, so its name may be used as a label.
+pub fn add_known_service(service: &str) {
+    let mut known = KNOWN_SERVICES
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if !known.contains(service) {
+        known.insert(service.to_string());
+    }
+}
+
+/// Returns the service name if the service is known to exist, or
+/// UNKNOWN_SERVICE otherwise.
+///
+/// Service names often come straight from API callers.  Labeling
+/// only known services keeps callers from growing the registry, and
+/// the /metrics output, without limit.
+pub fn service_label(service: &str) -> &str {
+    let known = KNOWN_SERVICES
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+
+    if known.contains(service) {
+        service
+    } else {
+        UNKNOWN_SERVICE
+    }
+}
+
+/// Render all metrics as OpenMetrics text.
+pub fn render() -> String {
+    let mut text = String::new();
+
+    for (name, metric) in registry().iter() {
+        let suffix = match metric.mtype {
+            MetricType::Counter => "_total",
+            MetricType::Gauge => "",
+        };
+
+        writeln!(text, "# TYPE {name} {}", metric.mtype.as_str()).ok();
+        writeln!(text, "# HELP {name} {}", metric.help).ok();
+
+        for (labels, value) in metric.samples.iter() {
+            writeln!(text, "{name}{suffix}{labels} {value}").ok();
+        }
+    }
+
+    render_service_stats(&mut text);
+    render_cache_stats(&mut text);
+
+    text.push_str("# EOF\n");
+    text
+}
+
+/// Add usage counters for each cache used by this process.
+fn render_cache_stats(text: &mut String) {
+    let caches = Cache::all_stats();
+
+    if caches.is_empty() {
+        return;
+    }
+
+    text.push_str("# TYPE osrf_cache_requests counter\n");
+    text.push_str("# HELP osrf_cache_requests Cache lookups by result\n");
+    for (cache, s) in caches.iter() {
+        for (result, count) in [("hit", s.hits), ("miss", s.misses)] {
+            let labels = format_labels(&[("cache", cache), ("result", result)]);
+            writeln!(text, "osrf_cache_requests_total{labels} {count}").ok();
+        }
+    }
+
+    text.push_str("# TYPE osrf_cache_sets counter\n");
+    text.push_str("# HELP osrf_cache_sets Values stored, by compression\n");
+    for (cache, s) in caches.iter() {
+        let plain = s.sets - s.compressed_sets;
+        for (compressed, count) in [("false", plain), ("true", s.compressed_sets)] {
+            let labels = format_labels(&[("cache", cache), ("compressed", compressed)]);
+            writeln!(text, "osrf_cache_sets_total{labels} {count}").ok();
+        }
+    }
+
+    text.push_str("# TYPE osrf_cache_compression_bytes counter\n");
+    text.push_str("# HELP osrf_cache_compression_bytes Size of compressed values\n");
+    for (cache, s) in caches.iter() {
+        let sizes = [
+            ("before", s.uncompressed_bytes),
+            ("after", s.compressed_bytes),
+        ];
+        for (stage, bytes) in sizes {
+            let labels = format_labels(&[("cache", cache), ("stage", stage)]);
+            writeln!(text, "osrf_cache_compression_bytes_total{labels} {bytes}").ok();
+        }
+    }
+}
+
+/// Add request statistics collected by OpenSRF services.
+fn render_service_stats(text: &mut String) {
+    let stats = stats::stats();
+
+    if stats.methods().is_empty() && stats.workers().is_empty() {
+        // Not a service or no activity yet.
+        return;
+    }
+
+    let active = stats.workers().values().filter(|w| w.active).count();
+    let idle = stats.workers().len() - active;
+
+    text.push_str("# TYPE osrf_workers gauge\n");
+    text.push_str("# HELP osrf_workers Worker threads by state\n");
+    writeln!(text, "osrf_workers{{state=\"active\"}} {active}").ok();
+    writeln!(text, "osrf_workers{{state=\"idle\"}} {idle}").ok();
+
+    text.push_str("# TYPE osrf_worker_requests counter\n");
+    text.push_str("# HELP osrf_worker_requests Requests handled by all workers\n");
+    writeln!(
+        text,
+        "osrf_worker_requests_total {}",
+        stats.total_requests()
+    )
+    .ok();
+
+    text.push_str("# TYPE osrf_request_errors counter\n");
+    text.push_str("# HELP osrf_request_errors API calls which returned an error\n");
+    for (method, m) in stats.methods().iter() {
+        let labels = format_labels(&[("method", method)]);
+        writeln!(text, "osrf_request_errors_total{labels} {}", m.errors).ok();
+    }
+
+    text.push_str("# TYPE osrf_request_duration_seconds histogram\n");
+    text.push_str("# HELP osrf_request_duration_seconds API call duration\n");
+
+    for (method, m) in stats.methods().iter() {
+        let mut cumulative = 0;
+
+        for (idx, count) in m.histogram.iter().enumerate() {
+            cumulative += count;
+
+            let le = match stats::LATENCY_BUCKETS_MS.get(idx) {
+                Some(ms) => format!("{}", *ms as f64 / 1000.0),
+                None => "+Inf".to_string(),
+            };
+
+            let labels = format_labels(&[("method", method), ("le", &le)]);
+            writeln!(
+                text,
+                "osrf_request_duration_seconds_bucket{labels} {cumulative}"
+            )
+            .ok();
+        }
+
+        let labels = format_labels(&[("method", method)]);
+        writeln!(
+            text,
+            "osrf_request_duration_seconds_count{labels} {}",
+            m.calls
+        )
+        .ok();
+        writeln!(
+            text,
+            "osrf_request_duration_seconds_sum{labels} {}",
+            m.total_time.as_secs_f64()
+        )
+        .ok();
+    }
+}
+
+/// Start the metrics HTTP listener in a background thread if one is
+/// configured via the environment or the provided config.
+///
+/// Metrics are not critical to the operation of the caller, so
+/// errors are logged and otherwise ignored.
+pub fn start_listener(config: Option<&MetricsConfig>) {
+    let mut config = config.cloned();
+
+    if let Ok(port) = env::var("OSRF_METRICS_PORT") {
+        let port = match port.parse::<u16>() {
+            Ok(p) => p,
+            Err(e) => {
+                log::error!("Cannot start metrics listener: invalid OSRF_METRICS_PORT: {port} {e}");
+                return;
+            }
+        };
+
+        let address = env::var("OSRF_METRICS_ADDRESS").unwrap_or("127.0.0.1".to_string());
+
+        config = Some(MetricsConfig::new(&address, port));
+    }
+
+    if let Some(c) = config {
+        serve(&c);
+    }
+}
+
+/// Start the metrics HTTP listener in a background thread at the
+/// provided address, regardless of the environment.
+///
+/// Errors are logged and otherwise ignored.
+pub fn serve(config: &MetricsConfig) {
+    if let Err(e) = spawn_listener(config) {
+        log::error!("Cannot start metrics listener: {e}");
+    }
+}
+
+fn spawn_listener(config: &MetricsConfig) -> EgResult<()> {
+    let listener = util::tcp_listener(config.address(), config.port(), LISTEN_POLL_TIMEOUT)?;
+
+    log::info!(
+        "Serving metrics at {}:{}/metrics",
+        config.address(),
+        config.port()
+    );
+
+    thread::spawn(move || loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = handle_request(stream) {
+                    log::warn!("Error serving metrics: {e}");
+                }
+            }
+            Err(e) => match e.kind() {
+                // socket read timeout.
+                std::io::ErrorKind::WouldBlock => {}
+                _ => log::error!("Metrics accept() failed: {e}"),
+            },
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_request(mut stream: TcpStream) -> EgResult<()> {
+    let request = util::read_http_request(&mut stream)?;
+
+    let response = if request.method == "GET" && request.path.starts_with("/metrics") {
+        let body = render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+    };
+
+    stream
+        .write_all(response.as_bytes())
+        .map_err(|e| format!("Error writing metrics response: {e}"))?;
+
+    stream.shutdown(std::net::Shutdown::Both).ok();
+
+    Ok(())
+}
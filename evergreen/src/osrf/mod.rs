@@ -1,15 +1,22 @@
 //! OpenSRF Components
 pub mod addr;
 pub mod app;
+pub mod breaker;
 pub mod bus;
 pub mod cache;
 pub mod client;
 pub mod conf;
 pub mod logging;
+pub mod membus;
 pub mod message;
+pub mod metrics;
 pub mod method;
 pub mod params;
 pub mod sclient;
 pub mod server;
 pub mod session;
+pub mod signing;
+pub mod stats;
+pub mod stubs;
+pub mod trace;
 pub mod worker;
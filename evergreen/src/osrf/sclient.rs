@@ -35,17 +35,22 @@ impl HostSettings {
         )?;
 
         if let Some(s) = req.recv_with_timeout(SETTINGS_TIMEOUT)? {
-            let sets = HostSettings { settings: s };
-            if OSRF_HOST_CONFIG.set(sets).is_err() {
-                return Err(format!("Cannot apply host settings more than once").into());
-            }
-
-            Ok(())
+            HostSettings::store(s)
         } else {
             Err(format!("Settings server returned no response!").into())
         }
     }
 
+    /// Store the provided host config as our global host settings,
+    /// e.g. when running a service without opensrf.settings.
+    pub fn store(settings: EgValue) -> EgResult<()> {
+        if OSRF_HOST_CONFIG.set(HostSettings { settings }).is_err() {
+            return Err("Cannot apply host settings more than once".into());
+        }
+
+        Ok(())
+    }
+
     /// Returns the full host settings config as a JsonValue.
     pub fn settings(&self) -> &EgValue {
         &self.settings
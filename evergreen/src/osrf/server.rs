@@ -1,20 +1,26 @@
 use crate::init;
 use crate::osrf::app;
+use crate::osrf::cache::Cache;
 use crate::osrf::client::Client;
 use crate::osrf::conf;
 use crate::osrf::message;
 use crate::osrf::method;
+use crate::osrf::metrics;
 use crate::osrf::sclient::HostSettings;
 use crate::osrf::session;
+use crate::osrf::stats;
 use crate::osrf::worker::{Worker, WorkerState, WorkerStateEvent};
 use crate::util;
 use crate::EgResult;
+use crate::EgValue;
 use mptc::signals::SignalTracker;
 use std::collections::HashMap;
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::sync::OnceLock;
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Warn when there are fewer than this many idle threads
@@ -24,14 +30,63 @@ const IDLE_THREAD_WARN_THRESHOLD: usize = 1;
 const IDLE_WAKE_TIME: u64 = 3;
 /// Max time in seconds to allow active workers to finish their tasks.
 const SHUTDOWN_MAX_WAIT: i32 = 30;
+/// Default max time in seconds to allow in-flight sessions to finish
+/// when draining.
+const DEFAULT_DRAIN_TIMEOUT: usize = 60;
 const DEFAULT_MIN_WORKERS: usize = 3;
 const DEFAULT_MAX_WORKERS: usize = 30;
 const DEFAULT_MIN_IDLE_WORKERS: usize = 1;
+/// How often, in seconds, we check worker ages and process memory use.
+const WORKER_CHECK_INTERVAL: u64 = 5;
+/// After cycling workers to reduce memory use, wait at least this many
+/// seconds before doing it again.
+const RSS_CYCLE_COOLDOWN: u64 = 300;
+
+/// Signal tracker for the Server running in this process, so drains
+/// may be requested from elsewhere in the process.
+static SERVER_SIGNALS: OnceLock<SignalTracker> = OnceLock::new();
+
+/// Ask the Server running in this process to drain.
+///
+/// This has the same effect as sending the process a SIG_DRAIN
+/// (SIGUSR2) signal or calling opensrf.system.drain.  See
+/// Server::drain().
+///
+/// Returns Err if no Server is listening in this process.
+pub fn request_drain() -> EgResult<()> {
+    match SERVER_SIGNALS.get() {
+        Some(tracker) => {
+            tracker.request_drain();
+            Ok(())
+        }
+        None => Err("No server is running in this process".into()),
+    }
+}
 
 #[derive(Debug)]
 pub struct WorkerThread {
     pub state: WorkerState,
     pub join_handle: thread::JoinHandle<()>,
+
+    /// Ask the worker to exit once it's done with any active session.
+    pub shutdown_tx: mpsc::Sender<()>,
+
+    /// When the worker was spawned.
+    pub started: Instant,
+
+    /// True if we have asked the worker to exit.
+    pub retiring: bool,
+}
+
+impl WorkerThread {
+    /// Ask the worker to exit once it's done with any active session.
+    fn retire(&mut self) {
+        if !self.retiring {
+            self.retiring = true;
+            // An Err here means the worker is already gone.
+            self.shutdown_tx.send(()).ok();
+        }
+    }
 }
 
 pub struct Server {
@@ -49,24 +104,74 @@ pub struct Server {
 
     sig_tracker: SignalTracker,
 
-    /// Minimum number of idle workers.  Note we don't support
-    /// max_idle_workers at this time -- it would require adding an
-    /// additional mpsc channel for every thread to deliver the shutdown
-    /// request to individual threads.  Hardly seems worth it -- maybe.
-    /// For comparision, the OSRF C code has no min/max idle support
-    /// either.
+    /// Minimum number of idle workers.
     min_idle_workers: usize,
+
+    /// Maximum number of idle workers.  Extra idle workers are asked
+    /// to exit.  0 means no maximum.
+    max_idle_workers: usize,
+
+    /// Workers older than this many seconds exit once they are done
+    /// with any active session.  0 means no limit.
+    max_worker_lifetime: u64,
+
+    /// When the resident memory of this process exceeds this many
+    /// kilobytes, all workers are cycled.  0 means no limit.
+    max_rss_kb: u64,
+
+    /// When we last checked worker ages and memory use.
+    last_worker_check: Instant,
+
+    /// When we last cycled workers because of memory use.
+    last_rss_cycle: Option<Instant>,
+
+    /// How often, in seconds, to write a request statistics summary
+    /// to the activity log.  0 disables.
+    stats_log_interval: u64,
+
+    /// When we last wrote a statistics summary.
+    last_stats_log: Instant,
+
+    /// Max time in seconds to wait for in-flight sessions to finish
+    /// once a drain is requested.
+    drain_timeout: usize,
 }
 
 impl Server {
     pub fn start(application: Box<dyn app::Application>) -> EgResult<()> {
-        let service = application.name();
-
         let mut options = init::InitOptions::new();
-        options.appname = Some(service.to_string());
+        options.appname = Some(application.name().to_string());
 
         let client = init::osrf_init(&options)?;
 
+        Server::start_with_client(application, client)
+    }
+
+    /// Start a server using an existing OpenSRF connection.
+    ///
+    /// The OpenSRF config and host settings must already be loaded.
+    pub fn start_with_client(
+        application: Box<dyn app::Application>,
+        client: Client,
+    ) -> EgResult<()> {
+        let service = application.name();
+
+        // Services on the same host share the opensrf_core.xml client
+        // config and environment, so a per-service port in the host
+        // settings takes precedence over both.
+        let metrics_conf = conf::config().client().metrics();
+
+        match HostSettings::get(&format!("apps/{service}/unix_config/metrics_port"))?.as_usize() {
+            Some(port) => {
+                let address = metrics_conf
+                    .map(|m| m.address().to_string())
+                    .unwrap_or("127.0.0.1".to_string());
+
+                metrics::serve(&conf::MetricsConfig::new(&address, port as u16))
+            }
+            None => metrics::start_listener(metrics_conf),
+        }
+
         let min_workers = HostSettings::get(&format!("apps/{service}/unix_config/min_children"))?
             .as_usize()
             .unwrap_or(DEFAULT_MIN_WORKERS);
@@ -80,6 +185,40 @@ impl Server {
             .as_usize()
             .unwrap_or(DEFAULT_MAX_WORKERS);
 
+        let mut max_idle_workers =
+            HostSettings::get(&format!("apps/{service}/unix_config/max_spare_children"))?
+                .as_usize()
+                .unwrap_or(0);
+
+        if max_idle_workers > 0 && max_idle_workers < min_idle_workers {
+            log::warn!(
+                "max_spare_children={max_idle_workers} is less than \
+                min_spare_children={min_idle_workers}; using {min_idle_workers}"
+            );
+            max_idle_workers = min_idle_workers;
+        }
+
+        let max_worker_lifetime =
+            HostSettings::get(&format!("apps/{service}/unix_config/max_worker_lifetime"))?
+                .as_usize()
+                .unwrap_or(0) as u64;
+
+        // Configured in megabytes.
+        let max_rss_kb = HostSettings::get(&format!("apps/{service}/unix_config/max_rss_mb"))?
+            .as_usize()
+            .unwrap_or(0) as u64
+            * 1024;
+
+        let stats_log_interval =
+            HostSettings::get(&format!("apps/{service}/unix_config/stats_log_interval"))?
+                .as_usize()
+                .unwrap_or(0) as u64;
+
+        let drain_timeout =
+            HostSettings::get(&format!("apps/{service}/unix_config/drain_timeout"))?
+                .as_usize()
+                .unwrap_or(DEFAULT_DRAIN_TIMEOUT);
+
         // We have a single to-parent channel whose trasmitter is cloned
         // per thread.  Communication from worker threads to the parent
         // are synchronous so the parent always knows exactly how many
@@ -97,6 +236,14 @@ impl Server {
             min_workers,
             max_workers,
             min_idle_workers,
+            max_idle_workers,
+            max_worker_lifetime,
+            max_rss_kb,
+            last_worker_check: Instant::now(),
+            last_rss_cycle: None,
+            stats_log_interval,
+            last_stats_log: Instant::now(),
+            drain_timeout,
             methods: None,
             worker_id_gen: 0,
             to_parent_tx: tx,
@@ -125,8 +272,14 @@ impl Server {
         self.worker_id_gen
     }
 
+    /// True if we are shutting down or draining and should not
+    /// start any new workers.
+    fn stopping(&self) -> bool {
+        self.sig_tracker.any_shutdown_requested() || self.sig_tracker.drain_requested()
+    }
+
     fn spawn_threads(&mut self) {
-        if self.sig_tracker.any_shutdown_requested() {
+        if self.stopping() {
             return;
         }
         while self.workers.len() < self.min_workers {
@@ -141,6 +294,7 @@ impl Server {
         let service = self.service().to_string();
         let factory = self.app().worker_factory();
         let sig_tracker = self.sig_tracker.clone();
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
 
         log::trace!("server: spawning a new worker {worker_id}");
 
@@ -152,6 +306,7 @@ impl Server {
                 worker_id,
                 methods,
                 to_parent_tx,
+                shutdown_rx,
             );
         });
 
@@ -160,6 +315,9 @@ impl Server {
             WorkerThread {
                 state: WorkerState::Idle,
                 join_handle: handle,
+                shutdown_tx,
+                started: Instant::now(),
+                retiring: false,
             },
         );
     }
@@ -171,10 +329,20 @@ impl Server {
         worker_id: u64,
         methods: Arc<HashMap<String, method::MethodDef>>,
         to_parent_tx: mpsc::SyncSender<WorkerStateEvent>,
+        shutdown_rx: mpsc::Receiver<()>,
     ) {
         log::trace!("Creating new worker {worker_id}");
 
-        let mut worker = match Worker::new(service, worker_id, sig_tracker, methods, to_parent_tx) {
+        let worker = Worker::new(
+            service,
+            worker_id,
+            sig_tracker,
+            methods,
+            to_parent_tx,
+            shutdown_rx,
+        );
+
+        let mut worker = match worker {
             Ok(w) => w,
             Err(e) => {
                 log::error!("Cannot create worker: {e}. Exiting.");
@@ -286,6 +454,18 @@ impl Server {
         method.set_desc("Respond with system time in epoch seconds");
         hash.insert(name.to_string(), method);
 
+        let name = "opensrf.system.stats";
+        let mut method =
+            method::MethodDef::new(name, method::ParamCount::Zero, system_method_stats);
+        method.set_desc("Request, worker, and cache statistics for this service process");
+        hash.insert(name.to_string(), method);
+
+        let name = "opensrf.system.drain";
+        let mut method =
+            method::MethodDef::new(name, method::ParamCount::Zero, system_method_drain);
+        method.set_desc("Stop accepting requests, finish active sessions, and exit");
+        hash.insert(name.to_string(), method);
+
         let name = "opensrf.system.method.all";
         let mut method = method::MethodDef::new(
             name,
@@ -302,6 +482,22 @@ impl Server {
 
         hash.insert(name.to_string(), method);
 
+        let name = "opensrf.system.method";
+        let mut method = method::MethodDef::new(
+            name,
+            method::ParamCount::Exactly(1),
+            system_method_introspect_one,
+        );
+        method.set_desc("API definition, including parameter docs, for one method");
+
+        method.add_param(method::Param {
+            name: String::from("api_name"),
+            datatype: method::ParamDataType::String,
+            desc: Some(String::from("Full API name")),
+        });
+
+        hash.insert(name.to_string(), method);
+
         let name = "opensrf.system.method.all.summary";
         let mut method = method::MethodDef::new(
             name,
@@ -322,11 +518,20 @@ impl Server {
     pub fn listen(&mut self) -> EgResult<()> {
         self.service_init()?;
         self.register_methods()?;
-        self.register_routers()?;
+
+        // Start our workers before registering with the routers so
+        // we're ready to respond as soon as requests are routed our way.
         self.spawn_threads();
+        self.register_routers()?;
+
         self.sig_tracker.track_graceful_shutdown();
         self.sig_tracker.track_fast_shutdown();
         self.sig_tracker.track_reload();
+        self.sig_tracker.track_drain();
+
+        if SERVER_SIGNALS.set(self.sig_tracker.clone()).is_err() {
+            log::warn!("A server is already listening in this process");
+        }
 
         let duration = Duration::from_secs(IDLE_WAKE_TIME);
 
@@ -354,11 +559,19 @@ impl Server {
                 break;
             }
 
+            if self.sig_tracker.drain_requested() {
+                return self.drain();
+            }
+
             if !work_performed {
                 // Only perform idle worker maintenance if no other
                 // tasks were performed during this loop iter.
                 self.perform_idle_worker_maint();
             }
+
+            self.perform_worker_limits_maint();
+
+            self.log_stats();
         }
 
         self.unregister_routers()?;
@@ -367,9 +580,10 @@ impl Server {
         Ok(())
     }
 
-    /// Add additional idle workers if needed.
+    /// Add additional idle workers if needed or retire extra
+    /// idle workers.
     ///
-    /// Spawn at most one worker per maintenance cycle.
+    /// Spawn or retire at most one worker per maintenance cycle.
     fn perform_idle_worker_maint(&mut self) {
         let idle_workers = self.idle_thread_count();
 
@@ -379,7 +593,178 @@ impl Server {
         {
             self.spawn_one_thread();
             log::debug!("Sawned idle worker; idle={idle_workers}");
+            return;
+        }
+
+        let retiring = self.workers.values().filter(|w| w.retiring).count();
+        let idle_workers = self
+            .workers
+            .values()
+            .filter(|w| w.state == WorkerState::Idle && !w.retiring)
+            .count();
+
+        if self.max_idle_workers == 0
+            || idle_workers <= self.max_idle_workers
+            || self.workers.len() - retiring <= self.min_workers
+        {
+            return;
+        }
+
+        // Retire the oldest idle worker.
+        let worker_id = self
+            .workers
+            .iter()
+            .filter(|(_, w)| w.state == WorkerState::Idle && !w.retiring)
+            .map(|(id, _)| *id)
+            .min();
+
+        if let Some(worker_id) = worker_id {
+            log::debug!("Retiring idle worker {worker_id}; idle={idle_workers}");
+            self.workers.get_mut(&worker_id).unwrap().retire(); // known OK
+        }
+    }
+
+    /// Retire workers which have exceeded their max lifetime and cycle
+    /// all workers if our process is using too much memory.
+    ///
+    /// Retired workers exit once they are done with any active
+    /// session and replacements are spawned as needed.
+    fn perform_worker_limits_maint(&mut self) {
+        if self.last_worker_check.elapsed() < Duration::from_secs(WORKER_CHECK_INTERVAL) {
+            return;
+        }
+
+        self.last_worker_check = Instant::now();
+
+        if self.max_worker_lifetime > 0 {
+            let lifetime = Duration::from_secs(self.max_worker_lifetime);
+
+            for (worker_id, worker) in self.workers.iter_mut() {
+                if !worker.retiring && worker.started.elapsed() >= lifetime {
+                    log::info!("Retiring worker {worker_id} after max lifetime");
+                    worker.retire();
+                }
+            }
+        }
+
+        if self.max_rss_kb == 0 {
+            return;
+        }
+
+        if let Some(t) = self.last_rss_cycle {
+            if t.elapsed() < Duration::from_secs(RSS_CYCLE_COOLDOWN) {
+                return;
+            }
+        }
+
+        let rss_kb = match process_rss_kb() {
+            Some(r) => r,
+            None => return,
+        };
+
+        if rss_kb <= self.max_rss_kb {
+            return;
+        }
+
+        log::warn!(
+            "{} memory use {rss_kb}kB exceeds {}kB; cycling {} workers",
+            self.service(),
+            self.max_rss_kb,
+            self.workers.len()
+        );
+
+        self.last_rss_cycle = Some(Instant::now());
+
+        for worker in self.workers.values_mut() {
+            worker.retire();
+        }
+    }
+
+    /// Write a statistics summary to the activity log if it's time.
+    fn log_stats(&mut self) {
+        if self.stats_log_interval == 0
+            || self.last_stats_log.elapsed() < Duration::from_secs(self.stats_log_interval)
+        {
+            return;
+        }
+
+        self.last_stats_log = Instant::now();
+
+        log::info!("ACT:stats {}", stats::stats().summary().dump());
+    }
+
+    /// Stop accepting new work, let in-flight sessions finish, then exit.
+    ///
+    /// We un-register from our routers first so no new requests are
+    /// routed to this instance.  Workers continue to handle requests
+    /// already queued for our service and finish any stateful sessions
+    /// they are in.  For a grace period, they also keep polling for
+    /// requests the routers send our way before processing the
+    /// un-register, then exit once they have nothing left to do.
+    ///
+    /// Requests are only routed away from this instance if another
+    /// instance of the service is registered with the router, so
+    /// start the replacement instance before draining this one.
+    ///
+    /// Active sessions which are still running after drain_timeout
+    /// seconds are logged and abandoned.
+    fn drain(&mut self) -> EgResult<()> {
+        log::info!(
+            "{} draining; waiting up to {} seconds for {} active sessions",
+            self.service(),
+            self.drain_timeout,
+            self.active_thread_count(),
+        );
+
+        self.unregister_routers()?;
+
+        let timer = util::Timer::new(self.drain_timeout as i32);
+        let duration = Duration::from_secs(1);
+
+        while !timer.done() && !self.workers.is_empty() {
+            if self.sig_tracker.fast_shutdown_requested() {
+                log::info!("Fast shutdown requested while draining");
+                break;
+            }
+
+            let info = format!(
+                "{} drain: {} threads; {} active sessions; time remaining {}",
+                self.service(),
+                self.workers.len(),
+                self.active_thread_count(),
+                timer.remaining(),
+            );
+
+            log::info!("{info}");
+
+            if let Ok(evt) = self.to_parent_rx.recv_timeout(duration) {
+                self.handle_worker_event(&evt);
+            }
+
+            self.check_failed_threads();
         }
+
+        let mut active: Vec<u64> = self
+            .workers
+            .iter()
+            .filter(|(_, w)| w.state == WorkerState::Active)
+            .map(|(id, _)| *id)
+            .collect();
+
+        if active.is_empty() {
+            log::info!("{} drain complete", self.service());
+        } else {
+            active.sort();
+
+            log::warn!(
+                "{} drain timed out with {} active sessions on workers {:?}",
+                self.service(),
+                active.len(),
+                active
+            );
+        }
+
+        std::process::exit(0);
     }
 
     fn shutdown(&mut self) {
@@ -437,6 +822,7 @@ impl Server {
     fn remove_thread(&mut self, worker_id: &u64) {
         log::trace!("server: removing thread {}", worker_id);
         self.workers.remove(worker_id);
+        stats::stats().worker_exit(*worker_id);
         self.spawn_threads();
     }
 
@@ -468,7 +854,7 @@ impl Server {
 
         log::trace!("server: workers idle={idle} active={active}");
 
-        if self.sig_tracker.any_shutdown_requested() {
+        if self.stopping() {
             return;
         }
 
@@ -504,6 +890,18 @@ impl Server {
     }
 }
 
+/// Resident memory of this process in kilobytes, if known.
+fn process_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+
+    // e.g. "VmRSS:     12345 kB"
+    status
+        .lines()
+        .find(|l| l.starts_with("VmRSS:"))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|n| n.parse().ok())
+}
+
 // Toss our system method handlers down here.
 fn system_method_echo(
     _worker: &mut Box<dyn app::ApplicationWorker>,
@@ -535,6 +933,49 @@ fn system_method_time(
     }
 }
 
+fn system_method_stats(
+    _worker: &mut Box<dyn app::ApplicationWorker>,
+    session: &mut session::ServerSession,
+    _method: &message::MethodCall,
+) -> EgResult<()> {
+    let mut value = stats::stats().to_eg_value();
+
+    let mut caches = EgValue::new_object();
+    for (name, s) in Cache::all_stats().iter() {
+        caches[name] = s.to_eg_value();
+    }
+
+    value["caches"] = caches;
+
+    session.respond_complete(value)
+}
+
+/// Drain requests are only accepted from callers on our own domain,
+/// or on an admin domain of the router for our domain.
+fn system_method_drain(
+    _worker: &mut Box<dyn app::ApplicationWorker>,
+    session: &mut session::ServerSession,
+    _method: &message::MethodCall,
+) -> EgResult<()> {
+    let domain = conf::config().client().domain().name();
+    let caller = session.sender().domain();
+
+    let allowed = match conf::config().get_router_conf(domain) {
+        Some(router) => router.is_admin_domain(caller),
+        None => caller == domain,
+    };
+
+    if !allowed {
+        return Err(format!("Domain {caller} may not drain services on {domain}").into());
+    }
+
+    log::info!("Drain requested by {}", session.sender());
+
+    request_drain()?;
+
+    session.respond_complete(true)
+}
+
 fn system_method_introspect(
     worker: &mut Box<dyn app::ApplicationWorker>,
     session: &mut session::ServerSession,
@@ -572,3 +1013,20 @@ fn system_method_introspect(
 
     Ok(())
 }
+
+fn system_method_introspect_one(
+    worker: &mut Box<dyn app::ApplicationWorker>,
+    session: &mut session::ServerSession,
+    method: &message::MethodCall,
+) -> EgResult<()> {
+    let api_name = method.param(0).str()?;
+
+    // Atomic variants share the definition of their root method.
+    let root_name = api_name.strip_suffix(".atomic").unwrap_or(api_name);
+
+    // Unknown methods produce no response.
+    match worker.methods().get(root_name) {
+        Some(meth) => session.respond_complete(meth.to_eg_value()),
+        None => Ok(()),
+    }
+}
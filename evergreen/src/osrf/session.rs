@@ -1,6 +1,9 @@
+use crate::editor::Editor;
 use crate::osrf::addr::BusAddress;
+use crate::osrf::breaker;
 use crate::osrf::client::{Client, ClientSingleton};
 use crate::osrf::conf;
+use crate::osrf::conf::ClientPolicy;
 use crate::osrf::message;
 use crate::osrf::message::Message;
 use crate::osrf::message::MessageStatus;
@@ -10,6 +13,7 @@ use crate::osrf::message::Payload;
 use crate::osrf::message::Status;
 use crate::osrf::message::TransportMessage;
 use crate::osrf::params::ApiParams;
+use crate::osrf::trace::{Span, SpanKind};
 use crate::util;
 use crate::{EgResult, EgValue};
 use std::cell::RefCell;
@@ -17,10 +21,16 @@ use std::cell::RefMut;
 use std::collections::VecDeque;
 use std::fmt;
 use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
 
 const CONNECT_TIMEOUT: i32 = 10;
 pub const DEFAULT_REQUEST_TIMEOUT: i32 = 60;
 
+/// Wait this long before resending a request which bounced because
+/// no instances of the service were available.
+const NOT_FOUND_RETRY_DELAY: Duration = Duration::from_secs(1);
+
 /// Response data propagated from a session to the calling Request.
 #[derive(Debug)]
 struct Response {
@@ -32,6 +42,29 @@ struct Response {
     partial: bool,
 }
 
+/// Per-request overrides for the service's ClientPolicy.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Default number of seconds to wait for each response.
+    pub timeout: Option<i32>,
+
+    /// How many times the request may be resent.
+    pub retries: Option<u32>,
+
+    /// Whether the request may be resent after a timeout.
+    pub idempotent: Option<bool>,
+}
+
+/// What we need to resend a request.
+#[derive(Clone)]
+struct RetryState {
+    method: String,
+    params: Vec<EgValue>,
+    retries: u32,
+    on_timeout: bool,
+    on_not_found: bool,
+}
+
 /// Models a single API call through which the caller can receive responses.
 #[derive(Clone)]
 pub struct Request {
@@ -47,6 +80,21 @@ pub struct Request {
     /// Having a local copy of the thread can be handy since our
     /// session is only accessible via temporary borrow().
     thread: String,
+
+    /// Handling policy for the service we are calling.
+    policy: ClientPolicy,
+
+    /// Default receive timeout.
+    timeout: i32,
+
+    /// Set when the request may be resent.
+    retry: Option<RetryState>,
+
+    /// True if any response has arrived.
+    received: bool,
+
+    /// True if we gave up waiting for a response.
+    timed_out: bool,
 }
 
 impl Request {
@@ -54,12 +102,18 @@ impl Request {
         thread: String,
         session: Rc<RefCell<ClientSessionInternal>>,
         thread_trace: usize,
+        policy: ClientPolicy,
     ) -> Request {
         Request {
             session,
             thread,
             complete: false,
             thread_trace,
+            timeout: policy.timeout(),
+            policy,
+            retry: None,
+            received: false,
+            timed_out: false,
         }
     }
 
@@ -71,6 +125,17 @@ impl Request {
         self.thread_trace
     }
 
+    /// Default number of seconds to wait for each response.
+    pub fn timeout(&self) -> i32 {
+        self.timeout
+    }
+
+    /// True if the request gave up waiting for a response, after
+    /// any retries.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
     /// True if we have received a COMPLETE message from the server.
     ///
     /// This does not guarantee all responses have been read.
@@ -93,7 +158,7 @@ impl Request {
     /// about the first, but want to pull all data off the bus until the
     /// message is officially marked as complete.
     pub fn first(&mut self) -> EgResult<Option<EgValue>> {
-        self.first_with_timeout(DEFAULT_REQUEST_TIMEOUT)
+        self.first_with_timeout(self.timeout)
     }
 
     /// Returns the first response.
@@ -103,7 +168,7 @@ impl Request {
     /// message bus.
     pub fn first_with_timeout(&mut self, timeout: i32) -> EgResult<Option<EgValue>> {
         let mut resp: Option<EgValue> = None;
-        while !self.complete {
+        while !self.complete && !self.timed_out {
             if let Some(r) = self.recv_with_timeout(timeout)? {
                 if resp.is_none() {
                     resp = Some(r);
@@ -129,9 +194,26 @@ impl Request {
         }
 
         loop {
-            let response = self.session.borrow_mut().recv(self.thread_trace, timeout)?;
+            let response = self.session.borrow_mut().recv(self.thread_trace, timeout);
+
+            let response = match response {
+                Ok(r) => r,
+                Err(e) => {
+                    let status = self.session.borrow_mut().failed_status.take();
+
+                    // A bounced request never reached the service,
+                    // so it's always safe to send it again.
+                    if status == Some(MessageStatus::ServiceNotFound) && self.resend(false)? {
+                        continue;
+                    }
+
+                    return Err(e);
+                }
+            };
 
             if let Some(r) = response {
+                self.received = true;
+
                 if r.partial {
                     // Keep calling receive until our partial message is
                     // complete.  This effectively resets the receive
@@ -143,16 +225,89 @@ impl Request {
                 }
                 if r.complete {
                     self.complete = true;
+                    breaker::record_success(self.session.borrow().service());
                 }
                 return Ok(r.value);
-            } else {
+            }
+
+            if timeout == 0 || self.complete {
+                // Non-blocking check for data.
                 return Ok(None);
             }
+
+            let service = self.session.borrow().service().to_string();
+            breaker::record_timeout(&service, &self.policy);
+
+            if self.resend(true)? {
+                continue;
+            }
+
+            self.timed_out = true;
+
+            return Ok(None);
         }
     }
 
     pub fn recv(&mut self) -> EgResult<Option<EgValue>> {
-        self.recv_with_timeout(DEFAULT_REQUEST_TIMEOUT)
+        self.recv_with_timeout(self.timeout)
+    }
+
+    /// Stop waiting for responses to this request.
+    ///
+    /// Queued responses are discarded, as are any responses which
+    /// arrive later.  Intended for requests which are the only
+    /// request on their (stateless) session.
+    fn cancel(&mut self) {
+        let mut session = self.session.borrow_mut();
+
+        session.end_span(self.thread_trace, Some("request cancelled"));
+        session.backlog.clear();
+        session.client_internal_mut().cancel_thread(&self.thread);
+
+        self.complete = true;
+    }
+
+    /// Send the request again if our retry settings allow.
+    ///
+    /// Returns true if the request was resent.
+    fn resend(&mut self, after_timeout: bool) -> EgResult<bool> {
+        let retry = match self.retry.as_mut() {
+            Some(r) => r,
+            None => return Ok(false),
+        };
+
+        let allowed = match after_timeout {
+            // Avoid duplicate responses from a slow request.
+            true => retry.on_timeout && !self.received,
+            false => retry.on_not_found,
+        };
+
+        // Requests within a connected session depend on the
+        // state of the remote worker.  Leave those alone.
+        if !allowed || retry.retries == 0 || self.session.borrow().connected() {
+            return Ok(false);
+        }
+
+        retry.retries -= 1;
+
+        let mut session = self.session.borrow_mut();
+
+        log::warn!(
+            "{session} resending request {} {}; {} retries remaining",
+            self.thread_trace,
+            retry.method,
+            retry.retries
+        );
+
+        if !after_timeout {
+            thread::sleep(NOT_FOUND_RETRY_DELAY);
+        }
+
+        breaker::check(session.service(), &self.policy)?;
+
+        self.thread_trace = session.request(&retry.method, retry.params.clone())?;
+
+        Ok(true)
     }
 }
 
@@ -195,6 +350,13 @@ struct ClientSessionInternal {
 
     /// Staging ground for "partial" messages arriving in chunks.
     partial_buffer: Option<String>,
+
+    /// Trace spans for requests which have not yet completed,
+    /// keyed on thread_trace.
+    spans: Vec<(usize, Span)>,
+
+    /// Status of the most recent failed request, if any.
+    failed_status: Option<MessageStatus>,
 }
 
 impl fmt::Display for ClientSessionInternal {
@@ -220,6 +382,8 @@ impl ClientSessionInternal {
             last_thread_trace: 0,
             partial_buffer: None,
             backlog: VecDeque::new(),
+            spans: Vec::new(),
+            failed_status: None,
             thread: util::random_number(16),
         }
     }
@@ -241,6 +405,20 @@ impl ClientSessionInternal {
         self.worker_addr = None;
         self.connected = false;
         self.backlog.clear();
+
+        for (_, mut span) in self.spans.drain(..) {
+            span.set_error("session reset");
+        }
+    }
+
+    /// Finish the trace span for a request.
+    fn end_span(&mut self, thread_trace: usize, error: Option<&str>) {
+        if let Some(pos) = self.spans.iter().position(|(t, _)| *t == thread_trace) {
+            let (_, mut span) = self.spans.remove(pos);
+            if let Some(e) = error {
+                span.set_error(e);
+            }
+        }
     }
 
     fn router_addr(&self) -> &BusAddress {
@@ -433,6 +611,7 @@ impl ClientSessionInternal {
             }
             MessageStatus::Complete => {
                 log::trace!("{self} request {trace} complete");
+                self.end_span(trace, None);
                 Ok(Some(Response {
                     value: None,
                     complete: true,
@@ -440,7 +619,9 @@ impl ClientSessionInternal {
                 }))
             }
             _ => {
+                self.end_span(trace, Some(statmsg.status_label()));
                 self.reset();
+                self.failed_status = Some(*stat);
                 return Err(format!("{self} request {trace} failed: {}", statmsg).into());
             }
         }
@@ -466,7 +647,7 @@ impl ClientSessionInternal {
             self.worker_addr = None;
         }
 
-        let tmsg = TransportMessage::with_body(
+        let mut tmsg = TransportMessage::with_body(
             self.destination_addr().as_str(),
             self.client.address().as_str(),
             self.thread(),
@@ -477,6 +658,16 @@ impl ClientSessionInternal {
             ),
         );
 
+        // The span remains open until the request completes, which
+        // happens after this call returns.
+        let mut span = Span::detached(&format!("osrf.request {method}"), SpanKind::Client);
+        if let Some(tp) = span.traceparent() {
+            span.set_attr("osrf.service", &self.service);
+            span.set_attr("osrf.method", method);
+            tmsg.set_traceparent(Some(&tp));
+            self.spans.push((trace, span));
+        }
+
         if !self.connected() {
             // Top-level API calls always go through the router on
             // our primary domain
@@ -576,6 +767,9 @@ impl ClientSessionInternal {
 /// Public-facing Session wrapper which exports the needed session API.
 pub struct ClientSession {
     session: Rc<RefCell<ClientSessionInternal>>,
+
+    /// Request handling policy for our service.
+    policy: ClientPolicy,
 }
 
 impl ClientSession {
@@ -586,26 +780,84 @@ impl ClientSession {
 
         ClientSession {
             session: Rc::new(RefCell::new(ses)),
+            policy: conf::config().client().client_policy(service).clone(),
         }
     }
 
+    pub fn policy(&self) -> &ClientPolicy {
+        &self.policy
+    }
+
+    /// Replace the request handling policy for this session.
+    pub fn set_policy(&mut self, policy: ClientPolicy) {
+        self.policy = policy;
+    }
+
     /// Issue a new API call and return the Request
     ///
     /// params is a JSON-able thing.  E.g. vec![1,2,3], json::object!{"a": "b"}, etc.
     pub fn request(&mut self, method: &str, params: impl Into<ApiParams>) -> EgResult<Request> {
+        self.request_with_options(method, params, &RequestOptions::default())
+    }
+
+    /// Issue a new API call, overriding our policy with the provided
+    /// options, and return the Request.
+    ///
+    /// Returns Err without sending the request if the circuit breaker
+    /// for our service is open.
+    pub fn request_with_options(
+        &mut self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        options: &RequestOptions,
+    ) -> EgResult<Request> {
+        let service = self.session.borrow().service().to_string();
+        breaker::check(&service, &self.policy)?;
+
+        let mut policy = self.policy.clone();
+        if let Some(t) = options.timeout {
+            policy.set_timeout(t);
+        }
+        if let Some(r) = options.retries {
+            policy.set_retries(r);
+        }
+
+        let on_timeout = options
+            .idempotent
+            .unwrap_or_else(|| policy.is_idempotent(method));
+
+        let on_not_found = policy.retry_not_found();
+
+        let mut params: ApiParams = params.into();
+
+        // Keep a copy of the params only if we may need them again.
+        let mut retry = None;
+        if policy.retries() > 0 && (on_timeout || on_not_found) {
+            retry = Some(RetryState {
+                method: method.to_string(),
+                params: params.params().clone(),
+                retries: policy.retries(),
+                on_timeout,
+                on_not_found,
+            });
+        }
+
         let thread = self.session.borrow().thread().to_string();
+        let thread_trace = self
+            .session
+            .borrow_mut()
+            .request(method, params.take_params())?;
 
-        Ok(Request::new(
-            thread,
-            self.session.clone(),
-            self.session.borrow_mut().request(method, params)?,
-        ))
+        let mut request = Request::new(thread, self.session.clone(), thread_trace, policy);
+        request.retry = retry;
+
+        Ok(request)
     }
 
     /// Send a request and receive a ResponseIterator for iterating
     /// the responses to the method.
     ///
-    /// Uses the default request timeout DEFAULT_REQUEST_TIMEOUT.
+    /// Uses the request timeout from our policy.
     pub fn send_recv(
         &mut self,
         method: &str,
@@ -734,6 +986,263 @@ impl MultiSession {
     }
 }
 
+/// One request within a RequestGroup.
+struct GroupRequest {
+    id: usize,
+    request: Request,
+
+    /// Tracks how long we have been waiting for the next response.
+    ///
+    /// A negative duration means wait indefinitely.
+    timer: util::Timer,
+}
+
+impl GroupRequest {
+    fn expired(&self) -> bool {
+        self.timer.duration() >= 0 && self.timer.done()
+    }
+}
+
+/// Sends requests to any number of services over a single bus
+/// connection and returns responses in the order they arrive.
+///
+/// Each request lives in its own stateless session.  Requests which
+/// receive no response within their timeout are abandoned and any
+/// requests still outstanding when the group is dropped are
+/// cancelled.
+///
+/// ```no_run
+/// use evergreen as eg;
+/// # fn example(client: &eg::Client) -> eg::EgResult<()> {
+/// let mut group = client.request_group();
+///
+/// let a = group.request("open-ils.actor", "opensrf.system.echo", "a")?;
+/// let b = group.request("open-ils.search", "opensrf.system.echo", "b")?;
+///
+/// while let Some((id, resp)) = group.recv(-1)? {
+///     let label = if id == a { "actor" } else { "search" };
+///     println!("{label} replied {resp}");
+/// }
+///
+/// if group.timed_out().contains(&b) {
+///     println!("search did not reply in time");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct RequestGroup {
+    client: Client,
+    requests: Vec<GroupRequest>,
+    next_id: usize,
+
+    /// IDs of requests we gave up waiting on.
+    timed_out: Vec<usize>,
+
+    /// IDs of requests which returned an error.
+    failed: Vec<usize>,
+}
+
+impl RequestGroup {
+    pub fn new(client: Client) -> RequestGroup {
+        RequestGroup {
+            client,
+            requests: Vec::new(),
+            next_id: 0,
+            timed_out: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// Send a request to a service using the service's client policy.
+    ///
+    /// Returns the request ID so the caller can link responses
+    /// (see recv()) to their requests.
+    pub fn request(
+        &mut self,
+        service: &str,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> EgResult<usize> {
+        self.request_with_options(service, method, params, &RequestOptions::default())
+    }
+
+    /// Send a request to a service, overriding the service's client
+    /// policy with the provided options.
+    ///
+    /// The timeout applies to the wait between responses for this
+    /// request only.
+    pub fn request_with_options(
+        &mut self,
+        service: &str,
+        method: &str,
+        params: impl Into<ApiParams>,
+        options: &RequestOptions,
+    ) -> EgResult<usize> {
+        let mut ses = self.client.session(service);
+        let request = ses.request_with_options(method, params, options)?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.requests.push(GroupRequest {
+            id,
+            timer: util::Timer::new(request.timeout()),
+            request,
+        });
+
+        Ok(id)
+    }
+
+    /// True if no requests are awaiting responses.
+    ///
+    /// May mark additional requests as complete as a side effect.
+    pub fn complete(&mut self) -> bool {
+        self.remove_completed();
+        self.requests.is_empty()
+    }
+
+    /// IDs of requests which have not yet completed.
+    pub fn pending(&self) -> Vec<usize> {
+        self.requests.iter().map(|r| r.id).collect()
+    }
+
+    /// IDs of requests which were abandoned after receiving no
+    /// response within their timeout.
+    pub fn timed_out(&self) -> &[usize] {
+        &self.timed_out
+    }
+
+    /// IDs of requests which returned an error.
+    pub fn failed(&self) -> &[usize] {
+        &self.failed
+    }
+
+    /// Wait up to `timeout` seconds for a response to arrive for any
+    /// of our outstanding requests.
+    ///
+    /// timeout:
+    ///     <0 == wait until every request completes or times out
+    ///      0 == do not wait/block
+    ///     >0 == wait up to this many seconds for a reply.
+    ///
+    /// Returns (Request ID, Response) if found.
+    ///
+    /// Returns Err if any request fails.  The failed request is
+    /// removed from the group and its ID added to failed().
+    pub fn recv(&mut self, timeout: i32) -> EgResult<Option<(usize, EgValue)>> {
+        let timer = util::Timer::new(timeout);
+
+        loop {
+            if let Some(resp) = self.recv_ready()? {
+                return Ok(Some(resp));
+            }
+
+            self.remove_completed();
+            self.expire_requests()?;
+
+            if self.requests.is_empty() || (timeout >= 0 && timer.done()) {
+                return Ok(None);
+            }
+
+            // Wake up in time to expire the next stale request.
+            let mut wait = self
+                .requests
+                .iter()
+                .filter(|r| r.timer.duration() >= 0)
+                .map(|r| r.timer.remaining().max(1))
+                .min();
+
+            if timeout >= 0 {
+                wait = Some(wait.unwrap_or(i32::MAX).min(timer.remaining()));
+            }
+
+            let threads: Vec<&str> = self.requests.iter().map(|r| r.request.thread()).collect();
+
+            self.client
+                .singleton()
+                .borrow_mut()
+                .wait_for_threads(&threads, wait.map(|w| w.max(1)).unwrap_or(-1))?;
+        }
+    }
+
+    /// Return the first response we have already received, if any.
+    fn recv_ready(&mut self) -> EgResult<Option<(usize, EgValue)>> {
+        for pos in 0..self.requests.len() {
+            let req = &mut self.requests[pos];
+
+            match req.request.recv_with_timeout(0) {
+                Ok(Some(resp)) => {
+                    req.timer.reset();
+                    return Ok(Some((req.id, resp)));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let mut req = self.requests.remove(pos);
+                    log::error!("Group request {} failed: {e}", req.id);
+                    req.request.cancel();
+                    self.failed.push(req.id);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Resend or abandon requests which have waited too long.
+    fn expire_requests(&mut self) -> EgResult<()> {
+        let mut pos = 0;
+
+        while pos < self.requests.len() {
+            let req = &mut self.requests[pos];
+
+            if !req.expired() {
+                pos += 1;
+                continue;
+            }
+
+            let service = req.request.session.borrow().service().to_string();
+            breaker::record_timeout(&service, &req.request.policy);
+
+            if req.request.resend(true)? {
+                req.timer.reset();
+                pos += 1;
+                continue;
+            }
+
+            let mut req = self.requests.remove(pos);
+
+            log::warn!(
+                "Group request {} to {service} timed out after {} seconds",
+                req.id,
+                req.timer.duration()
+            );
+
+            req.request.cancel();
+            req.request.timed_out = true;
+            self.timed_out.push(req.id);
+        }
+
+        Ok(())
+    }
+
+    fn remove_completed(&mut self) {
+        self.requests.retain(|r| !r.request.exhausted());
+    }
+}
+
+impl Drop for RequestGroup {
+    /// Cancel any outstanding requests.
+    fn drop(&mut self) {
+        for req in self.requests.iter_mut() {
+            if !req.request.exhausted() {
+                log::debug!("Cancelling group request {}", req.id);
+                req.request.cancel();
+            }
+        }
+    }
+}
+
 pub struct ServerSession {
     /// Service name.
     service: String,
@@ -759,6 +1268,24 @@ pub struct ServerSession {
 
     /// Responses collected to be packed into an "atomic" response array.
     atomic_resp_queue: Option<Vec<EgValue>>,
+
+    /// Editor authenticated by the worker for methods with MethodAuth
+    /// settings.  Applies to the current request only.
+    editor: Option<Editor>,
+
+    /// Result values whose JSON exceeds this many bytes are sent
+    /// as a series of Partial messages.  0 disables chunking.
+    max_chunk_size: usize,
+
+    /// Collect streamed responses until they exceed this many bytes
+    /// before putting them on the bus.  0 disables bundling.
+    max_bundle_size: usize,
+
+    /// Responses waiting to be sent as a single transport message.
+    bundle: Vec<Message>,
+
+    /// Approximate size in bytes of the bundled responses.
+    bundle_size: usize,
 }
 
 impl fmt::Display for ServerSession {
@@ -783,9 +1310,41 @@ impl ServerSession {
             responded_complete: false,
             thread: thread.to_string(),
             atomic_resp_queue: None,
+            editor: None,
+            max_chunk_size: 0,
+            max_bundle_size: 0,
+            bundle: Vec::new(),
+            bundle_size: 0,
         }
     }
 
+    pub fn set_max_chunk_size(&mut self, size: usize) {
+        self.max_chunk_size = size;
+    }
+
+    pub fn set_max_bundle_size(&mut self, size: usize) {
+        self.max_bundle_size = size;
+    }
+
+    /// Returns the Editor authenticated by the worker for the
+    /// current request.
+    ///
+    /// Only available to methods which define MethodAuth settings.
+    pub fn take_editor(&mut self) -> EgResult<Editor> {
+        self.editor
+            .take()
+            .ok_or_else(|| format!("{self} has no authenticated editor").into())
+    }
+
+    pub fn set_editor(&mut self, editor: Editor) {
+        self.editor = Some(editor);
+    }
+
+    /// Discard any editor not collected by the method handler.
+    pub fn clear_editor(&mut self) {
+        self.editor = None;
+    }
+
     pub fn last_thread_trace(&self) -> usize {
         self.last_thread_trace
     }
@@ -875,6 +1434,80 @@ impl ServerSession {
         )))
     }
 
+    /// Returns the result message, or a series of Partial messages
+    /// followed by a PartialComplete message if the result content is
+    /// larger than our max chunk size, each paired with the size in
+    /// bytes of its content.
+    fn chunk_result(&self, msg: Message) -> Vec<(Message, usize)> {
+        if self.max_chunk_size == 0 && self.max_bundle_size == 0 {
+            // No need to know the size of the message.
+            return vec![(msg, 0)];
+        }
+
+        let json = match msg.payload() {
+            Payload::Result(r) => r.content().dump(),
+            _ => return vec![(msg, 0)],
+        };
+
+        if self.max_chunk_size == 0 || json.len() <= self.max_chunk_size {
+            return vec![(msg, json.len())];
+        }
+
+        log::debug!(
+            "{self} sending {} byte response in chunks of {} bytes",
+            json.len(),
+            self.max_chunk_size
+        );
+
+        let mut messages = Vec::new();
+        let mut start = 0;
+
+        while start < json.len() {
+            let mut end = (start + self.max_chunk_size).min(json.len());
+
+            // Avoid splitting multi-byte characters.
+            while !json.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            if end == start {
+                // Chunk size is smaller than one character.
+                end = start + 1;
+                while !json.is_char_boundary(end) {
+                    end += 1;
+                }
+            }
+
+            let chunk = &json[start..end];
+
+            messages.push((
+                self.result_message(MessageStatus::Partial, "Partial Response", chunk.into()),
+                chunk.len(),
+            ));
+
+            start = end;
+        }
+
+        messages.push((
+            self.result_message(
+                MessageStatus::PartialComplete,
+                "Partial Complete",
+                EgValue::from(""),
+            ),
+            0,
+        ));
+
+        messages
+    }
+
+    fn result_message(&self, status: MessageStatus, label: &str, content: EgValue) -> Message {
+        Message::new(
+            MessageType::Result,
+            self.last_thread_trace(),
+            Payload::Result(message::Result::new(status, label, "osrfResult", content)),
+        )
+    }
+
     /// Respond with a value and/or a complete message.
     fn respond_with_parts(&mut self, value: Option<EgValue>, complete: bool) -> EgResult<()> {
         if self.responded_complete {
@@ -886,15 +1519,25 @@ impl ServerSession {
             return Ok(());
         }
 
-        let mut complete_msg = None;
+        if let Some(msg) = self.build_result_message(value, complete)? {
+            for (msg, size) in self.chunk_result(msg) {
+                // Send what we have so far if this message would
+                // push the bundle beyond its max size.
+                if !self.bundle.is_empty() && self.bundle_size + size > self.max_bundle_size {
+                    self.flush_responses()?;
+                }
 
-        let mut result_msg = self.build_result_message(value, complete)?;
+                self.bundle.push(msg);
+                self.bundle_size += size;
+            }
+        }
 
         if complete {
-            // Add a Request Complete message
+            // Add a Request Complete message to whatever is
+            // waiting to be sent.
             self.responded_complete = true;
 
-            complete_msg = Some(Message::new(
+            self.bundle.push(Message::new(
                 MessageType::Status,
                 self.last_thread_trace(),
                 Payload::Status(message::Status::new(
@@ -905,13 +1548,19 @@ impl ServerSession {
             ));
         }
 
-        if result_msg.is_none() && complete_msg.is_none() {
-            // Nothing to send to the caller.
-            return Ok(());
+        if complete || self.bundle_size >= self.max_bundle_size {
+            self.flush_responses()?;
         }
 
-        // We have at least one message to return.
-        // Pack what we have into a single transport message.
+        Ok(())
+    }
+
+    /// Send any responses collected in the current bundle to the
+    /// caller as a single transport message.
+    pub fn flush_responses(&mut self) -> EgResult<()> {
+        if self.bundle.is_empty() {
+            return Ok(());
+        }
 
         let mut tmsg = TransportMessage::new(
             self.sender.as_str(),
@@ -919,13 +1568,8 @@ impl ServerSession {
             self.thread(),
         );
 
-        if let Some(msg) = result_msg.take() {
-            tmsg.body_mut().push(msg);
-        }
-
-        if let Some(msg) = complete_msg.take() {
-            tmsg.body_mut().push(msg);
-        }
+        tmsg.body_mut().append(&mut self.bundle);
+        self.bundle_size = 0;
 
         self.client_internal_mut()
             .get_domain_bus(self.sender.domain())?
@@ -0,0 +1,211 @@
+//! Bus message signing and encryption.
+//!
+//! Each bus domain may be assigned a shared key in the
+//! <message_security> section of opensrf_core.xml.  Messages sent from
+//! a domain with a key are signed with an HMAC-SHA256 of every envelope
+//! field, the signing time, and the message body.  Optionally, the
+//! body is first encrypted with AES-256-GCM using a key derived from
+//! the same secret.
+//!
+//! Since the recipient and router fields are signed, a client which
+//! receives a signed message cannot redirect it or replay it as a
+//! router command.
+//!
+//! Receivers verify messages using the key for the domain in the
+//! sender's address.  Once a domain has a key, unsigned or badly
+//! signed messages claiming to come from that domain are discarded,
+//! so a client which only holds the public domain key cannot forge
+//! private domain traffic.
+//!
+//! Signatures travel in the "signature" and "signed_at" fields of the
+//! TransportMessage JSON.  Encrypted bodies replace the "body" field
+//! with a base64 "body_enc" field.
+//!
+//! Routers re-sign the messages they forward, so a router needs the
+//! keys for every domain whose traffic it passes along.
+use crate::osrf::addr::BusAddress;
+use crate::osrf::conf::MessageSecurity;
+use crate::EgResult;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use json::JsonValue;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const NONCE_SIZE: usize = 12;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Domain of the address in the message "from" field.
+fn sender_domain(json_val: &JsonValue) -> EgResult<String> {
+    let from = json_val["from"]
+        .as_str()
+        .ok_or("Bus message has no sender")?;
+
+    Ok(BusAddress::from_str(from)?.domain().to_string())
+}
+
+/// The encryption key is derived from, but not the same as, the
+/// signing key.
+fn cipher(key: &str) -> Aes256Gcm {
+    let mut hasher = Sha256::new();
+    hasher.update(b"osrf-message-encryption:");
+    hasher.update(key.as_bytes());
+
+    Aes256Gcm::new(&hasher.finalize())
+}
+
+/// Envelope fields covered by the signature, in signing order.
+const SIGNED_FIELDS: &[&str] = &[
+    "to",
+    "from",
+    "thread",
+    "osrf_xid",
+    "router_command",
+    "router_class",
+    "router_reply",
+    "traceparent",
+];
+
+fn mac(key: &str, json_val: &JsonValue, signed_at: u64) -> EgResult<HmacSha256> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.as_bytes())
+        .map_err(|e| format!("Invalid message security key: {e}"))?;
+
+    let body = match json_val["body_enc"].as_str() {
+        Some(b) => b.to_string(),
+        None => json_val["body"].dump(),
+    };
+
+    // A JSON array gives us an unambiguous encoding of the envelope,
+    // with absent fields as null.
+    let mut envelope = JsonValue::new_array();
+    for field in SIGNED_FIELDS {
+        let value = match json_val[*field].as_str() {
+            Some(v) => json::from(v),
+            None => JsonValue::Null,
+        };
+        envelope.push(value).ok();
+    }
+    envelope.push(signed_at).ok();
+
+    mac.update(envelope.dump().as_bytes());
+    mac.update(b"\n");
+    mac.update(body.as_bytes());
+
+    Ok(mac)
+}
+
+/// Sign, and possibly encrypt, an outbound message.
+///
+/// Messages from domains we have no key for are left as-is.
+pub fn seal(json_val: &mut JsonValue, security: &MessageSecurity) -> EgResult<()> {
+    // Drop any signature from a forwarded message.  It's replaced
+    // below if we can.
+    json_val.remove("signature");
+    json_val.remove("signed_at");
+
+    let domain = sender_domain(json_val)?;
+
+    let key = match security.key(&domain) {
+        Some(k) => k,
+        None => return Ok(()),
+    };
+
+    if security.encrypt() {
+        let body = json_val.remove("body").dump();
+        let nonce: [u8; NONCE_SIZE] = rand::random();
+
+        let mut sealed = cipher(key)
+            .encrypt(Nonce::from_slice(&nonce), body.as_bytes())
+            .map_err(|e| format!("Cannot encrypt message: {e}"))?;
+
+        let mut data = nonce.to_vec();
+        data.append(&mut sealed);
+
+        json_val["body_enc"] = json::from(BASE64.encode(data));
+    }
+
+    let signed_at = now();
+    let signature = mac(key, json_val, signed_at)?.finalize().into_bytes();
+
+    json_val["signed_at"] = json::from(signed_at);
+    json_val["signature"] = json::from(BASE64.encode(signature));
+
+    Ok(())
+}
+
+/// Verify, and if needed decrypt, an inbound message.
+///
+/// Returns Err if the message should be discarded.
+pub fn open(json_val: &mut JsonValue, security: &MessageSecurity) -> EgResult<()> {
+    let domain = sender_domain(json_val)?;
+
+    let key = match security.key(&domain) {
+        Some(k) => k,
+        None => {
+            if security.require_signatures() {
+                return Err(format!("No key to verify message from domain {domain}").into());
+            }
+            if json_val.has_key("body_enc") {
+                return Err(format!("No key to decrypt message from domain {domain}").into());
+            }
+            return Ok(());
+        }
+    };
+
+    let signature = json_val.remove("signature");
+    let signature = signature
+        .as_str()
+        .ok_or_else(|| format!("Unsigned message from domain {domain}"))?;
+
+    let signature = BASE64
+        .decode(signature)
+        .map_err(|e| format!("Invalid message signature: {e}"))?;
+
+    let signed_at = json_val
+        .remove("signed_at")
+        .as_u64()
+        .ok_or("Signed message has no signing time")?;
+
+    mac(key, json_val, signed_at)?
+        .verify_slice(&signature)
+        .map_err(|_| format!("Invalid signature on message from domain {domain}"))?;
+
+    if security.max_age() > 0 && now().abs_diff(signed_at) > security.max_age() {
+        return Err(format!("Expired signature on message from domain {domain}").into());
+    }
+
+    if let Some(data) = json_val.remove("body_enc").as_str() {
+        let data = BASE64
+            .decode(data)
+            .map_err(|e| format!("Invalid encrypted message body: {e}"))?;
+
+        if data.len() < NONCE_SIZE {
+            return Err("Invalid encrypted message body".into());
+        }
+
+        let (nonce, sealed) = data.split_at(NONCE_SIZE);
+
+        let body = cipher(key)
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| format!("Cannot decrypt message from domain {domain}"))?;
+
+        let body =
+            String::from_utf8(body).map_err(|e| format!("Invalid encrypted message body: {e}"))?;
+
+        json_val["body"] =
+            json::parse(&body).map_err(|e| format!("Invalid encrypted message body: {e}"))?;
+    }
+
+    Ok(())
+}
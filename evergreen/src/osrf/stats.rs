@@ -0,0 +1,272 @@
+//! Request statistics for OpenSRF services.
+//!
+//! One set of statistics is maintained per process, shared by all
+//! worker threads.  Statistics are reported via the opensrf.system.stats
+//! API and optionally written to the activity log.
+use crate as eg;
+use crate::EgValue;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+static SERVICE_STATS: OnceLock<Mutex<ServiceStats>> = OnceLock::new();
+
+/// Upper bounds in milliseconds of our latency histogram buckets.
+///
+/// Calls which exceed the last bucket are counted in a final "+Inf" bucket.
+pub const LATENCY_BUCKETS_MS: &[u64] = &[10, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000];
+
+/// Lock and return the process-wide service statistics.
+pub fn stats() -> MutexGuard<'static, ServiceStats> {
+    SERVICE_STATS
+        .get_or_init(|| Mutex::new(ServiceStats::new()))
+        .lock()
+        // Stats are not critical.  Carry on if a thread panicked
+        // while holding the lock.
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Call statistics for a single API.
+#[derive(Debug, Clone)]
+pub struct MethodStats {
+    pub calls: u64,
+    pub errors: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+    /// One count per LATENCY_BUCKETS_MS entry plus one for "+Inf".
+    pub histogram: Vec<u64>,
+}
+
+impl MethodStats {
+    fn new() -> Self {
+        MethodStats {
+            calls: 0,
+            errors: 0,
+            total_time: Duration::ZERO,
+            max_time: Duration::ZERO,
+            histogram: vec![0; LATENCY_BUCKETS_MS.len() + 1],
+        }
+    }
+
+    fn record(&mut self, duration: Duration, failed: bool) {
+        self.calls += 1;
+        if failed {
+            self.errors += 1;
+        }
+
+        self.total_time += duration;
+        if duration > self.max_time {
+            self.max_time = duration;
+        }
+
+        let ms = duration.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|b| ms <= *b)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+
+        self.histogram[bucket] += 1;
+    }
+
+    pub fn avg_time(&self) -> Duration {
+        if self.calls == 0 {
+            Duration::ZERO
+        } else {
+            self.total_time / self.calls as u32
+        }
+    }
+
+    pub fn to_eg_value(&self) -> EgValue {
+        let mut histogram = eg::hash! {};
+
+        for (idx, count) in self.histogram.iter().enumerate() {
+            let key = match LATENCY_BUCKETS_MS.get(idx) {
+                Some(ms) => format!("le_{ms}ms"),
+                None => "le_inf".to_string(),
+            };
+            histogram[&key] = EgValue::from(*count);
+        }
+
+        eg::hash! {
+            "calls": self.calls,
+            "errors": self.errors,
+            "avg_ms": self.avg_time().as_millis() as u64,
+            "max_ms": self.max_time.as_millis() as u64,
+            "histogram": histogram,
+        }
+    }
+}
+
+/// Activity statistics for a single worker thread.
+#[derive(Debug, Clone)]
+pub struct WorkerStats {
+    pub requests: u64,
+    pub busy_time: Duration,
+    pub idle_time: Duration,
+    pub active: bool,
+    /// When the worker last changed state.
+    pub state_since: Instant,
+}
+
+impl WorkerStats {
+    fn new() -> Self {
+        WorkerStats {
+            requests: 0,
+            busy_time: Duration::ZERO,
+            idle_time: Duration::ZERO,
+            active: false,
+            state_since: Instant::now(),
+        }
+    }
+
+    /// Apply the time spent in the current state to our totals.
+    fn set_active(&mut self, active: bool) {
+        let elapsed = self.state_since.elapsed();
+
+        if self.active {
+            self.busy_time += elapsed;
+        } else {
+            self.idle_time += elapsed;
+        }
+
+        self.active = active;
+        self.state_since = Instant::now();
+    }
+
+    pub fn to_eg_value(&self) -> EgValue {
+        // Include time spent in the current state.
+        let (mut busy, mut idle) = (self.busy_time, self.idle_time);
+        if self.active {
+            busy += self.state_since.elapsed();
+        } else {
+            idle += self.state_since.elapsed();
+        }
+
+        eg::hash! {
+            "requests": self.requests,
+            "busy_secs": busy.as_secs(),
+            "idle_secs": idle.as_secs(),
+            "state": if self.active { "active" } else { "idle" },
+        }
+    }
+}
+
+/// Statistics for all API calls and workers within a service process.
+#[derive(Debug)]
+pub struct ServiceStats {
+    started: Instant,
+    methods: HashMap<String, MethodStats>,
+    workers: HashMap<u64, WorkerStats>,
+    /// Requests handled by workers which have since exited.
+    retired_requests: u64,
+    retired_workers: u64,
+}
+
+impl ServiceStats {
+    fn new() -> Self {
+        ServiceStats {
+            started: Instant::now(),
+            methods: HashMap::new(),
+            workers: HashMap::new(),
+            retired_requests: 0,
+            retired_workers: 0,
+        }
+    }
+
+    pub fn methods(&self) -> &HashMap<String, MethodStats> {
+        &self.methods
+    }
+
+    pub fn workers(&self) -> &HashMap<u64, WorkerStats> {
+        &self.workers
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Record the outcome of one API call.
+    pub fn record_call(&mut self, method: &str, duration: Duration, failed: bool) {
+        if let Some(m) = self.methods.get_mut(method) {
+            m.record(duration, failed);
+            return;
+        }
+
+        let mut m = MethodStats::new();
+        m.record(duration, failed);
+        self.methods.insert(method.to_string(), m);
+    }
+
+    /// Track a worker moving between idle and active states.
+    pub fn worker_state(&mut self, worker_id: u64, active: bool) {
+        self.workers
+            .entry(worker_id)
+            .or_insert_with(WorkerStats::new)
+            .set_active(active);
+    }
+
+    /// Count a completed request (or stateful conversation) for a worker.
+    pub fn worker_request(&mut self, worker_id: u64) {
+        self.workers
+            .entry(worker_id)
+            .or_insert_with(WorkerStats::new)
+            .requests += 1;
+    }
+
+    /// Remove an exited worker, retaining its request count.
+    pub fn worker_exit(&mut self, worker_id: u64) {
+        if let Some(w) = self.workers.remove(&worker_id) {
+            self.retired_requests += w.requests;
+            self.retired_workers += 1;
+        }
+    }
+
+    /// Total requests handled by current and past workers.
+    pub fn total_requests(&self) -> u64 {
+        self.retired_requests + self.workers.values().map(|w| w.requests).sum::<u64>()
+    }
+
+    pub fn to_eg_value(&self) -> EgValue {
+        let mut methods = eg::hash! {};
+        for (name, m) in self.methods.iter() {
+            methods[name] = m.to_eg_value();
+        }
+
+        let mut workers = eg::hash! {};
+        for (id, w) in self.workers.iter() {
+            workers[&id.to_string()] = w.to_eg_value();
+        }
+
+        eg::hash! {
+            "uptime": self.uptime().as_secs(),
+            "total_requests": self.total_requests(),
+            "retired_workers": self.retired_workers,
+            "methods": methods,
+            "workers": workers,
+        }
+    }
+
+    /// Compact one-line summary suitable for the activity log.
+    ///
+    /// Includes per-method call counts and latencies, omitting
+    /// per-worker details.
+    pub fn summary(&self) -> EgValue {
+        let mut methods = eg::hash! {};
+        for (name, m) in self.methods.iter() {
+            methods[name] = eg::hash! {
+                "calls": m.calls,
+                "errors": m.errors,
+                "avg_ms": m.avg_time().as_millis() as u64,
+                "max_ms": m.max_time.as_millis() as u64,
+            };
+        }
+
+        eg::hash! {
+            "uptime": self.uptime().as_secs(),
+            "total_requests": self.total_requests(),
+            "workers": self.workers.len(),
+            "active_workers": self.workers.values().filter(|w| w.active).count(),
+            "methods": methods,
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Typed client stub generation.
+//!
+//! Translates published method definitions, as returned by
+//! opensrf.system.method.all or compiled from StaticMethodDef tables,
+//! into Rust source containing one wrapper function per method.
+//!
+//! Each wrapper accepts a Client plus one named argument per method
+//! parameter, so typos in API names and incorrect argument counts
+//! become compile errors instead of runtime failures.
+//!
+//! ```text
+//! pub fn renewal_chain_retrieve_by_circ(
+//!     client: &Client,
+//!     authtoken: &str,
+//!     circ_id: i64,
+//! ) -> EgResult<ResponseIterator>
+//! ```
+use crate::osrf::method::StaticMethodDef;
+use crate::{EgResult, EgValue};
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Crate name used in paths within generated code.
+const CRATE_NAME: &str = "evergreen";
+
+/// Generic methods published by every service.
+const SYSTEM_METHOD_PREFIX: &str = "opensrf.system.";
+
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+    "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "try", "type", "typeof", "unsafe", "use", "virtual", "where",
+    "while", "yield",
+];
+
+/// Names used by the generated function body.
+const RESERVED_PARAM_NAMES: &[&str] = &["client", "params"];
+
+/// Remove trailing NULL values for optional parameters which were
+/// not provided, so the request satisfies the method's param count.
+///
+/// Generated stubs call this for methods with optional parameters.
+///
+/// ```
+/// use evergreen::osrf::stubs;
+/// use evergreen::EgValue;
+///
+/// let params = vec![EgValue::from("a"), EgValue::Null, EgValue::Null];
+/// assert_eq!(stubs::trim_optional(params, 1).len(), 1);
+///
+/// let params = vec![EgValue::Null, EgValue::Null];
+/// assert_eq!(stubs::trim_optional(params, 1).len(), 1);
+/// ```
+pub fn trim_optional(mut params: Vec<EgValue>, required: usize) -> Vec<EgValue> {
+    while params.len() > required && params.last().map(|p| p.is_null()).unwrap_or(false) {
+        params.pop();
+    }
+    params
+}
+
+/// Generate stubs from a StaticMethodDef table, e.g. the list of
+/// methods compiled into a Rust service.
+pub fn generate_from_static(
+    service: &str,
+    api_prefix: &str,
+    defs: &[StaticMethodDef],
+) -> EgResult<String> {
+    let methods: Vec<EgValue> = defs
+        .iter()
+        .map(|d| d.into_method(api_prefix).to_eg_value())
+        .collect();
+
+    generate(service, &methods)
+}
+
+/// Generate a Rust module containing a wrapper function for each
+/// method definition.
+///
+/// Method definitions use the opensrf.system.method.all response
+/// format.  The older Perl format, which uses a numeric "argc" and
+/// parameter "type" values, is also supported.
+pub fn generate(service: &str, methods: &[EgValue]) -> EgResult<String> {
+    let mut methods: Vec<&EgValue> = methods
+        .iter()
+        .filter(|m| match m["api_name"].as_str() {
+            Some(n) => !n.starts_with(SYSTEM_METHOD_PREFIX),
+            None => false,
+        })
+        .collect();
+
+    // Stable output regardless of response order.
+    methods.sort_by(|a, b| a["api_name"].as_str().cmp(&b["api_name"].as_str()));
+
+    let mut body = String::new();
+    let mut fn_names = HashSet::new();
+
+    for method in methods {
+        let stub = MethodStub::from_eg_value(service, method, &mut fn_names)?;
+        stub.write(&mut body);
+    }
+
+    let mut src = String::new();
+
+    writeln!(src, "//! Client stubs for {service}.").ok();
+    writeln!(src, "//!").ok();
+    writeln!(
+        src,
+        "//! Generated by eg-client-stubs.  Do not edit by hand."
+    )
+    .ok();
+    writeln!(src, "#![allow(clippy::too_many_arguments, unused_imports)]").ok();
+    writeln!(src, "use {CRATE_NAME}::osrf::client::Client;").ok();
+    writeln!(src, "use {CRATE_NAME}::osrf::params::ApiParams;").ok();
+    writeln!(src, "use {CRATE_NAME}::osrf::session::ResponseIterator;").ok();
+    writeln!(src, "use {CRATE_NAME}::osrf::stubs;").ok();
+    writeln!(src, "use {CRATE_NAME}::{{EgResult, EgValue}};").ok();
+    writeln!(src).ok();
+    writeln!(src, "pub const SERVICE: &str = \"{service}\";").ok();
+    src.push_str(&body);
+
+    Ok(src)
+}
+
+/// Rust type used for a parameter in the generated signature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StubType {
+    Str,
+    Bool,
+    Int,
+    Value,
+}
+
+impl StubType {
+    fn from_datatype(datatype: &str) -> StubType {
+        match datatype {
+            "String" | "string" => StubType::Str,
+            "Boolish" | "bool" => StubType::Bool,
+            "Number" | "number" => StubType::Int,
+            _ => StubType::Value,
+        }
+    }
+
+    fn signature(&self, required: bool) -> &'static str {
+        match (self, required) {
+            (StubType::Str, true) => "&str",
+            (StubType::Str, false) => "Option<&str>",
+            (StubType::Bool, true) => "bool",
+            (StubType::Bool, false) => "Option<bool>",
+            (StubType::Int, true) => "i64",
+            (StubType::Int, false) => "Option<i64>",
+            (StubType::Value, true) => "impl Into<EgValue>",
+            (StubType::Value, false) => "Option<EgValue>",
+        }
+    }
+
+    /// Expression translating the named argument into an EgValue.
+    fn value_expr(&self, required: bool, name: &str) -> String {
+        match (self, required) {
+            (StubType::Value, true) => format!("{name}.into()"),
+            (StubType::Value, false) => format!("{name}.unwrap_or(EgValue::Null)"),
+            _ => format!("EgValue::from({name})"),
+        }
+    }
+}
+
+struct ParamStub {
+    name: String,
+    datatype: String,
+    stub_type: StubType,
+    required: bool,
+    desc: Option<String>,
+}
+
+struct MethodStub {
+    api_name: String,
+    fn_name: String,
+    desc: Option<String>,
+    /// None if the method accepts parameters but does not define them.
+    params: Option<Vec<ParamStub>>,
+    auth: Option<String>,
+}
+
+impl MethodStub {
+    fn from_eg_value(
+        service: &str,
+        method: &EgValue,
+        fn_names: &mut HashSet<String>,
+    ) -> EgResult<MethodStub> {
+        let api_name = method["api_name"]
+            .as_str()
+            .ok_or_else(|| format!("Method definition has no api_name: {}", method.dump()))?;
+
+        let short_name = api_name
+            .strip_prefix(service)
+            .and_then(|n| n.strip_prefix('.'))
+            .unwrap_or(api_name);
+
+        let mut fn_name = identifier(short_name, "api");
+        if fn_names.contains(&fn_name) {
+            let mut count = 2;
+            while fn_names.contains(&format!("{fn_name}_{count}")) {
+                count += 1;
+            }
+            fn_name = format!("{fn_name}_{count}");
+        }
+        fn_names.insert(fn_name.to_string());
+
+        let min_params = argc_minimum(&method["argc"]);
+        let mut params = Vec::new();
+        let mut param_names = HashSet::new();
+
+        for (idx, param) in method["params"].members().enumerate() {
+            let mut name = identifier(param["name"].as_str().unwrap_or(""), "param");
+
+            if RESERVED_PARAM_NAMES.contains(&name.as_str()) {
+                name += "_";
+            }
+
+            if param_names.contains(&name) {
+                name = format!("{name}{idx}");
+            }
+            param_names.insert(name.to_string());
+
+            let datatype = param["datatype"]
+                .as_str()
+                .or(param["type"].as_str())
+                .unwrap_or("Any")
+                .to_string();
+
+            params.push(ParamStub {
+                name,
+                stub_type: StubType::from_datatype(&datatype),
+                datatype,
+                required: param["required"].as_bool().unwrap_or(idx < min_params),
+                desc: param["desc"].as_str().map(|s| s.to_string()),
+            });
+        }
+
+        let takes_params = !matches!(method["argc"].as_str(), Some("Zero"))
+            && method["argc"].as_usize().unwrap_or(1) > 0;
+
+        let mut auth = None;
+        if method["auth"].is_object() {
+            let perms: Vec<&str> = method["auth"]["perms"]
+                .members()
+                .filter_map(|p| p.as_str())
+                .collect();
+
+            auth = Some(match perms.len() {
+                0 => "Requires authentication.".to_string(),
+                _ => format!(
+                    "Requires authentication and permissions: {}",
+                    perms.join(", ")
+                ),
+            });
+        }
+
+        Ok(MethodStub {
+            fn_name,
+            auth,
+            api_name: api_name.to_string(),
+            desc: method["desc"].as_str().map(|s| s.to_string()),
+            params: if params.is_empty() && takes_params {
+                None
+            } else {
+                Some(params)
+            },
+        })
+    }
+
+    fn write(&self, src: &mut String) {
+        writeln!(src).ok();
+
+        if let Some(desc) = self.desc.as_ref() {
+            write_doc_text(src, "", desc);
+            writeln!(src, "///").ok();
+        }
+
+        writeln!(src, "/// API: `{}`", self.api_name).ok();
+
+        if let Some(params) = self.params.as_ref().filter(|p| !p.is_empty()) {
+            writeln!(src, "///").ok();
+            writeln!(src, "/// # Parameters").ok();
+            writeln!(src, "///").ok();
+
+            for param in params {
+                let optional = if param.required { "" } else { ", optional" };
+                let desc = param.desc.as_deref().unwrap_or("");
+                write_doc_text(
+                    src,
+                    "  ",
+                    &format!("* `{}` (`{}`{optional}) {desc}", param.name, param.datatype),
+                );
+            }
+        }
+
+        if let Some(auth) = self.auth.as_ref() {
+            writeln!(src, "///").ok();
+            writeln!(src, "/// {auth}").ok();
+        }
+
+        writeln!(src, "pub fn {}(", self.fn_name).ok();
+        writeln!(src, "    client: &Client,").ok();
+
+        let params = match self.params.as_ref() {
+            Some(p) => p,
+            None => {
+                // Parameters are not documented.  Pass them through.
+                writeln!(src, "    params: impl Into<ApiParams>,").ok();
+                writeln!(src, ") -> EgResult<ResponseIterator> {{").ok();
+                writeln!(
+                    src,
+                    "    client.send_recv_iter(SERVICE, \"{}\", params)",
+                    self.api_name
+                )
+                .ok();
+                writeln!(src, "}}").ok();
+                return;
+            }
+        };
+
+        for param in params {
+            writeln!(
+                src,
+                "    {}: {},",
+                param.name,
+                param.stub_type.signature(param.required)
+            )
+            .ok();
+        }
+
+        writeln!(src, ") -> EgResult<ResponseIterator> {{").ok();
+
+        let values: Vec<String> = params
+            .iter()
+            .map(|p| p.stub_type.value_expr(p.required, &p.name))
+            .collect();
+
+        let required = params.iter().filter(|p| p.required).count();
+
+        if values.is_empty() {
+            writeln!(src, "    let params: Vec<EgValue> = Vec::new();").ok();
+        } else if required < params.len() {
+            writeln!(src, "    let params = stubs::trim_optional(").ok();
+            writeln!(src, "        vec![").ok();
+            for value in values.iter() {
+                writeln!(src, "            {value},").ok();
+            }
+            writeln!(src, "        ],").ok();
+            writeln!(src, "        {required},").ok();
+            writeln!(src, "    );").ok();
+        } else {
+            writeln!(src, "    let params: Vec<EgValue> = vec![").ok();
+            for value in values.iter() {
+                writeln!(src, "        {value},").ok();
+            }
+            writeln!(src, "    ];").ok();
+        }
+
+        writeln!(
+            src,
+            "    client.send_recv_iter(SERVICE, \"{}\", params)",
+            self.api_name
+        )
+        .ok();
+        writeln!(src, "}}").ok();
+    }
+}
+
+/// Write free-form text as doc comment lines.
+///
+/// Continuation lines are indented with `indent`.
+fn write_doc_text(src: &mut String, indent: &str, text: &str) {
+    let mut first = true;
+    for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        if first {
+            writeln!(src, "/// {line}").ok();
+            first = false;
+        } else {
+            writeln!(src, "/// {indent}{line}").ok();
+        }
+    }
+}
+
+/// Minimum param count from a method's "argc" value, which is either
+/// a ParamCount display string or a number.
+fn argc_minimum(argc: &EgValue) -> usize {
+    if let Some(n) = argc.as_usize() {
+        return n;
+    }
+
+    let argc = match argc.as_str() {
+        Some(s) => s,
+        None => return 0,
+    };
+
+    // "Exactly 2", "AtLeast 1", "Range 1..3", "Any", "Zero"
+    argc.split_whitespace()
+        .nth(1)
+        .and_then(|n| n.split("..").next())
+        .and_then(|n| n.parse::<usize>().ok())
+        .unwrap_or(0)
+}
+
+/// Translate an API or parameter name into a Rust identifier.
+///
+/// E.g. "renewal_chain.retrieve_by_circ" => "renewal_chain_retrieve_by_circ"
+fn identifier(name: &str, fallback: &str) -> String {
+    let mut ident = String::new();
+
+    for c in name.chars() {
+        let c = c.to_ascii_lowercase();
+        if c.is_ascii_alphanumeric() {
+            ident.push(c);
+        } else if !ident.is_empty() && !ident.ends_with('_') {
+            ident.push('_');
+        }
+    }
+
+    let mut ident = ident.trim_end_matches('_').to_string();
+
+    if ident.is_empty() {
+        return fallback.to_string();
+    }
+
+    if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident = format!("{fallback}_{ident}");
+    }
+
+    if RUST_KEYWORDS.contains(&ident.as_str()) {
+        ident += "_";
+    }
+
+    ident
+}
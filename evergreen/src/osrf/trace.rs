@@ -0,0 +1,537 @@
+//! Distributed request tracing.
+//!
+//! Spans record the timing of client requests, router forwarding,
+//! service method execution, and database queries.  Trace context
+//! travels between processes in the "traceparent" field of each
+//! TransportMessage using the W3C Trace Context format:
+//!
+//! 00-<32 hex trace id>-<16 hex parent span id>-<2 hex flags>
+//!
+//! Finished spans are exported in batches as OTLP-compatible JSON,
+//! either appended as lines to a file or posted to a collector:
+//!
+//! * file:/var/log/osrf/spans.jsonl
+//! * http://localhost:4318/v1/traces
+//!
+//! The export target is set with a <tracing><export> element in the
+//! opensrf_core.xml client section or the OSRF_TRACE_EXPORT environment
+//! variable.  Processes without an export target still propagate any
+//! trace context they receive, so tracing may be enabled selectively.
+use crate::EgResult;
+use json::JsonValue;
+use rand::Rng;
+use std::cell::RefCell;
+use std::fs;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Export a batch once it contains this many spans.
+const EXPORT_BATCH_SIZE: usize = 64;
+
+/// Export a partial batch after it has waited this long.
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest attribute value we record, e.g. for SQL statements.
+const MAX_ATTR_LENGTH: usize = 1024;
+
+const TRACE_VERSION: &str = "00";
+const FLAG_SAMPLED: u8 = 0x01;
+
+static EXPORTER: OnceLock<Mutex<mpsc::Sender<SpanData>>> = OnceLock::new();
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+}
+
+/// Start the background span exporter.
+///
+/// `export` is a "file:<path>" or "http://..." target.  The
+/// OSRF_TRACE_EXPORT environment variable takes precedence.
+/// Returns Ok(false) if no export target is configured.
+pub fn init(service_name: &str, export: Option<&str>) -> EgResult<bool> {
+    let target = match std::env::var("OSRF_TRACE_EXPORT") {
+        Ok(t) => t,
+        Err(_) => match export {
+            Some(t) => t.to_string(),
+            None => return Ok(false),
+        },
+    };
+
+    let target = ExportTarget::parse(&target)?;
+
+    if EXPORTER.get().is_some() {
+        return Err("Trace exporter already initialized".into());
+    }
+
+    let (tx, rx) = mpsc::channel();
+
+    EXPORTER.set(Mutex::new(tx)).ok();
+
+    let service_name = service_name.to_string();
+
+    thread::spawn(move || export_loop(rx, target, service_name));
+
+    log::info!("Exporting trace spans");
+
+    Ok(true)
+}
+
+/// True if finished spans are exported from this process.
+pub fn enabled() -> bool {
+    EXPORTER.get().is_some()
+}
+
+/// Apply the trace context received from another process, e.g. from
+/// the traceparent of an inbound TransportMessage, as the parent of
+/// spans created in this thread.
+///
+/// Invalid or missing values clear the current context.
+pub fn set_remote_context(traceparent: Option<&str>) {
+    let context = traceparent.and_then(TraceContext::parse);
+    CURRENT_CONTEXT.with(|c| *c.borrow_mut() = context);
+}
+
+/// The W3C traceparent value for the current context, if any.
+pub fn current_traceparent() -> Option<String> {
+    CURRENT_CONTEXT.with(|c| c.borrow().as_ref().map(|ctx| ctx.to_traceparent()))
+}
+
+fn current_context() -> Option<TraceContext> {
+    CURRENT_CONTEXT.with(|c| c.borrow().clone())
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..bytes)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .collect()
+}
+
+fn epoch_nanos(t: SystemTime) -> u128 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+/// W3C trace context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceContext {
+    trace_id: String,
+    span_id: String,
+    flags: u8,
+}
+
+impl TraceContext {
+    /// Parse a W3C traceparent value.
+    ///
+    /// ```
+    /// use evergreen::osrf::trace::TraceContext;
+    ///
+    /// let tp = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    /// let ctx = TraceContext::parse(tp).unwrap();
+    ///
+    /// assert_eq!(ctx.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+    /// assert_eq!(ctx.span_id(), "b7ad6b7169203331");
+    /// assert!(ctx.sampled());
+    /// assert_eq!(ctx.to_traceparent(), tp);
+    ///
+    /// assert!(TraceContext::parse("00-abc-def-01").is_none());
+    /// ```
+    pub fn parse(traceparent: &str) -> Option<TraceContext> {
+        let parts: Vec<&str> = traceparent.trim().split('-').collect();
+
+        if parts.len() < 4 || parts[0] != TRACE_VERSION {
+            return None;
+        }
+
+        let is_hex =
+            |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit());
+
+        if !is_hex(parts[1], 32) || !is_hex(parts[2], 16) || !is_hex(parts[3], 2) {
+            return None;
+        }
+
+        // All-zero IDs are invalid.
+        if parts[1].chars().all(|c| c == '0') || parts[2].chars().all(|c| c == '0') {
+            return None;
+        }
+
+        Some(TraceContext {
+            trace_id: parts[1].to_lowercase(),
+            span_id: parts[2].to_lowercase(),
+            flags: u8::from_str_radix(parts[3], 16).ok()?,
+        })
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    pub fn sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "{TRACE_VERSION}-{}-{}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+impl SpanKind {
+    /// OTLP SpanKind value
+    fn otlp_value(&self) -> u8 {
+        match self {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        }
+    }
+}
+
+/// A finished span on its way to the exporter.
+#[derive(Debug)]
+struct SpanData {
+    name: String,
+    kind: SpanKind,
+    context: TraceContext,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+impl SpanData {
+    fn to_otlp(&self) -> JsonValue {
+        let mut attrs = JsonValue::new_array();
+        for (key, value) in self.attributes.iter() {
+            attrs
+                .push(json::object! {
+                    "key": key.as_str(),
+                    "value": {"stringValue": value.as_str()},
+                })
+                .ok();
+        }
+
+        let mut span = json::object! {
+            "traceId": self.context.trace_id(),
+            "spanId": self.context.span_id(),
+            "name": self.name.as_str(),
+            "kind": self.kind.otlp_value(),
+            // 64-bit integers are encoded as strings in OTLP JSON.
+            "startTimeUnixNano": epoch_nanos(self.start).to_string(),
+            "endTimeUnixNano": epoch_nanos(self.end).to_string(),
+            "attributes": attrs,
+        };
+
+        if let Some(p) = self.parent_span_id.as_ref() {
+            span["parentSpanId"] = p.as_str().into();
+        }
+
+        if let Some(e) = self.error.as_ref() {
+            // STATUS_CODE_ERROR
+            span["status"] = json::object! {"code": 2, "message": e.as_str()};
+        }
+
+        span
+    }
+}
+
+/// One timed operation within a trace.
+///
+/// Spans are recorded when ended or dropped.
+pub struct Span {
+    name: String,
+    kind: SpanKind,
+
+    /// None if tracing is inactive for this span.
+    context: Option<TraceContext>,
+    parent_span_id: Option<String>,
+
+    /// Context to restore when an entered span ends.
+    previous: Option<Option<TraceContext>>,
+
+    start: SystemTime,
+    started: Instant,
+    attributes: Vec<(String, String)>,
+    error: Option<String>,
+    ended: bool,
+}
+
+impl Span {
+    fn new(name: &str, kind: SpanKind) -> Span {
+        let parent = current_context();
+
+        // Only trace when spans are exported from this process or
+        // a caller has asked for this request to be traced.
+        let context = match parent.as_ref() {
+            Some(p) if p.sampled() || enabled() => Some(TraceContext {
+                trace_id: p.trace_id.to_string(),
+                span_id: random_hex(8),
+                flags: p.flags,
+            }),
+            None if enabled() => Some(TraceContext {
+                trace_id: random_hex(16),
+                span_id: random_hex(8),
+                flags: FLAG_SAMPLED,
+            }),
+            _ => None,
+        };
+
+        Span {
+            kind,
+            context,
+            name: name.to_string(),
+            parent_span_id: parent.map(|p| p.span_id),
+            previous: None,
+            start: SystemTime::now(),
+            started: Instant::now(),
+            attributes: Vec::new(),
+            error: None,
+            ended: false,
+        }
+    }
+
+    /// Start a span as a child of the current context and make it
+    /// the current context for this thread until it ends.
+    pub fn enter(name: &str, kind: SpanKind) -> Span {
+        let mut span = Span::new(name, kind);
+
+        if let Some(ctx) = span.context.clone() {
+            let prev = CURRENT_CONTEXT.with(|c| c.borrow_mut().replace(ctx));
+            span.previous = Some(prev);
+        }
+
+        span
+    }
+
+    /// Start a span as a child of the current context without
+    /// changing the current context.
+    ///
+    /// Useful for operations which complete out of band, e.g.
+    /// an API call whose responses arrive later.
+    pub fn detached(name: &str, kind: SpanKind) -> Span {
+        Span::new(name, kind)
+    }
+
+    /// Trace context for this span, if tracing is active.
+    pub fn context(&self) -> Option<&TraceContext> {
+        self.context.as_ref()
+    }
+
+    /// W3C traceparent value which makes this span the parent of
+    /// operations in another process.
+    pub fn traceparent(&self) -> Option<String> {
+        self.context.as_ref().map(|c| c.to_traceparent())
+    }
+
+    pub fn parent_span_id(&self) -> Option<&str> {
+        self.parent_span_id.as_deref()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Add an attribute, e.g. ("osrf.method", "opensrf.system.echo")
+    pub fn set_attr(&mut self, key: &str, value: &str) {
+        if self.context.is_none() {
+            return;
+        }
+
+        let value = if value.len() > MAX_ATTR_LENGTH {
+            let mut end = MAX_ATTR_LENGTH;
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            &value[..end]
+        } else {
+            value
+        };
+
+        self.attributes.push((key.to_string(), value.to_string()));
+    }
+
+    /// Mark the span as failed.
+    pub fn set_error(&mut self, error: &str) {
+        self.error = Some(error.to_string());
+    }
+
+    /// End the span now.
+    pub fn end(mut self) {
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        if self.ended {
+            return;
+        }
+        self.ended = true;
+
+        if let Some(prev) = self.previous.take() {
+            CURRENT_CONTEXT.with(|c| *c.borrow_mut() = prev);
+        }
+
+        let context = match self.context.take() {
+            Some(c) => c,
+            None => return,
+        };
+
+        let exporter = match EXPORTER.get() {
+            Some(e) => e,
+            None => return,
+        };
+
+        let data = SpanData {
+            context,
+            kind: self.kind,
+            name: std::mem::take(&mut self.name),
+            parent_span_id: self.parent_span_id.take(),
+            start: self.start,
+            end: self.start + self.started.elapsed(),
+            attributes: std::mem::take(&mut self.attributes),
+            error: self.error.take(),
+        };
+
+        if let Ok(tx) = exporter.lock() {
+            tx.send(data).ok();
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+enum ExportTarget {
+    File(String),
+    Http {
+        host: String,
+        port: u16,
+        path: String,
+    },
+}
+
+impl ExportTarget {
+    fn parse(target: &str) -> EgResult<ExportTarget> {
+        if let Some(path) = target.strip_prefix("file:") {
+            // Allow file:///path and file:/path
+            let path = path.trim_start_matches("//");
+            return Ok(ExportTarget::File(path.to_string()));
+        }
+
+        let url = url::Url::parse(target)
+            .map_err(|e| format!("Invalid trace export target: {target} {e}"))?;
+
+        if url.scheme() != "http" {
+            return Err(format!("Unsupported trace export target: {target}").into());
+        }
+
+        Ok(ExportTarget::Http {
+            host: url.host_str().unwrap_or("localhost").to_string(),
+            port: url.port().unwrap_or(80),
+            path: url.path().to_string(),
+        })
+    }
+
+    fn export(&self, body: &str) -> Result<(), String> {
+        match self {
+            ExportTarget::File(path) => {
+                let mut file = fs::File::options()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Cannot open trace file {path}: {e}"))?;
+
+                file.write_all(format!("{body}\n").as_bytes())
+                    .map_err(|e| format!("Cannot write trace file {path}: {e}"))
+            }
+            ExportTarget::Http { host, port, path } => {
+                let mut stream = TcpStream::connect((host.as_str(), *port))
+                    .map_err(|e| format!("Cannot connect to trace collector: {e}"))?;
+
+                let request = format!(
+                    "POST {path} HTTP/1.1\r\nHost: {host}:{port}\r\n\
+                    Content-Type: application/json\r\nContent-Length: {}\r\n\
+                    Connection: close\r\n\r\n{body}",
+                    body.len()
+                );
+
+                stream
+                    .write_all(request.as_bytes())
+                    .map_err(|e| format!("Cannot send spans to trace collector: {e}"))
+            }
+        }
+    }
+}
+
+fn export_loop(rx: mpsc::Receiver<SpanData>, target: ExportTarget, service_name: String) {
+    let mut batch: Vec<SpanData> = Vec::new();
+    let mut last_export = Instant::now();
+
+    loop {
+        match rx.recv_timeout(EXPORT_INTERVAL) {
+            Ok(span) => batch.push(span),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+
+        if batch.is_empty() {
+            continue;
+        }
+
+        if batch.len() < EXPORT_BATCH_SIZE && last_export.elapsed() < EXPORT_INTERVAL {
+            continue;
+        }
+
+        let body = otlp_request(&service_name, &batch).dump();
+
+        if let Err(e) = target.export(&body) {
+            log::warn!("Trace export failed: {e}");
+        }
+
+        batch.clear();
+        last_export = Instant::now();
+    }
+}
+
+/// OTLP ExportTraceServiceRequest JSON for a batch of spans.
+fn otlp_request(service_name: &str, batch: &[SpanData]) -> JsonValue {
+    let mut spans = JsonValue::new_array();
+    for span in batch.iter() {
+        spans.push(span.to_otlp()).ok();
+    }
+
+    let mut request = json::object! {
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{
+                    "key": "service.name",
+                    "value": {"stringValue": service_name},
+                }],
+            },
+            "scopeSpans": [{"scope": {"name": "opensrf"}}],
+        }],
+    };
+
+    request["resourceSpans"][0]["scopeSpans"][0]["spans"] = spans;
+    request
+}
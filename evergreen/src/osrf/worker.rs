@@ -1,3 +1,4 @@
+use crate::editor::Editor;
 use crate::osrf::addr::BusAddress;
 use crate::osrf::app;
 use crate::osrf::client::{Client, ClientSingleton};
@@ -10,9 +11,10 @@ use crate::osrf::message::MessageType;
 use crate::osrf::message::Payload;
 use crate::osrf::message::TransportMessage;
 use crate::osrf::method;
-use crate::osrf::method::ParamCount;
 use crate::osrf::sclient::HostSettings;
 use crate::osrf::session::ServerSession;
+use crate::osrf::stats;
+use crate::osrf::trace::{self, SpanKind};
 use crate::util;
 use crate::EgResult;
 use mptc::signals::SignalTracker;
@@ -27,6 +29,19 @@ use std::time;
 // How often each worker wakes to check for shutdown signals, etc.
 const IDLE_WAKE_TIME: i32 = 5;
 
+/// Once a drain is requested, keep polling our service queue for this
+/// many seconds before exiting on an empty queue.  This gives the
+/// router time to process our unregister, since requests it routes
+/// our way in the meantime would otherwise go unanswered.
+const DRAIN_GRACE_PERIOD: u64 = (IDLE_WAKE_TIME * 3) as u64;
+
+/// Responses whose JSON exceeds this many bytes are sent to the
+/// caller as a series of Partial messages.
+///
+/// Disabled by default, since measuring each response means encoding
+/// it an extra time.  Set unix_config/max_chunk_size to enable.
+const DEFAULT_MAX_CHUNK_SIZE: usize = 0;
+
 /// Each worker thread is in one of these states.
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum WorkerState {
@@ -76,6 +91,18 @@ pub struct Worker {
 
     /// Channel for sending worker state info to our parent.
     to_parent_tx: mpsc::SyncSender<WorkerStateEvent>,
+
+    /// Our parent asks us to exit by sending a message on this channel.
+    shutdown_rx: mpsc::Receiver<()>,
+
+    /// Responses larger than this many bytes are sent in chunks.
+    max_chunk_size: usize,
+
+    /// Streamed responses are bundled up to this many bytes.
+    max_bundle_size: usize,
+
+    /// When we first noticed a drain request.
+    drain_seen: Option<time::Instant>,
 }
 
 impl fmt::Display for Worker {
@@ -91,6 +118,7 @@ impl Worker {
         sig_tracker: SignalTracker,
         methods: Arc<HashMap<String, method::MethodDef>>,
         to_parent_tx: mpsc::SyncSender<WorkerStateEvent>,
+        shutdown_rx: mpsc::Receiver<()>,
     ) -> EgResult<Worker> {
         let client = Client::connect()?;
 
@@ -101,8 +129,12 @@ impl Worker {
             methods,
             client,
             to_parent_tx,
+            shutdown_rx,
             session: None,
             connected: false,
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
+            max_bundle_size: 0,
+            drain_seen: None,
         })
     }
 
@@ -126,6 +158,18 @@ impl Worker {
         self.worker_id
     }
 
+    /// True if a drain has been requested and at least
+    /// DRAIN_GRACE_PERIOD seconds have passed since we noticed.
+    fn drain_grace_expired(&mut self) -> bool {
+        if !self.sig_tracker.drain_requested() {
+            return false;
+        }
+
+        let seen = *self.drain_seen.get_or_insert_with(time::Instant::now);
+
+        seen.elapsed() >= time::Duration::from_secs(DRAIN_GRACE_PERIOD)
+    }
+
     /// Wait for and process inbound API calls.
     //pub fn listen(&mut self, mut appworker: Box<dyn app::ApplicationWorker>) {
     pub fn listen(&mut self, factory: app::ApplicationWorkerFactory) {
@@ -150,6 +194,20 @@ impl Worker {
                 .as_usize()
                 .unwrap_or(5);
 
+        self.max_chunk_size =
+            HostSettings::get(&format!("apps/{}/unix_config/max_chunk_size", self.service))
+                .expect("Host Settings Not Retrieved")
+                .as_usize()
+                .unwrap_or(DEFAULT_MAX_CHUNK_SIZE);
+
+        self.max_bundle_size = HostSettings::get(&format!(
+            "apps/{}/unix_config/max_bundle_size",
+            self.service
+        ))
+        .expect("Host Settings Not Retrieved")
+        .as_usize()
+        .unwrap_or(0);
+
         let mut requests: usize = 0;
 
         // We listen for API calls at an addressed scoped to our
@@ -183,7 +241,15 @@ impl Worker {
                 }
 
                 sent_to = &service_addr;
-                timeout = IDLE_WAKE_TIME;
+
+                // While draining, keep working through any requests
+                // already queued for our service, but once the grace
+                // period has passed, don't wait around for new ones.
+                timeout = if self.drain_grace_expired() {
+                    0
+                } else {
+                    IDLE_WAKE_TIME
+                };
             }
 
             // work_occurred will be true if we handled a message or
@@ -223,6 +289,7 @@ impl Worker {
                     // Increment our message handled count.
                     // Each connected session counts as 1 "request".
                     requests += 1;
+                    stats::stats().worker_request(self.worker_id);
 
                     // An inbound message may have modified our
                     // thread-scoped locale.  Reset our locale back
@@ -230,6 +297,9 @@ impl Worker {
                     // affect future messages.
                     message::reset_thread_locale();
                 }
+            } else if self.drain_grace_expired() {
+                log::info!("{selfstr} has no more work and is draining");
+                break;
             } else {
                 // Let the worker know we woke up and nothing interesting
                 // happened.
@@ -246,6 +316,11 @@ impl Worker {
                 log::info!("{selfstr} received a stop signal");
                 break;
             }
+
+            if self.shutdown_requested() {
+                log::info!("{selfstr} retiring at the request of the server");
+                break;
+            }
         }
 
         log::debug!("{self} exiting listen loop and cleaning up");
@@ -260,6 +335,15 @@ impl Worker {
         self.reset().ok();
     }
 
+    /// True if our parent has asked us to exit or has gone away.
+    fn shutdown_requested(&self) -> bool {
+        match self.shutdown_rx.try_recv() {
+            Ok(()) => true,
+            Err(mpsc::TryRecvError::Empty) => false,
+            Err(mpsc::TryRecvError::Disconnected) => true,
+        }
+    }
+
     /// Call recv() on our message bus and process the response.
     ///
     /// Return value consists of (work_occurred, msg_handled).
@@ -332,6 +416,8 @@ impl Worker {
 
     /// Tell our parent we're about to perform some work.
     fn set_active(&mut self) -> EgResult<()> {
+        stats::stats().worker_state(self.worker_id, true);
+
         if let Err(e) = self.notify_state(WorkerState::Active) {
             Err(format!(
                 "{self} failed to notify parent of Active state. Exiting. {e}"
@@ -343,6 +429,8 @@ impl Worker {
 
     /// Tell our parent we're available to perform work.
     fn set_idle(&mut self) -> EgResult<()> {
+        stats::stats().worker_state(self.worker_id, false);
+
         if let Err(e) = self.notify_state(WorkerState::Idle) {
             Err(format!(
                 "{self} failed to notify parent of Idle state. Exiting. {e}"
@@ -360,16 +448,24 @@ impl Worker {
         // Always adopt the log trace of an inbound API call.
         Logger::set_log_trace(tmsg.osrf_xid());
 
+        // And its trace context, if any.
+        trace::set_remote_context(tmsg.traceparent());
+
         if self.session.is_none() || self.session().thread().ne(tmsg.thread()) {
             log::trace!("server: creating new server session for {}", tmsg.thread());
 
-            self.session = Some(ServerSession::new(
+            let mut session = ServerSession::new(
                 self.client.clone(),
                 &self.service,
                 tmsg.thread(),
                 0, // thread trace -- updated later as needed
                 BusAddress::from_str(tmsg.from())?,
-            ));
+            );
+
+            session.set_max_chunk_size(self.max_chunk_size);
+            session.set_max_bundle_size(self.max_bundle_size);
+
+            self.session = Some(session);
         }
 
         for msg in tmsg.body_mut().drain(..) {
@@ -450,7 +546,6 @@ impl Worker {
             _ => return self.reply_bad_request("Request sent without a MethoCall payload"),
         };
 
-        let param_count = method_call.params().len();
         let api_name = method_call.method();
 
         let log_params =
@@ -496,48 +591,50 @@ impl Worker {
         }
 
         let method_def = method_def.unwrap();
-        let pcount = method_def.param_count();
-
-        // Make sure the number of params sent by the caller matches the
-        // parameter count for the method.
-        if !ParamCount::matches(&pcount, param_count as u8) {
-            return self.reply_bad_request(&format!(
-                "Invalid param count sent: method={} sent={} needed={}",
-                api_name, param_count, &pcount,
-            ));
+
+        // Make sure the params sent by the caller match the param
+        // count and, at least superficially, the param types.
+        if let Err(e) = method_def.validate_params(method_call.params()) {
+            log::warn!("{self} rejecting call to {api_name}: {e}");
+            return self.reply_bad_request(&e);
         }
 
-        // Verify paramter types are correct, at least superficially.
-        // Do this after deserialization.
-        if let Some(param_defs) = method_def.params() {
-            for (idx, param_def) in param_defs.iter().enumerate() {
-                // There may be more param defs than parameters if
-                // some param are optional.
-                if let Some(param_val) = method_call.params().get(idx) {
-                    if idx >= pcount.minimum().into() && param_val.is_null() {
-                        // NULL placeholders for non-required parameters are
-                        // allowed.
-                        continue;
-                    }
-                    if !param_def.datatype.matches(param_val) {
-                        return self.reply_bad_request(&format!(
-                            "Invalid paramter type: wanted={} got={}",
-                            param_def.datatype,
-                            param_val.clone().dump()
-                        ));
-                    }
-                } else {
-                    // More defs than actual params. Verification complete.
-                    break;
+        if let Some(auth) = method_def.auth() {
+            match self.authorize(auth, method_call) {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(err) => {
+                    let msg = format!("{self} cannot authorize call to {api_name}: {err}");
+                    log::error!("{msg}");
+                    self.reply_server_error(&msg)?;
+                    Err(msg)?;
                 }
             }
         }
 
+        // Responses sent by the handler carry the method span as
+        // their trace context, as do any API calls it makes.
+        let mut span = trace::Span::enter(&format!("osrf.method {api_name}"), SpanKind::Server);
+        span.set_attr("osrf.service", &self.service);
+        span.set_attr("osrf.method", api_name);
+
         // Call the API
-        if let Err(err) = (method_def.handler())(appworker, self.session_mut(), &method_call) {
+        let started = time::Instant::now();
+        let result = (method_def.handler())(appworker, self.session_mut(), &method_call);
+
+        stats::stats().record_call(method_call.method(), started.elapsed(), result.is_err());
+
+        self.session_mut().clear_editor();
+
+        if let Err(err) = result {
+            span.set_error(&err.to_string());
+
             let msg = format!("{self} method {} failed with {err}", method_call.method());
             log::error!("{msg}");
             appworker.api_call_error(&method_call, err);
+
+            // Deliver any bundled responses ahead of the error.
+            self.session_mut().flush_responses()?;
             self.reply_server_error(&msg)?;
             Err(msg)?;
         }
@@ -549,6 +646,56 @@ impl Worker {
         }
     }
 
+    /// Verify the caller's authtoken and permissions for a method
+    /// with MethodAuth settings.
+    ///
+    /// On success, the authenticated Editor is handed to the method
+    /// handler via our session.  Otherwise, the failure event or a
+    /// bad request status is returned to the caller and false is
+    /// returned.
+    fn authorize(
+        &mut self,
+        auth: &method::MethodAuth,
+        method_call: &message::MethodCall,
+    ) -> EgResult<bool> {
+        let authtoken = method_call
+            .params()
+            .get(auth.authtoken_param)
+            .and_then(|p| p.as_str())
+            .unwrap_or("");
+
+        let mut editor = Editor::with_auth(&self.client, authtoken);
+
+        if !editor.checkauth()? {
+            self.session_mut().respond_complete(editor.event())?;
+            return Ok(false);
+        }
+
+        // A missing org param means the caller's workstation org,
+        // but a value we cannot use must not silently fall back to it.
+        let org_id = match auth.org_param.and_then(|idx| method_call.params().get(idx)) {
+            Some(p) if !p.is_null() => match p.as_int() {
+                Some(id) => id,
+                None => {
+                    self.reply_bad_request(&format!("Invalid org unit parameter: {}", p.dump()))?;
+                    return Ok(false);
+                }
+            },
+            _ => editor.perm_org(),
+        };
+
+        for perm in auth.perms {
+            if !editor.allowed_at(perm, org_id)? {
+                self.session_mut().respond_complete(editor.event())?;
+                return Ok(false);
+            }
+        }
+
+        self.session_mut().set_editor(editor);
+
+        Ok(true)
+    }
+
     fn reply_server_error(&mut self, text: &str) -> EgResult<()> {
         self.connected = false;
 
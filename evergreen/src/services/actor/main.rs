@@ -10,3 +10,27 @@ fn main() {
         log::info!("Server exited normally");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::methods::METHODS;
+    use evergreen::osrf::stubs;
+
+    #[test]
+    fn client_stubs_parse() {
+        let src =
+            stubs::generate_from_static("open-ils.rs-actor", "open-ils.rs-actor", METHODS).unwrap();
+
+        let file =
+            syn::parse_file(&src).unwrap_or_else(|e| panic!("Invalid stub source: {e}\n{src}"));
+
+        let stub_count = file
+            .items
+            .iter()
+            .filter(|item| matches!(item, syn::Item::Fn(_)))
+            .count();
+
+        assert_eq!(stub_count, METHODS.len());
+        assert!(src.contains(": i64,"));
+    }
+}
@@ -45,6 +45,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Whole barcode or a partial 'completable' barcode",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "user_has_work_perm_at.batch",
@@ -68,6 +69,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "User ID to check permissions for; defaults to the API requestor",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "ou_setting.ancestor_default.batch",
@@ -91,6 +93,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Authtoken.  Required for perm-protected settings",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "settings.retrieve",
@@ -114,6 +117,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "user.opac.vital_stats",
@@ -132,6 +136,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "User ID whose stats to load; defaults to requestor",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "user.penalties.update",
@@ -156,6 +161,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                     May be a list of strings (names) or numbers (IDs)",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "user.penalties.update_at_home",
@@ -180,6 +186,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                     May be a list of strings (names) or numbers (IDs)",
             },
         ],
+        auth: None,
     },
 ];
 
@@ -25,6 +25,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Object,
             desc: "Hash of Login Options and Values",
         }],
+        auth: None,
     },
     StaticMethodDef {
         name: "user.validate",
@@ -36,6 +37,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Object,
             desc: "Hash of Login Options and Values",
         }],
+        auth: None,
     },
 ];
 
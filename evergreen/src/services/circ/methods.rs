@@ -1,18 +1,14 @@
 use eg::common::circ;
 use eg::common::circulator::Circulator;
-use eg::editor::Editor;
 use eg::osrf::app::ApplicationWorker;
 use eg::osrf::message;
-use eg::osrf::method::{ParamCount, ParamDataType, StaticMethodDef, StaticParam};
+use eg::osrf::method::{MethodAuth, ParamCount, ParamDataType, StaticMethodDef, StaticParam};
 use eg::osrf::session::ServerSession;
 use eg::EgResult;
 use eg::EgValue;
 use evergreen as eg;
 use std::collections::HashMap;
 
-// Import our local app module
-use crate::app;
-
 /// List of method definitions we know at compile time.
 ///
 /// These will form the basis (and possibly all) of our published methods.
@@ -34,6 +30,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.", // TODO expand
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "checkin.override",
@@ -52,6 +53,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.", // TODO expand
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "checkout",
@@ -70,6 +76,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "checkout.override",
@@ -88,6 +99,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "checkout.inspect",
@@ -106,6 +122,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "renew",
@@ -124,6 +145,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "renew.override",
@@ -142,6 +168,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options including copy_barcode, etc.",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &[],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "renewal_chain.retrieve_by_circ.summary",
@@ -160,6 +191,11 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Circulation ID to lookup",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &["VIEW_CIRCULATIONS"],
+            org_param: None,
+        }),
     },
     StaticMethodDef {
         name: "prev_renewal_chain.retrieve_by_circ.summary",
@@ -178,17 +214,19 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Circulation ID to lookup",
             },
         ],
+        auth: Some(MethodAuth {
+            authtoken_param: 0,
+            perms: &["VIEW_CIRCULATIONS"],
+            org_param: None,
+        }),
     },
 ];
 
 pub fn checkout_renew_checkin(
-    worker: &mut Box<dyn ApplicationWorker>,
+    _worker: &mut Box<dyn ApplicationWorker>,
     session: &mut ServerSession,
     method: &message::MethodCall,
 ) -> EgResult<()> {
-    let worker = app::RsCircWorker::downcast(worker)?;
-    let authtoken = method.param(0).str()?;
-
     // Translate the object into a hashmap our circulator can use.
     let mut options: HashMap<String, EgValue> = HashMap::new();
     let op_params = method.param(1);
@@ -196,12 +234,8 @@ pub fn checkout_renew_checkin(
         options.insert(k.to_string(), v.clone());
     }
 
-    let mut editor = Editor::with_auth(worker.client(), authtoken);
-
-    // Auth check
-    if !editor.checkauth()? {
-        return session.respond(editor.event());
-    }
+    // Authtoken verified by the worker.
+    let mut editor = session.take_editor()?;
 
     let mut circulator = Circulator::new(&mut editor, options)?;
     circulator.is_inspect = method.method().contains(".inspect");
@@ -247,23 +281,14 @@ pub fn checkout_renew_checkin(
 }
 
 pub fn renewal_chain_summary(
-    worker: &mut Box<dyn ApplicationWorker>,
+    _worker: &mut Box<dyn ApplicationWorker>,
     session: &mut ServerSession,
     method: &message::MethodCall,
 ) -> EgResult<()> {
-    let worker = app::RsCircWorker::downcast(worker)?;
-    let authtoken = method.param(0).str()?;
     let circ_id = method.param(1).int()?;
 
-    let mut editor = Editor::with_auth(worker.client(), authtoken);
-
-    if !editor.checkauth()? {
-        return session.respond(editor.event());
-    }
-
-    if !editor.allowed("VIEW_CIRCULATIONS")? {
-        return session.respond(editor.event());
-    }
+    // Authtoken and permissions verified by the worker.
+    let mut editor = session.take_editor()?;
 
     let chain = circ::summarize_circ_chain(&mut editor, circ_id)?;
 
@@ -271,23 +296,14 @@ pub fn renewal_chain_summary(
 }
 
 pub fn prev_renewal_chain_summary(
-    worker: &mut Box<dyn ApplicationWorker>,
+    _worker: &mut Box<dyn ApplicationWorker>,
     session: &mut ServerSession,
     method: &message::MethodCall,
 ) -> EgResult<()> {
-    let worker = app::RsCircWorker::downcast(worker)?;
-    let authtoken = method.param(0).str()?;
     let circ_id = method.param(1).int()?;
 
-    let mut editor = Editor::with_auth(worker.client(), authtoken);
-
-    if !editor.checkauth()? {
-        return session.respond(editor.event());
-    }
-
-    if !editor.allowed("VIEW_CIRCULATIONS")? {
-        return session.respond(editor.event());
-    }
+    // Authtoken and permissions verified by the worker.
+    let mut editor = session.take_editor()?;
 
     let chain = circ::circ_chain(&mut editor, circ_id)?;
     let first_circ = &chain[0]; // circ_chain errors on not-found
@@ -23,6 +23,7 @@ pub static METHODS: &[StaticMethodDef] = &[StaticMethodDef {
         datatype: ParamDataType::Object,
         desc: "Targeting Options",
     }],
+    auth: None,
 }];
 
 pub fn target(
@@ -34,6 +34,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options Hash",
             },
         ],
+        auth: None,
     },
     StaticMethodDef {
         name: "biblio.record.catalog_summary.staff",
@@ -57,6 +58,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Options Hash",
             },
         ],
+        auth: None,
     },
 ];
 
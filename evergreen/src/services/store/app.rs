@@ -3,7 +3,7 @@ use eg::idl;
 use eg::osrf::app::{Application, ApplicationWorker, ApplicationWorkerFactory};
 use eg::osrf::conf;
 use eg::osrf::message;
-use eg::osrf::method::MethodDef;
+use eg::osrf::method::{MethodDef, ParamDataType};
 use eg::osrf::sclient::HostSettings;
 use eg::Client;
 use eg::EgError;
@@ -54,13 +54,13 @@ impl RsStoreApplication {
             }
         };
 
-        for fieldmapper in classes
+        for class in classes
             .filter(|c| !c.is_virtual())
             .filter(cfilter)
             .filter(|c| c.fieldmapper().is_some())
-            .map(|c| c.fieldmapper().unwrap())
-            .map(|fm| fm.replace("::", "."))
         {
+            let fieldmapper = class.fieldmapper().unwrap().replace("::", ".");
+
             for mtype in DIRECT_METHODS {
                 // Each direct method type has a stub method defined
                 // in our list of StaticMethodDef's.  Use the stub as the
@@ -78,6 +78,14 @@ impl RsStoreApplication {
                 log::trace!("Registering: {apiname}");
 
                 clone.set_name(&apiname);
+
+                // create and update require an object of this class.
+                if *mtype == "create" || *mtype == "update" {
+                    if let Some(param) = clone.params.as_mut().and_then(|p| p.first_mut()) {
+                        param.datatype = ParamDataType::IdlObject(class.classname());
+                    }
+                }
+
                 methods.push(clone);
             }
         }
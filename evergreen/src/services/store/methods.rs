@@ -23,6 +23,7 @@ pub static METHODS: &[StaticMethodDef] = &[
         param_count: ParamCount::Zero,
         handler: manage_xact,
         params: &[],
+        auth: None,
     },
     StaticMethodDef {
         name: "transaction.rollback",
@@ -30,6 +31,7 @@ pub static METHODS: &[StaticMethodDef] = &[
         param_count: ParamCount::Zero,
         handler: manage_xact,
         params: &[],
+        auth: None,
     },
     StaticMethodDef {
         name: "transaction.commit",
@@ -37,6 +39,7 @@ pub static METHODS: &[StaticMethodDef] = &[
         param_count: ParamCount::Zero,
         handler: manage_xact,
         params: &[],
+        auth: None,
     },
     // Stub method for *.create calls.  Not directly published.
     StaticMethodDef {
@@ -49,6 +52,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Object,
             desc: "Object to update",
         }],
+        auth: None,
     },
     // Stub method for *.retrieve calls. Not directly published.
     StaticMethodDef {
@@ -68,6 +72,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Flesh Fields Object",
             },
         ],
+        auth: None,
     },
     // Stub method for *.search calls. Not directly published.
     StaticMethodDef {
@@ -87,6 +92,7 @@ pub static METHODS: &[StaticMethodDef] = &[
                 desc: "Flesh Fields Object",
             },
         ],
+        auth: None,
     },
     // Stub method for *.update calls. Not directly published.
     StaticMethodDef {
@@ -99,6 +105,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Object,
             desc: "Object to update",
         }],
+        auth: None,
     },
     // Stub method for *.delete calls.  Not directly published.
     StaticMethodDef {
@@ -111,6 +118,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Scalar,
             desc: "Primary Key Value",
         }],
+        auth: None,
     },
     // Stub method for *.delete calls.  Not directly published.
     StaticMethodDef {
@@ -123,6 +131,7 @@ pub static METHODS: &[StaticMethodDef] = &[
             datatype: ParamDataType::Object,
             desc: "JSON Query Object/Hash",
         }],
+        auth: None,
     },
 ];
 
@@ -322,13 +331,17 @@ pub fn json_query(
         params.push(p);
     }
 
+    let mut span = eg::db::query_span(sql);
     let query_res = db.borrow_mut().client().query(sql, &params);
 
     if let Err(ref e) = query_res {
+        span.set_error(&e.to_string());
         log::error!("DB Error: {e} query={query} param={params:?}");
         Err(format!("DB query failed. See error logs"))?;
     }
 
+    span.end();
+
     for row in query_res.unwrap() {
         let mut obj = eg::hash! {};
 
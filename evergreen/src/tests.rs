@@ -1,7 +1,27 @@
+use crate::osrf::addr::BusAddress;
+use crate::osrf::breaker;
+use crate::osrf::bus::{Bus, BusBackend};
+use crate::osrf::cache::{Cache, MemoryCacheBackend};
+use crate::osrf::conf::{ConfigBuilder, MessageSecurity};
+use crate::osrf::membus::MemoryHub;
 use crate::osrf::message::Message;
+use crate::osrf::message::MessageStatus;
 use crate::osrf::message::Payload;
 use crate::osrf::message::TransportMessage;
+use crate::osrf::method::{MethodDef, Param, ParamCount, ParamDataType};
+use crate::osrf::metrics;
+use crate::osrf::session::{ServerSession, DEFAULT_REQUEST_TIMEOUT};
+use crate::osrf::signing;
+use crate::osrf::stats;
+use crate::osrf::stubs;
+use crate::osrf::trace;
+use crate::util;
+use crate::Client;
+use crate::EgValue;
 use json;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 const TRANSPORT_MSG_JSON: &str = r#"{
     "to":"my-to",
@@ -54,3 +74,651 @@ fn parse_opensrf_message() {
     let msg = msg_op.unwrap();
     assert_eq!(msg.ingress(), "opensrf");
 }
+
+fn memory_bus(hub: &Arc<MemoryHub>, username: &str) -> Bus {
+    let mut bus = Bus::with_backend(Box::new(hub.connect()), username, "localhost", "router");
+    bus.set_raw_data_mode(true);
+    bus
+}
+
+#[test]
+fn memory_bus_send_recv() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut sender = memory_bus(&hub, "sender");
+    let mut receiver = memory_bus(&hub, "receiver");
+
+    let json_value = json::parse(TRANSPORT_MSG_JSON).unwrap();
+    let tm = TransportMessage::from_json_value(json_value, true).unwrap();
+
+    let recipient = receiver.address().as_str().to_string();
+    sender.send_to(tm, &recipient).unwrap();
+
+    let tm = receiver.recv(0, None).unwrap().unwrap();
+    assert_eq!(tm.thread(), "my-thread");
+
+    // Queue is now empty
+    assert!(receiver.recv(0, None).unwrap().is_none());
+    assert!(receiver.recv(1, None).unwrap().is_none());
+}
+
+#[test]
+fn memory_bus_blocking_recv() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut receiver = memory_bus(&hub, "receiver");
+    let recipient = receiver.address().as_str().to_string();
+
+    let sender_hub = hub.clone();
+    let handle = thread::spawn(move || {
+        let mut sender = memory_bus(&sender_hub, "sender");
+        let json_value = json::parse(TRANSPORT_MSG_JSON).unwrap();
+        let tm = TransportMessage::from_json_value(json_value, true).unwrap();
+        sender.send_to(tm, &recipient).unwrap();
+    });
+
+    let tm = receiver.recv(-1, None).unwrap().unwrap();
+    assert_eq!(tm.thread(), "my-thread");
+
+    handle.join().unwrap();
+}
+
+#[test]
+fn memory_bus_keys() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut con = hub.connect();
+
+    con.push("opensrf:service:a", "1".to_string()).unwrap();
+    con.push("opensrf:service:a", "2".to_string()).unwrap();
+    con.push("opensrf:service:a", "3".to_string()).unwrap();
+    con.push("opensrf:client:b", "1".to_string()).unwrap();
+
+    assert_eq!(
+        con.keys("opensrf:service:*").unwrap(),
+        vec!["opensrf:service:a"]
+    );
+    assert_eq!(con.keys("opensrf:*:?").unwrap().len(), 2);
+    assert_eq!(con.llen("opensrf:service:a").unwrap(), 3);
+    assert_eq!(
+        con.lrange("opensrf:service:a", 1, -1).unwrap(),
+        vec!["2", "3"]
+    );
+
+    assert_eq!(con.ttl("opensrf:service:a").unwrap(), -1);
+    assert_eq!(con.ttl("opensrf:service:x").unwrap(), -2);
+    assert_eq!(con.expire("opensrf:service:a", 60).unwrap(), 1);
+    assert!(con.ttl("opensrf:service:a").unwrap() > 0);
+
+    assert_eq!(con.del("opensrf:client:b").unwrap(), 1);
+    assert_eq!(con.llen("opensrf:client:b").unwrap(), 0);
+}
+
+#[test]
+fn memory_cache_lru() {
+    let backend = MemoryCacheBackend::new(2);
+    Cache::init_cache_with("test-lru", Box::new(backend), 60, 1024, 0);
+
+    Cache::set("test-lru", "a", EgValue::from("A"), 0).unwrap();
+    Cache::set("test-lru", "b", EgValue::from("B"), 0).unwrap();
+
+    // Touch "a" so "b" becomes the least recently used.
+    assert_eq!(
+        Cache::get("test-lru", "a").unwrap().unwrap().as_str(),
+        Some("A")
+    );
+
+    Cache::set("test-lru", "c", EgValue::from("C"), 0).unwrap();
+
+    assert!(Cache::get("test-lru", "b").unwrap().is_none());
+    assert!(Cache::get("test-lru", "a").unwrap().is_some());
+    assert!(Cache::get("test-lru", "c").unwrap().is_some());
+
+    Cache::del_from("test-lru", "a").unwrap();
+    assert!(Cache::get("test-lru", "a").unwrap().is_none());
+
+    // Exceeds max_cache_size
+    let big = "x".repeat(2048);
+    assert!(Cache::set("test-lru", "big", EgValue::from(big), 0).is_err());
+}
+
+#[test]
+fn memory_cache_compression() {
+    let backend = MemoryCacheBackend::new(10);
+    Cache::init_cache_with("test-compress", Box::new(backend), 60, 4096, 1024);
+
+    // Compresses to well under the max cache size.
+    let big = "x".repeat(8192);
+    Cache::set("test-compress", "big", EgValue::from(big.as_str()), 0).unwrap();
+
+    let value = Cache::get("test-compress", "big").unwrap().unwrap();
+    assert_eq!(value.as_str(), Some(big.as_str()));
+
+    Cache::set("test-compress", "small", EgValue::from("small"), 0).unwrap();
+    assert!(Cache::get("test-compress", "nope").unwrap().is_none());
+
+    // Random digits do not compress below the max cache size and
+    // are not counted as stored.
+    let noise: String = (0..1000).map(|_| util::random_number(10)).collect();
+    assert!(Cache::set("test-compress", "noise", EgValue::from(noise), 0).is_err());
+
+    // Counters are shared by every thread using the cache.
+    std::thread::spawn(|| {
+        let backend = MemoryCacheBackend::new(10);
+        Cache::init_cache_with("test-compress", Box::new(backend), 60, 4096, 1024);
+        assert!(Cache::get("test-compress", "other").unwrap().is_none());
+    })
+    .join()
+    .unwrap();
+
+    let stats = Cache::stats("test-compress");
+    assert_eq!(stats.sets, 2);
+    assert_eq!(stats.compressed_sets, 1);
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.misses, 2);
+    assert!(stats.compressed_bytes < stats.uncompressed_bytes);
+
+    let text = metrics::render();
+    assert!(text.contains("osrf_cache_requests_total{cache=\"test-compress\",result=\"miss\"} 2\n"));
+    assert!(text.contains("osrf_cache_sets_total{cache=\"test-compress\",compressed=\"true\"} 1\n"));
+}
+
+#[test]
+fn service_stats() {
+    let mut stats = stats::stats();
+
+    stats.record_call("test.stats.method", Duration::from_millis(5), false);
+    stats.record_call("test.stats.method", Duration::from_millis(300), true);
+
+    let m = stats.methods().get("test.stats.method").unwrap();
+    assert_eq!(m.calls, 2);
+    assert_eq!(m.errors, 1);
+    assert_eq!(m.max_time, Duration::from_millis(300));
+    assert_eq!(m.histogram[0], 1); // <= 10ms
+    assert_eq!(m.histogram[4], 1); // <= 500ms
+
+    stats.worker_state(9999, true);
+    stats.worker_request(9999);
+    stats.worker_state(9999, false);
+    assert_eq!(stats.workers().get(&9999).unwrap().requests, 1);
+
+    let requests = stats.total_requests();
+    stats.worker_exit(9999);
+    assert!(stats.workers().get(&9999).is_none());
+    assert_eq!(stats.total_requests(), requests);
+
+    let value = stats.to_eg_value();
+    assert_eq!(
+        value["methods"]["test.stats.method"]["calls"].as_int(),
+        Some(2)
+    );
+}
+
+#[test]
+fn metrics_render() {
+    metrics::inc_counter("test_requests", "Test requests", &[("service", "a\"b")], 2);
+    metrics::inc_counter("test_requests", "Test requests", &[("service", "a\"b")], 1);
+    metrics::set_gauge("test_queue_depth", "Test queue depth", &[], 7.0);
+    metrics::set_gauge(
+        "test_queue_depth",
+        "Test queue depth",
+        &[("service", "x")],
+        2.0,
+    );
+    metrics::remove("test_queue_depth", &[("service", "x")]);
+
+    let text = metrics::render();
+
+    assert!(text.contains("# TYPE test_requests counter\n"));
+    assert!(text.contains("test_requests_total{service=\"a\\\"b\"} 3\n"));
+    assert!(text.contains("test_queue_depth 7\n"));
+    assert!(!text.contains("test_queue_depth{service=\"x\"}"));
+    assert!(text.ends_with("# EOF\n"));
+}
+
+#[test]
+fn metrics_service_label() {
+    assert_eq!(
+        metrics::service_label("test.label"),
+        metrics::UNKNOWN_SERVICE
+    );
+
+    metrics::add_known_service("test.label");
+
+    assert_eq!(metrics::service_label("test.label"), "test.label");
+    assert_eq!(
+        metrics::service_label("test.bogus"),
+        metrics::UNKNOWN_SERVICE
+    );
+}
+
+/// Server session whose responses are delivered to a "caller" bus.
+fn server_session(hub: &Arc<MemoryHub>, caller: &Bus) -> ServerSession {
+    let client = Client::from_bus(memory_bus(hub, "server"));
+    ServerSession::new(client, "test", "thread", 1, caller.address().clone())
+}
+
+fn result_status(msg: &Message) -> Option<MessageStatus> {
+    match msg.payload() {
+        Payload::Result(r) => Some(*r.status()),
+        Payload::Status(s) => Some(*s.status()),
+        _ => None,
+    }
+}
+
+const TEST_CONFIG_XML: &str = r#"
+<config>
+  <opensrf>
+    <domain>localhost</domain>
+    <username>opensrf</username>
+    <passwd>password</passwd>
+  </opensrf>
+</config>
+"#;
+
+/// Client sessions read their router and policies from the global config.
+fn store_test_config() {
+    static STORED: std::sync::Once = std::sync::Once::new();
+    STORED.call_once(|| {
+        ConfigBuilder::from_xml_string(TEST_CONFIG_XML)
+            .unwrap()
+            .build()
+            .unwrap()
+            .store()
+            .unwrap();
+    });
+}
+
+#[test]
+fn client_server_session_round_trip() {
+    store_test_config();
+
+    let hub = Arc::new(MemoryHub::new());
+    let client = Client::from_bus(memory_bus(&hub, "client"));
+
+    let mut ses = client.session("test.echo");
+    let mut req = ses
+        .request("test.echo.say", vec!["Hello", "World"])
+        .unwrap();
+
+    // Play the part of the router and the service worker.
+    let mut router = memory_bus(&hub, "router");
+    let router_addr = BusAddress::for_router("router", "localhost");
+    let tm = router.recv(0, Some(router_addr.as_str())).unwrap().unwrap();
+
+    let msg = &tm.body()[0];
+    let Payload::Method(method) = msg.payload() else {
+        panic!("Request is not a method call");
+    };
+
+    assert_eq!(method.method(), "test.echo.say");
+
+    let mut session = ServerSession::new(
+        Client::from_bus(memory_bus(&hub, "server")),
+        "test.echo",
+        tm.thread(),
+        msg.thread_trace(),
+        BusAddress::from_str(tm.from()).unwrap(),
+    );
+
+    for param in method.params() {
+        session.respond(param.clone()).unwrap();
+    }
+    session.send_complete().unwrap();
+
+    assert_eq!(req.recv().unwrap().unwrap().as_str(), Some("Hello"));
+    assert_eq!(req.recv().unwrap().unwrap().as_str(), Some("World"));
+    assert!(req.recv().unwrap().is_none());
+    assert!(req.complete());
+}
+
+#[test]
+fn server_session_chunking() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut caller = memory_bus(&hub, "caller");
+    let mut session = server_session(&hub, &caller);
+
+    session.set_max_chunk_size(10);
+
+    let value = EgValue::from("A response well beyond ten bytes");
+    session.respond_complete(value.clone()).unwrap();
+
+    let mut buf = String::new();
+    let mut statuses = Vec::new();
+
+    while let Some(tm) = caller.recv(0, None).unwrap() {
+        for msg in tm.body() {
+            statuses.push(result_status(msg).unwrap());
+            if let Payload::Result(r) = msg.payload() {
+                assert!(r.content().as_str().unwrap().len() <= 10);
+                buf += r.content().as_str().unwrap();
+            }
+        }
+    }
+
+    assert_eq!(buf, value.dump());
+    assert_eq!(statuses[0], MessageStatus::Partial);
+    assert_eq!(statuses[statuses.len() - 2], MessageStatus::PartialComplete);
+    assert_eq!(statuses[statuses.len() - 1], MessageStatus::Complete);
+}
+
+#[test]
+fn server_session_bundling() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut caller = memory_bus(&hub, "caller");
+    let mut session = server_session(&hub, &caller);
+
+    session.set_max_bundle_size(1000);
+
+    session.respond("one").unwrap();
+    session.respond("two").unwrap();
+    assert!(caller.recv(0, None).unwrap().is_none());
+
+    session.respond_complete("three").unwrap();
+
+    let tm = caller.recv(0, None).unwrap().unwrap();
+    assert_eq!(tm.body().len(), 4);
+    assert!(caller.recv(0, None).unwrap().is_none());
+}
+
+#[test]
+fn trace_context_propagation() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut sender = memory_bus(&hub, "sender");
+    let mut receiver = memory_bus(&hub, "receiver");
+
+    let caller = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+    trace::set_remote_context(Some(caller));
+
+    let span = trace::Span::enter("test", trace::SpanKind::Server);
+    let ctx = span.context().unwrap().clone();
+
+    assert_eq!(ctx.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+    assert_eq!(span.parent_span_id(), Some("b7ad6b7169203331"));
+    assert_ne!(ctx.span_id(), "b7ad6b7169203331");
+
+    // Messages sent within the span carry the span as their parent.
+    let json_value = json::parse(TRANSPORT_MSG_JSON).unwrap();
+    let tm = TransportMessage::from_json_value(json_value, true).unwrap();
+    let recipient = receiver.address().as_str().to_string();
+    sender.send_to(tm, &recipient).unwrap();
+
+    let tm = receiver.recv(0, None).unwrap().unwrap();
+    assert_eq!(tm.traceparent(), Some(ctx.to_traceparent().as_str()));
+
+    // Ending the span restores the caller's context.
+    span.end();
+    assert_eq!(trace::current_traceparent().as_deref(), Some(caller));
+
+    trace::set_remote_context(None);
+    assert!(trace::current_traceparent().is_none());
+}
+
+#[test]
+fn client_stub_generation() {
+    let mut method = MethodDef::new(
+        "open-ils.test.thing.retrieve",
+        ParamCount::Range(1, 2),
+        |_, _, _| Ok(()),
+    );
+    method.set_desc("Retrieve a thing");
+
+    method.add_param(Param {
+        name: "authtoken".to_string(),
+        datatype: ParamDataType::String,
+        desc: Some("Authentication token".to_string()),
+    });
+
+    method.add_param(Param {
+        name: "type".to_string(),
+        datatype: ParamDataType::Number,
+        desc: None,
+    });
+
+    let echo = MethodDef::new("opensrf.system.echo", ParamCount::Any, |_, _, _| Ok(()));
+
+    let src =
+        stubs::generate("open-ils.test", &[method.to_eg_value(), echo.to_eg_value()]).unwrap();
+
+    assert!(src.contains("pub const SERVICE: &str = \"open-ils.test\";"));
+    assert!(src.contains("pub fn thing_retrieve("));
+    assert!(src.contains("    authtoken: &str,\n    type_: Option<i64>,\n"));
+    assert!(
+        src.contains("client.send_recv_iter(SERVICE, \"open-ils.test.thing.retrieve\", params)")
+    );
+
+    // Generic system methods are not included.
+    assert!(!src.contains("opensrf.system.echo"));
+}
+
+const CLIENT_POLICY_XML: &str = r#"
+<config>
+  <opensrf>
+    <domain>localhost</domain>
+    <username>opensrf</username>
+    <passwd>password</passwd>
+    <client_policies>
+      <policy service="open-ils.sip2">
+        <timeout>30</timeout>
+      </policy>
+      <policy>
+        <retries>2</retries>
+        <idempotent>open-ils.search.*</idempotent>
+        <breaker_threshold>2</breaker_threshold>
+      </policy>
+    </client_policies>
+  </opensrf>
+</config>
+"#;
+
+#[test]
+fn client_policies() {
+    let config = ConfigBuilder::from_xml_string(CLIENT_POLICY_XML)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let policy = config.client().client_policy("open-ils.actor");
+    assert_eq!(policy.timeout(), DEFAULT_REQUEST_TIMEOUT);
+    assert_eq!(policy.retries(), 2);
+
+    // Service policies inherit unset values from the default policy.
+    let policy = config.client().client_policy("open-ils.sip2");
+    assert_eq!(policy.timeout(), 30);
+    assert_eq!(policy.retries(), 2);
+    assert!(policy.is_idempotent("open-ils.search.biblio.multiclass"));
+
+    let service = "test.client-policies";
+    breaker::check(service, policy).unwrap();
+
+    breaker::record_timeout(service, policy);
+    breaker::check(service, policy).unwrap();
+
+    // Second consecutive timeout opens the circuit.
+    breaker::record_timeout(service, policy);
+    assert!(breaker::check(service, policy).is_err());
+
+    breaker::record_success(service);
+    breaker::check(service, policy).unwrap();
+
+    // Once the reset window passes, one trial request is let through
+    // and others are rejected until the trial completes.
+    let mut policy = policy.clone();
+    policy.set_breaker_reset(0);
+
+    breaker::record_timeout(service, &policy);
+    breaker::record_timeout(service, &policy);
+    breaker::check(service, &policy).unwrap();
+    assert!(breaker::check(service, &policy).is_err());
+
+    // A timed out trial re-opens the circuit.
+    breaker::record_timeout(service, &policy);
+    breaker::check(service, &policy).unwrap();
+
+    breaker::record_success(service);
+    breaker::check(service, &policy).unwrap();
+    breaker::check(service, &policy).unwrap();
+}
+
+const ROUTER_XML: &str = r#"
+<config>
+  <opensrf>
+    <domain>private.localhost</domain>
+    <username>opensrf</username>
+    <passwd>password</passwd>
+  </opensrf>
+  <routers>
+    <router>
+      <trusted_domains>
+        <server>private.localhost</server>
+        <client>private.localhost</client>
+        <client>public.localhost</client>
+      </trusted_domains>
+      <transport>
+        <domain>public.localhost</domain>
+        <username>router</username>
+        <password>password</password>
+      </transport>
+    </router>
+    <router>
+      <trusted_domains>
+        <server>private.localhost</server>
+        <client>private.localhost</client>
+        <admin>admin.localhost</admin>
+      </trusted_domains>
+      <transport>
+        <domain>private.localhost</domain>
+        <username>router</username>
+        <password>password</password>
+      </transport>
+    </router>
+  </routers>
+</config>
+"#;
+
+#[test]
+fn router_admin_domains() {
+    let config = ConfigBuilder::from_xml_string(ROUTER_XML)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    // Public clients may route requests, but not administer the router.
+    let public = config.get_router_conf("public.localhost").unwrap();
+    assert!(public
+        .trusted_client_domains()
+        .contains(&"public.localhost".to_string()));
+    assert!(!public.is_admin_domain("public.localhost"));
+    assert!(public.is_admin_domain("private.localhost"));
+
+    // Explicit admin domains replace the trusted server domains.
+    let private = config.get_router_conf("private.localhost").unwrap();
+    assert!(!private.is_admin_domain("private.localhost"));
+    assert!(private.is_admin_domain("admin.localhost"));
+}
+
+#[test]
+fn cancelled_thread_replies() {
+    let hub = Arc::new(MemoryHub::new());
+    let mut sender = memory_bus(&hub, "sender");
+    let client = Client::from_bus(memory_bus(&hub, "client"));
+    let recipient = client.address().as_str().to_string();
+
+    client.singleton().borrow_mut().cancel_thread("cancelled");
+
+    let tm = TransportMessage::new(&recipient, "sender", "cancelled");
+    sender.send_to(tm, &recipient).unwrap();
+
+    // Replies to cancelled threads are dropped on arrival.
+    assert!(!client.wait(1).unwrap());
+
+    let tm = TransportMessage::new(&recipient, "sender", "active");
+    sender.send_to(tm, &recipient).unwrap();
+
+    // Replies for other threads do not satisfy a thread-specific wait.
+    let found = client
+        .singleton()
+        .borrow_mut()
+        .wait_for_threads(&["cancelled"], 1)
+        .unwrap();
+
+    assert!(!found);
+    assert!(client.wait(0).unwrap());
+}
+
+#[test]
+fn bus_message_signing() {
+    let hub = Arc::new(MemoryHub::new());
+
+    let domain_bus = |domain: &str, security: &MessageSecurity| {
+        let mut bus = Bus::with_backend(Box::new(hub.connect()), "opensrf", domain, "router");
+        bus.set_raw_data_mode(true);
+        bus.set_message_security(Some(security.clone()));
+        bus
+    };
+
+    let mut private = MessageSecurity::default();
+    private.set_key("private.localhost", "private-secret");
+    private.set_key("public.localhost", "public-secret");
+    private.set_encrypt(true);
+
+    let mut public = MessageSecurity::default();
+    public.set_key("public.localhost", "public-secret");
+
+    let mut receiver = domain_bus("private.localhost", &private);
+    let recipient = receiver.address().as_str().to_string();
+
+    let mut send = |sender: &mut Bus| {
+        let json_value = json::parse(TRANSPORT_MSG_JSON).unwrap();
+        let mut tm = TransportMessage::from_json_value(json_value, true).unwrap();
+        tm.set_from(sender.address().as_str());
+        sender.send_to(tm, &recipient).unwrap();
+        receiver.recv(0, None).unwrap()
+    };
+
+    // Encrypted and signed within the private domain.
+    let mut sender = domain_bus("private.localhost", &private);
+    let tm = send(&mut sender).unwrap();
+    if let Payload::Method(method) = tm.body()[0].payload() {
+        assert_eq!(method.params()[1].as_str().unwrap(), "World");
+    } else {
+        panic!("Signed message body did not survive the trip");
+    }
+
+    // Signed with the public key.
+    let mut sender = domain_bus("public.localhost", &public);
+    assert!(send(&mut sender).is_some());
+
+    // A public client posing as a private domain client.
+    let mut sender = domain_bus("public.localhost", &public);
+    sender.set_address(&BusAddress::for_client("opensrf", "private.localhost"));
+    assert!(send(&mut sender).is_none());
+
+    // Or guessing at the private key.
+    let mut forged = MessageSecurity::default();
+    forged.set_key("private.localhost", "guess");
+    let mut sender = domain_bus("private.localhost", &forged);
+    assert!(send(&mut sender).is_none());
+
+    // Tampering with a signed message invalidates it.
+    let mut json_value = json::parse(TRANSPORT_MSG_JSON).unwrap();
+    json_value["from"] = json::from(sender.address().as_str());
+    signing::seal(&mut json_value, &public).unwrap(); // no private key
+    assert!(json_value["signature"].is_null());
+
+    signing::seal(&mut json_value, &private).unwrap();
+    assert!(json_value["body"].is_null());
+
+    let mut tampered = json_value.clone();
+    tampered["thread"] = json::from("other-thread");
+    assert!(signing::open(&mut tampered, &private).is_err());
+
+    // Including redirecting it or turning it into a router command.
+    let mut tampered = json_value.clone();
+    tampered["to"] = json::from("opensrf:service:opensrf.settings");
+    assert!(signing::open(&mut tampered, &private).is_err());
+
+    let mut tampered = json_value.clone();
+    tampered["router_command"] = json::from("unregister");
+    assert!(signing::open(&mut tampered, &private).is_err());
+
+    signing::open(&mut json_value, &private).unwrap();
+    assert_eq!(json_value["body"][0]["__p"]["threadTrace"].as_u8(), Some(1));
+}
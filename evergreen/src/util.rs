@@ -5,6 +5,7 @@ use rand::Rng;
 use socket2::{Domain, Socket, Type};
 use std::collections::HashSet;
 use std::fs;
+use std::io::Read;
 use std::net::{SocketAddr, TcpListener};
 use std::path::Path;
 use std::thread;
@@ -344,3 +345,134 @@ pub fn json_int(v: &EgValue) -> EgResult<i64> {
 pub fn json_bool(v: &EgValue) -> bool {
     v.boolish()
 }
+
+/// Size of each chunk read from an HTTP stream.
+const HTTP_READ_BUFSIZE: usize = 1024;
+
+/// Just the parts of an HTTP request we need.
+pub struct HttpRequest {
+    pub path: String,
+    pub method: String,
+    /// Only POST requests will have an HTTP body
+    pub body: Option<String>,
+}
+
+/// Read an HTTP request from a stream, returning the parts we care about.
+///
+/// Reads until the headers and Content-Length bytes of body
+/// data have been received.
+pub fn read_http_request(stream: &mut impl Read) -> EgResult<HttpRequest> {
+    let mut header_byte_count = 0;
+    let mut parsed_req = None;
+    let mut content_length = 0;
+    let mut chars: Vec<u8> = Vec::new();
+
+    loop {
+        // Pull a chunk of bytes from the stream and see what we can
+        // do with it.
+        let mut buffer = [0u8; HTTP_READ_BUFSIZE];
+
+        let num_bytes = stream
+            .read(&mut buffer)
+            .or_else(|e| Err(format!("Error reading HTTP stream: {e}")))?;
+
+        log::trace!("Read {num_bytes} from the TCP stream");
+
+        if num_bytes == 0 {
+            return Err(format!("HTTP stream closed before request was complete").into());
+        }
+
+        for c in buffer.iter() {
+            if *c == 0 {
+                // Drop any trailing '\0' chars.
+                break;
+            }
+            chars.push(*c);
+        }
+
+        if parsed_req.is_none() {
+            // Parse the headers and extract the values we care about.
+
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+            let mut req = httparse::Request::new(&mut headers);
+
+            log::trace!(
+                "Parsing chars: {}",
+                String::from_utf8_lossy(chars.as_slice())
+            );
+
+            let res = req
+                .parse(chars.as_slice())
+                .or_else(|e| Err(format!("Error readong HTTP headers: {e}")))?;
+
+            if res.is_partial() {
+                // We haven't read enough header data yet.
+                // Go back to pulling bytes from the socket.
+                continue;
+            }
+
+            // httparse::Result contains the byte count of the header
+            // once full parsed.
+            header_byte_count = res.unwrap();
+
+            for header in req.headers.iter() {
+                if header.name.to_lowercase().as_str() == "content-length" {
+                    let len = String::from_utf8_lossy(&header.value);
+                    if let Ok(size) = len.parse::<usize>() {
+                        content_length = size;
+                        break;
+                    }
+                }
+            }
+
+            let method = req
+                .method
+                .map(|v| v.to_string())
+                .ok_or(format!("Invalid HTTP request"))?;
+
+            let path = req
+                .path
+                .map(|v| v.to_string())
+                .ok_or(format!("Invalid HTTP request"))?;
+
+            parsed_req = Some(HttpRequest {
+                method,
+                path,
+                body: None,
+            });
+        }
+
+        if chars.len() == header_byte_count {
+            // We have read zero bytes of body data.
+            // There may be none to read.
+
+            if content_length == 0 {
+                return Ok(parsed_req.take().unwrap());
+            }
+
+            // We have a non-zero content-length.
+            // Keep reading data.
+            continue;
+        }
+
+        let body_bytes = &chars[header_byte_count..];
+        let body_byte_count = body_bytes.len();
+
+        log::trace!("Read {body_byte_count} body bytes, want {content_length}");
+
+        if body_byte_count == content_length {
+            // We've read all the body data.
+            let mut parsed_req = parsed_req.take().unwrap();
+
+            parsed_req.body = Some(String::from_utf8_lossy(body_bytes).to_string());
+
+            return Ok(parsed_req);
+        }
+
+        if body_byte_count > content_length {
+            return Err(format!("Content exceeds Content-Length header value").into());
+        }
+
+        // Keep reading data until body_byte_count >= content_length
+    }
+}
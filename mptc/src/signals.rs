@@ -7,6 +7,7 @@ use std::time::SystemTime;
 pub const SIG_FAST_SHUTDOWN: i32 = sigs::consts::SIGTERM;
 pub const SIG_GRACEFUL_SHUTDOWN: i32 = sigs::consts::SIGINT;
 pub const SIG_RELOAD: i32 = sigs::consts::SIGHUP;
+pub const SIG_DRAIN: i32 = sigs::consts::SIGUSR2;
 
 /// Tracks various signals so threaded, etc. applications can
 /// easily respond to received signals.
@@ -24,11 +25,13 @@ pub struct SignalTracker {
     fast_shutdown: Arc<AtomicBool>,
     reload: Arc<AtomicBool>,
     reload_request_time: Arc<AtomicU64>,
+    drain: Arc<AtomicBool>,
 
     /// Avoid duplicate signal handlers
     graceful_shutdown_tracked: bool,
     fast_shutdown_tracked: bool,
     reload_tracked: bool,
+    drain_tracked: bool,
 }
 
 impl SignalTracker {
@@ -38,9 +41,11 @@ impl SignalTracker {
             fast_shutdown: Arc::new(AtomicBool::new(false)),
             reload: Arc::new(AtomicBool::new(false)),
             reload_request_time: Arc::new(AtomicU64::new(0)),
+            drain: Arc::new(AtomicBool::new(false)),
             graceful_shutdown_tracked: false,
             fast_shutdown_tracked: false,
             reload_tracked: false,
+            drain_tracked: false,
         }
     }
 
@@ -59,6 +64,11 @@ impl SignalTracker {
         self.reload.store(true, Ordering::Relaxed);
     }
 
+    /// Directly initiate a drain request.
+    pub fn request_drain(&self) {
+        self.drain.store(true, Ordering::Relaxed);
+    }
+
     /// True if any shutdown signals have been received.
     pub fn any_shutdown_requested(&self) -> bool {
         self.graceful_shutdown_requested() || self.fast_shutdown_requested()
@@ -193,4 +203,40 @@ impl SignalTracker {
     pub fn reload_request_time(&self) -> u64 {
         self.reload_request_time.load(Ordering::Relaxed)
     }
+
+    /// Activate drain signal tracking.
+    ///
+    /// A drain asks a server to stop accepting new work, let in-flight
+    /// work finish, then exit.
+    ///
+    /// ```
+    /// use mptc::signals::SignalTracker;
+    /// use signal_hook::low_level::raise;
+    ///
+    /// let mut tracker = SignalTracker::new();
+    /// tracker.track_drain();
+    ///
+    /// raise(mptc::signals::SIG_DRAIN).expect("Signal Sent");
+    ///
+    /// assert!(tracker.drain_requested());
+    /// assert!(!tracker.any_shutdown_requested());
+    /// ```
+    pub fn track_drain(&mut self) {
+        if self.drain_tracked {
+            log::warn!("Already tracking drain signals");
+            return;
+        }
+
+        let result = sigs::flag::register(SIG_DRAIN, self.drain.clone());
+
+        if let Err(e) = result {
+            panic!("Cannot register drain handler: {}", e);
+        }
+
+        self.drain_tracked = true;
+    }
+
+    pub fn drain_requested(&self) -> bool {
+        self.drain.load(Ordering::Relaxed)
+    }
 }
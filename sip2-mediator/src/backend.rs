@@ -0,0 +1,172 @@
+use super::conf;
+use eg::osrf::session::{RequestOptions, DEFAULT_REQUEST_TIMEOUT};
+use eg::EgEvent;
+use eg::EgResult;
+use eg::EgValue;
+use evergreen as eg;
+use json::JsonValue;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// OpenSRF service and method which process SIP requests.
+const OSRF_SIP_SERVICE: &str = "open-ils.sip2";
+const OSRF_SIP_METHOD: &str = "open-ils.sip2.request";
+
+/// Relays SIP messages, encoded as JSON, to the ILS for processing.
+pub trait Backend {
+    /// Send a SIP request to the ILS on behalf of the session
+    /// identified by 'key' and wait up to 'timeout' seconds for
+    /// the response.
+    ///
+    /// If 'timeout' is None, the backend's default is used.
+    fn round_trip(
+        &mut self,
+        key: &str,
+        msg_json: JsonValue,
+        timeout: Option<i32>,
+    ) -> EgResult<JsonValue>;
+
+    /// Returns our OpenSRF bus connection, if we have one, so it may
+    /// be reused by another session.
+    fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus> {
+        None
+    }
+}
+
+/// Communicates with the ILS via OpenSRF API calls to open-ils.sip2.
+pub struct OsrfBackend {
+    client: eg::Client,
+}
+
+impl OsrfBackend {
+    pub fn new(bus: eg::osrf::bus::Bus) -> Self {
+        OsrfBackend {
+            client: eg::Client::from_bus(bus),
+        }
+    }
+}
+
+impl Backend for OsrfBackend {
+    fn round_trip(
+        &mut self,
+        key: &str,
+        msg_json: JsonValue,
+        timeout: Option<i32>,
+    ) -> EgResult<JsonValue> {
+        let msg_val = EgValue::from_json_value(msg_json)?;
+
+        let params = vec![EgValue::from(key), msg_val];
+
+        // Retries, circuit breaking, and the default timeout come
+        // from the client policy for the SIP service.
+        let options = RequestOptions {
+            timeout,
+            ..Default::default()
+        };
+
+        let mut ses = self.client.session(OSRF_SIP_SERVICE);
+        let mut req = ses.request_with_options(OSRF_SIP_METHOD, params, &options)?;
+
+        let timeout = req.timeout();
+
+        // Collect the first response, but keep reading until the
+        // request is complete so no replies are left on the bus.
+        let timer = eg::util::Timer::new(timeout);
+        let mut response = None;
+
+        while !req.complete() {
+            if timer.done() {
+                return Err(format!("Request timed out after {timeout} seconds").into());
+            }
+
+            if let Some(r) = req.recv_with_timeout(timer.remaining())? {
+                if response.is_none() {
+                    response = Some(r);
+                }
+            }
+        }
+
+        let response = response.ok_or_else(|| format!("No response received"))?;
+
+        if let Some(evt) = EgEvent::parse(&response) {
+            return Err(format!("SIP request failed with event: {evt}").into());
+        }
+
+        Ok(response.into_json_value())
+    }
+
+    fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus> {
+        Some(self.client.take_bus())
+    }
+}
+
+/// Communicates with the ILS via HTTP, posting requests to the
+/// Evergreen SIP2 Mediator HTTP handler.
+///
+/// Each request is a form-encoded POST containing the session key
+/// and the JSON-encoded SIP message.  The response body contains the
+/// JSON-encoded SIP response.
+pub struct HttpBackend {
+    agent: ureq::Agent,
+    url: String,
+}
+
+impl HttpBackend {
+    pub fn new(sip_config: &conf::Config) -> EgResult<Self> {
+        let url = match sip_config.http_url.as_deref() {
+            Some(u) => u.to_string(),
+            None => return Err(format!("HTTP backend requires an http-url").into()),
+        };
+
+        let mut builder = ureq::AgentBuilder::new();
+
+        if sip_config.ignore_ssl_errors {
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()
+                .map_err(|e| format!("Cannot create TLS connector: {e}"))?;
+
+            builder = builder.tls_connector(Arc::new(connector));
+        }
+
+        Ok(HttpBackend {
+            url,
+            agent: builder.build(),
+        })
+    }
+}
+
+impl Backend for HttpBackend {
+    fn round_trip(
+        &mut self,
+        key: &str,
+        msg_json: JsonValue,
+        timeout: Option<i32>,
+    ) -> EgResult<JsonValue> {
+        let message = msg_json.dump();
+        let timeout = timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT);
+
+        let response = self
+            .agent
+            .post(&self.url)
+            .timeout(Duration::from_secs(timeout.max(1) as u64))
+            .send_form(&[("session", key), ("message", &message)])
+            .map_err(|e| format!("HTTP request to {} failed: {e}", self.url))?;
+
+        let body = response
+            .into_string()
+            .map_err(|e| format!("Error reading HTTP response: {e}"))?;
+
+        let response = json::parse(&body)
+            .map_err(|e| format!("Invalid JSON in HTTP response: {e} [{body}]"))?;
+
+        let response = EgValue::from_json_value(response)?;
+
+        if let Some(evt) = EgEvent::parse(&response) {
+            return Err(format!("SIP request failed with event: {evt}").into());
+        }
+
+        Ok(response.into_json_value())
+    }
+}
@@ -1,3 +1,11 @@
+//! End-to-end SIP2 tester.
+//!
+//! Runs a series of SIP requests against a running SIP server using
+//! sample data created (and later removed) via the Evergreen API.
+//!
+//! The engine which processes the requests is determined by the SIP
+//! account used to log in.  When a second, native-engine account is
+//! provided, the tests run once per account so both engines are covered.
 use eg::samples::SampleData;
 use eg::EgValue;
 use evergreen as eg;
@@ -46,6 +54,13 @@ const HELP_TEXT: &str = r#"
     --sip-port
     --sip-user
     --sip-pass
+    --institution
+
+    --native-sip-user
+    --native-sip-pass
+        SIP account configured with "engine: native".  When set, the
+        tests are run a second time, logged in as this account.
+
     --help
 "#;
 
@@ -59,6 +74,8 @@ fn main() -> Result<(), String> {
     opts.optopt("", "sip-user", "", "");
     opts.optopt("", "sip-pass", "", "");
     opts.optopt("", "institution", "", "");
+    opts.optopt("", "native-sip-user", "", "");
+    opts.optopt("", "native-sip-pass", "", "");
 
     let params = match opts.parse(&args[1..]) {
         Ok(p) => p,
@@ -106,28 +123,52 @@ fn main() -> Result<(), String> {
             .unwrap(),
     };
 
+    run_suite(&mut tester)?;
+
+    tester.sipcon.disconnect().ok();
+
+    if let Some(user) = params.opt_str("native-sip-user") {
+        println!("Running tests with native engine account {user}");
+
+        let t = Timer::new();
+        tester.sipcon = sip2::Connection::new(&sip_host).expect("Error creating SIP connection");
+        t.done("SIP Connect");
+
+        tester.sip_user = user;
+        tester.sip_pass = params
+            .opt_get_default("native-sip-pass", "sip-pass".to_string())
+            .unwrap();
+
+        run_suite(&mut tester)?;
+
+        tester.sipcon.disconnect().ok();
+    }
+
+    Ok(())
+}
+
+/// Run all tests against freshly created test assets.
+fn run_suite(tester: &mut Tester) -> Result<(), String> {
     let t = Timer::new();
-    delete_test_assets(&mut tester)?;
+    delete_test_assets(tester)?;
     t.done("Pre-Delete Test Assets");
 
     let t = Timer::new();
-    create_test_assets(&mut tester)?;
+    create_test_assets(tester)?;
     t.done("Create Test Assets");
 
     println!("--------------------------------------");
 
-    if let Err(e) = run_tests(&mut tester) {
+    if let Err(e) = run_tests(tester) {
         eprintln!("Tester exited with error: {e}");
     };
 
     println!("--------------------------------------");
 
     let t = Timer::new();
-    delete_test_assets(&mut tester)?;
+    delete_test_assets(tester)?;
     t.done("Delete Test Assets");
 
-    tester.sipcon.disconnect().ok();
-
     Ok(())
 }
 
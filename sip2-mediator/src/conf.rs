@@ -1,8 +1,288 @@
 use eg::EgResult;
+use eg::EgValue;
 use evergreen as eg;
+use std::collections::HashMap;
 use std::fs;
+use yaml_rust::yaml;
+use yaml_rust::Yaml;
 use yaml_rust::YamlLoader;
 
+/// How SIP requests are relayed to the ILS.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendType {
+    /// OpenSRF API calls to open-ils.sip2.  Requires Redis/OpenSRF.
+    Opensrf,
+    /// HTTP POST requests to the Evergreen SIP2 Mediator HTTP handler.
+    Http,
+}
+
+impl TryFrom<&str> for BackendType {
+    type Error = String;
+    fn try_from(s: &str) -> Result<BackendType, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "opensrf" => Ok(Self::Opensrf),
+            "http" => Ok(Self::Http),
+            _ => Err(format!("Invalid SIP backend: {s}")),
+        }
+    }
+}
+
+/// How a SIP session processes requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineType {
+    /// Requests are handled here, using the ILS API and database.
+    Native,
+    /// Requests are relayed to the ILS via the configured backend.
+    Relay,
+}
+
+impl TryFrom<&str> for EngineType {
+    type Error = String;
+    fn try_from(s: &str) -> Result<EngineType, Self::Error> {
+        match s.to_lowercase().as_str() {
+            "native" => Ok(Self::Native),
+            "relay" => Ok(Self::Relay),
+            _ => Err(format!("Invalid SIP engine: {s}")),
+        }
+    }
+}
+
+// Shorthand for pulling a bool value from a yaml
+// node and applying it to a setting.
+fn set_bool(g: &yaml_rust::Yaml, k: &str, f: &mut bool) {
+    if let Some(v) = g[k].as_bool() {
+        *f = v;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Msg64HoldDatatype {
+    Barcode,
+    Title,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Msg64SummaryDatatype {
+    Barcode,
+    Title,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvFormat {
+    Legacy,
+    SwyerA,
+    SwyerB,
+    ThreeM,
+}
+
+impl From<&str> for AvFormat {
+    fn from(s: &str) -> AvFormat {
+        match s.to_lowercase().as_str() {
+            "eg_legacy" => Self::Legacy,
+            "swyer_a" => Self::SwyerA,
+            "swyer_b" => Self::SwyerB,
+            "3m" => Self::ThreeM,
+            _ => Self::Legacy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldFilter {
+    field_code: String,
+    replace_with: Option<String>,
+}
+
+impl FieldFilter {
+    pub fn field_code(&self) -> &str {
+        &self.field_code
+    }
+    pub fn replace_with(&self) -> Option<&str> {
+        self.replace_with.as_deref()
+    }
+}
+
+/// Named collection of SIP session settings.
+#[derive(Debug, Clone)]
+pub struct SipSettings {
+    institution: String,
+    due_date_use_sip_date_format: bool,
+    patron_status_permit_all: bool,
+    patron_status_permit_loans: bool,
+    msg64_hold_items_available: bool,
+    checkin_holds_as_transits: bool,
+    msg64_hold_datatype: Msg64HoldDatatype,
+    msg64_summary_datatype: Msg64SummaryDatatype,
+    av_format: AvFormat,
+    checkout_override_all: bool,
+    checkin_override_all: bool,
+    checkout_override: Vec<String>,
+    checkin_override: Vec<String>,
+    field_filters: Vec<FieldFilter>,
+    sc_status_library_info: bool,
+    use_native_checkin: bool,
+    use_native_checkout: bool,
+}
+
+impl SipSettings {
+    pub fn new(institution: &str) -> Self {
+        SipSettings {
+            institution: institution.to_string(),
+            due_date_use_sip_date_format: true,
+            patron_status_permit_all: false,
+            patron_status_permit_loans: false,
+            msg64_hold_items_available: false,
+            checkin_holds_as_transits: false,
+            msg64_hold_datatype: Msg64HoldDatatype::Barcode,
+            msg64_summary_datatype: Msg64SummaryDatatype::Barcode,
+            av_format: AvFormat::ThreeM,
+            checkout_override_all: false,
+            checkin_override_all: false,
+            sc_status_library_info: false,
+            checkout_override: Vec::new(),
+            checkin_override: Vec::new(),
+            field_filters: Vec::new(),
+            use_native_checkin: false,
+            use_native_checkout: false,
+        }
+    }
+    /// If true, uses the native Rust checkin API.
+    pub fn use_native_checkin(&self) -> bool {
+        self.use_native_checkin
+    }
+    /// If true, uses the native Rust checkout API.
+    pub fn use_native_checkout(&self) -> bool {
+        self.use_native_checkout
+    }
+    pub fn institution(&self) -> &str {
+        &self.institution
+    }
+    /// Use SIP date format instead of ISO8601 format
+    pub fn due_date_use_sip_date_format(&self) -> bool {
+        self.due_date_use_sip_date_format
+    }
+    /// If true patrons are only reported as blocked if the account
+    /// is expired.  Fines, overdues, etc. are ignored.
+    pub fn patron_status_permit_all(&self) -> bool {
+        self.patron_status_permit_all
+    }
+    /// Like patron_status_permit_all, but only relates to checkouts/renewals.
+    pub fn patron_status_permit_loans(&self) -> bool {
+        self.patron_status_permit_loans
+    }
+    /// Limit holds list to available holds
+    pub fn msg64_hold_items_available(&self) -> bool {
+        self.msg64_hold_items_available
+    }
+    /// Format items as barcodes or titles
+    pub fn msg64_summary_datatype(&self) -> &Msg64SummaryDatatype {
+        &self.msg64_summary_datatype
+    }
+    /// Format holds as item barcodes or titles
+    pub fn msg64_hold_datatype(&self) -> &Msg64HoldDatatype {
+        &self.msg64_hold_datatype
+    }
+    /// Format for fine items
+    pub fn av_format(&self) -> &AvFormat {
+        &self.av_format
+    }
+    pub fn checkin_holds_as_transits(&self) -> bool {
+        self.checkin_holds_as_transits
+    }
+    /// Attempt to override all checkout failure events
+    pub fn checkout_override_all(&self) -> bool {
+        self.checkout_override_all
+    }
+    /// Attempt to override all checkin failure events
+    pub fn checkin_override_all(&self) -> bool {
+        self.checkin_override_all
+    }
+    /// List of event codes we will try to override when necessary.
+    ///
+    /// This is superseded by checkout_override_all.
+    pub fn checkout_override(&self) -> &Vec<String> {
+        &self.checkout_override
+    }
+    /// List of event codes will will try to override when necessary.
+    ///
+    /// This is superseded by checkin_override_all.
+    pub fn checkin_override(&self) -> &Vec<String> {
+        &self.checkin_override
+    }
+    /// Filters to apply to outbound messages.
+    pub fn field_filters(&self) -> &Vec<FieldFilter> {
+        &self.field_filters
+    }
+    pub fn sc_status_library_info(&self) -> bool {
+        self.sc_status_library_info
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SipAccount {
+    settings: SipSettings,
+    sip_username: String,
+    /// None if the account was loaded from the database, in which
+    /// case the password is verified by the database.
+    sip_password: Option<String>,
+    ils_username: String,
+    ils_user_id: Option<i64>,
+    workstation: Option<String>,
+    activity_as: Option<String>,
+    checkin_block_on_checked_out: bool,
+}
+
+impl SipAccount {
+    pub fn new(
+        settings: &SipSettings,
+        sip_username: &str,
+        sip_password: &str,
+        ils_username: &str,
+    ) -> SipAccount {
+        SipAccount {
+            settings: settings.clone(),
+            sip_username: sip_username.to_string(),
+            sip_password: Some(sip_password.to_string()),
+            ils_username: ils_username.to_string(),
+            ils_user_id: None,
+            workstation: None,
+            activity_as: None,
+            checkin_block_on_checked_out: false,
+        }
+    }
+
+    pub fn settings(&self) -> &SipSettings {
+        &self.settings
+    }
+    pub fn sip_username(&self) -> &str {
+        &self.sip_username
+    }
+    /// Plain text password for accounts defined in the configuration
+    /// file.  None for accounts loaded from the database.
+    pub fn sip_password(&self) -> Option<&str> {
+        self.sip_password.as_deref()
+    }
+    pub fn ils_username(&self) -> &str {
+        &self.ils_username
+    }
+    pub fn ils_user_id(&self) -> Option<i64> {
+        self.ils_user_id
+    }
+    pub fn set_ils_user_id(&mut self, id: i64) {
+        self.ils_user_id = Some(id)
+    }
+    pub fn workstation(&self) -> Option<&str> {
+        self.workstation.as_deref()
+    }
+    pub fn activity_as(&self) -> Option<&str> {
+        self.activity_as.as_deref()
+    }
+    /// Prevent checkin of items that are currently checked out.
+    pub fn checkin_block_on_checked_out(&self) -> bool {
+        self.checkin_block_on_checked_out
+    }
+}
+
 /// SIP configuration
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -11,6 +291,68 @@ pub struct Config {
     pub max_clients: usize,
     pub min_workers: usize,
     pub ascii: bool,
+
+    pub backend: BackendType,
+
+    /// URL of the HTTP backend.  Required for the HTTP backend.
+    pub http_url: Option<String>,
+
+    /// Accept invalid/self-signed certificates from the HTTP backend.
+    pub ignore_ssl_errors: bool,
+
+    /// Syslog settings for the HTTP backend, which runs without
+    /// an OpenSRF configuration.
+    pub syslog_facility: Option<String>,
+    pub syslog_level: Option<String>,
+
+    /// How long to wait for the ILS to respond to a SIP request.
+    ///
+    /// If unset, the OpenSRF backend uses the client policy timeout
+    /// for the open-ils.sip2 service.
+    pub request_timeout: Option<i32>,
+
+    /// Per-message request timeouts, keyed on SIP message code.
+    ///
+    /// These override request_timeout.
+    pub message_timeouts: HashMap<String, i32>,
+
+    /// Answer SC Status requests locally using the most recent ACS
+    /// Status response from the ILS for up to this many seconds.
+    ///
+    /// Zero means every SC Status request is relayed to the ILS.
+    pub sc_status_cache_ttl: u64,
+
+    /// If true, reply with a well-formed "offline" / "not ok" response
+    /// when the ILS fails to respond in time, instead of disconnecting
+    /// the SIP client.
+    pub offline_responses: bool,
+
+    /// If set, each SIP session writes a transcript of its messages
+    /// as JSON lines to a file in this directory.
+    pub transcript_dir: Option<String>,
+
+    /// Engine used for SIP accounts which do not specify one, and
+    /// for requests which arrive before login.
+    pub default_engine: EngineType,
+
+    /// Engines selected by specific SIP accounts, keyed on username.
+    pub account_engines: HashMap<String, EngineType>,
+
+    /// Allow SC Status requests before login.  Native engine only.
+    pub sc_status_before_login: bool,
+
+    /// If true, native SIP accounts and setting groups are also
+    /// loaded from the Evergreen database.
+    pub database_accounts: bool,
+
+    /// Currency type reported by the native engine.
+    pub currency: String,
+
+    /// Native engine setting groups, keyed on name.
+    setting_groups: HashMap<String, SipSettings>,
+
+    /// Native engine SIP accounts, keyed on username.
+    accounts: HashMap<String, SipAccount>,
 }
 
 impl Config {
@@ -21,9 +363,58 @@ impl Config {
             max_clients: 64,
             min_workers: 1,
             ascii: true,
+            backend: BackendType::Opensrf,
+            http_url: None,
+            ignore_ssl_errors: false,
+            syslog_facility: None,
+            syslog_level: None,
+            request_timeout: None,
+            message_timeouts: HashMap::new(),
+            sc_status_cache_ttl: 0,
+            offline_responses: true,
+            transcript_dir: None,
+            default_engine: EngineType::Relay,
+            account_engines: HashMap::new(),
+            sc_status_before_login: false,
+            database_accounts: false,
+            currency: "USD".to_string(),
+            setting_groups: HashMap::new(),
+            accounts: HashMap::new(),
         }
     }
 
+    /// Request timeout for the provided SIP message code.
+    pub fn request_timeout_for(&self, code: &str) -> Option<i32> {
+        self.message_timeouts
+            .get(code)
+            .copied()
+            .or(self.request_timeout)
+    }
+
+    /// Engine used by the SIP account with the provided username.
+    pub fn engine_for(&self, username: &str) -> EngineType {
+        self.account_engines
+            .get(username)
+            .copied()
+            .unwrap_or(self.default_engine)
+    }
+
+    /// True if any part of our configuration requires a connection
+    /// to OpenSRF.
+    pub fn needs_opensrf(&self) -> bool {
+        self.backend == BackendType::Opensrf
+            || self.database_accounts
+            || self.default_engine == EngineType::Native
+            || self
+                .account_engines
+                .values()
+                .any(|e| e == &EngineType::Native)
+    }
+
+    pub fn get_account(&self, username: &str) -> Option<&SipAccount> {
+        self.accounts.get(username)
+    }
+
     /// Parse a YAML configuration file.
     pub fn from_yaml(filename: &str) -> EgResult<Self> {
         let mut conf = Config::new();
@@ -63,6 +454,411 @@ impl Config {
             conf.ascii = v;
         }
 
+        if let Some(v) = root["backend"].as_str() {
+            conf.backend = BackendType::try_from(v)?;
+        }
+
+        if let Some(v) = root["http-url"].as_str() {
+            conf.http_url = Some(v.to_string());
+        }
+
+        if let Some(v) = root["ignore-ssl-errors"].as_bool() {
+            conf.ignore_ssl_errors = v;
+        }
+
+        if let Some(v) = root["syslog-facility"].as_str() {
+            conf.syslog_facility = Some(v.to_string());
+        }
+
+        if let Some(v) = root["syslog-level"].as_str() {
+            conf.syslog_level = Some(v.to_string());
+        }
+
+        if let Some(v) = root["request-timeout"].as_i64() {
+            conf.request_timeout = Some(v as i32);
+        }
+
+        if let Some(hash) = root["message-timeouts"].as_hash() {
+            for (code, timeout) in hash {
+                // Unquoted codes like 11 are parsed as numbers.
+                let code = match code.as_str() {
+                    Some(c) => c.to_string(),
+                    None => match code.as_i64() {
+                        Some(c) => format!("{c:02}"),
+                        None => continue,
+                    },
+                };
+
+                if let Some(t) = timeout.as_i64() {
+                    conf.message_timeouts.insert(code, t as i32);
+                }
+            }
+        }
+
+        if let Some(v) = root["sc-status-cache-ttl"].as_i64() {
+            conf.sc_status_cache_ttl = v as u64;
+        }
+
+        if let Some(v) = root["offline-responses"].as_bool() {
+            conf.offline_responses = v;
+        }
+
+        if let Some(v) = root["transcript-dir"].as_str() {
+            conf.transcript_dir = Some(v.to_string());
+        }
+
+        if let Some(v) = root["default-engine"].as_str() {
+            conf.default_engine = EngineType::try_from(v)?;
+        }
+
+        if let Some(v) = root["sc-status-before-login"].as_bool() {
+            conf.sc_status_before_login = v;
+        }
+
+        if let Some(v) = root["database-accounts"].as_bool() {
+            conf.database_accounts = v;
+        }
+
+        if let Some(v) = root["currency"].as_str() {
+            conf.currency = v.to_string();
+        }
+
+        conf.add_setting_groups(root);
+        conf.add_accounts(root)?;
+
         Ok(conf)
     }
+
+    fn add_setting_groups(&mut self, root: &yaml_rust::Yaml) {
+        if !root["setting-groups"].is_array() {
+            return;
+        }
+
+        for group in root["setting-groups"].as_vec().unwrap() {
+            self.add_setting_group(group);
+        }
+    }
+
+    /// Parse a single setting group and add it to our collection,
+    /// replacing any existing group with the same name.
+    fn add_setting_group(&mut self, group: &yaml_rust::Yaml) {
+        let name = group["name"].as_str().expect("Setting group name required");
+
+        let inst = group["institution"]
+            .as_str()
+            .expect("Setting group institution required");
+
+        let mut grp = SipSettings::new(inst);
+
+        set_bool(
+            group,
+            "due-date-use-sip-date-format",
+            &mut grp.due_date_use_sip_date_format,
+        );
+        set_bool(
+            group,
+            "patron-status-permit-all",
+            &mut grp.patron_status_permit_all,
+        );
+        set_bool(
+            group,
+            "patron-status-permit-loans",
+            &mut grp.patron_status_permit_loans,
+        );
+        set_bool(
+            group,
+            "msg64-hold-items-available",
+            &mut grp.msg64_hold_items_available,
+        );
+        set_bool(
+            group,
+            "checkin-holds-as-transits",
+            &mut grp.checkin_holds_as_transits,
+        );
+        set_bool(
+            group,
+            "checkout-override-all",
+            &mut grp.checkout_override_all,
+        );
+        set_bool(group, "checkin-override-all", &mut grp.checkin_override_all);
+        set_bool(
+            group,
+            "sc-status-library-info",
+            &mut grp.sc_status_library_info,
+        );
+
+        set_bool(group, "use-native-checkin", &mut grp.use_native_checkin);
+        set_bool(group, "use-native-checkout", &mut grp.use_native_checkout);
+
+        if let Some(s) = group["msg64-hold-datatype"].as_str() {
+            if s.to_lowercase().starts_with("t") {
+                grp.msg64_hold_datatype = Msg64HoldDatatype::Title;
+            }
+        }
+        if let Some(s) = group["msg64-summary-datatype"].as_str() {
+            if s.to_lowercase().starts_with("t") {
+                grp.msg64_summary_datatype = Msg64SummaryDatatype::Title;
+            }
+        }
+        if let Some(s) = group["av-format"].as_str() {
+            grp.av_format = s.into();
+        }
+
+        if group["checkin-override"].is_array() {
+            for ovride in group["checkin-override"].as_vec().unwrap() {
+                if let Some(code) = ovride.as_str() {
+                    grp.checkin_override.push(code.to_string());
+                }
+            }
+        }
+
+        if group["checkout-override"].is_array() {
+            for ovride in group["checkout-override"].as_vec().unwrap() {
+                if let Some(code) = ovride.as_str() {
+                    grp.checkout_override.push(code.to_string());
+                }
+            }
+        }
+
+        if group["field-filters"].is_array() {
+            for filter in group["field-filters"].as_vec().unwrap() {
+                if let Some(field) = filter["field-code"].as_str() {
+                    let mut mfilter = FieldFilter {
+                        field_code: field.to_string(),
+                        replace_with: None,
+                    };
+
+                    if let Some(rw) = filter["replace-with"].as_str() {
+                        mfilter.replace_with = Some(rw.to_string());
+                    }
+
+                    grp.field_filters.push(mfilter);
+                }
+            }
+        }
+
+        log::debug!("Adding setting group '{name}'");
+        self.setting_groups.insert(name.to_string(), grp);
+    }
+
+    fn add_accounts(&mut self, root: &yaml_rust::Yaml) -> Result<(), String> {
+        if root["accounts"].is_array() {
+            for account in root["accounts"].as_vec().unwrap() {
+                let username = account["sip-username"].as_str().unwrap();
+
+                if let Some(engine) = account["engine"].as_str() {
+                    let engine = EngineType::try_from(engine)?;
+                    self.account_engines.insert(username.to_string(), engine);
+                }
+
+                if self.engine_for(username) == EngineType::Relay {
+                    // Relay accounts are managed by the ILS.  No
+                    // additional settings are needed here.
+                    continue;
+                }
+
+                let group_name = account["settings"].as_str().unwrap();
+                let sgroup = match self.setting_groups.get(group_name) {
+                    Some(s) => s,
+                    None => Err(format!("No such settings group: '{}'", group_name))?,
+                };
+
+                let mut acct = SipAccount::new(
+                    &sgroup,
+                    account["sip-username"].as_str().unwrap(),
+                    account["sip-password"].as_str().unwrap(),
+                    account["ils-username"].as_str().unwrap(),
+                );
+
+                if let Some(ws) = account["workstation"].as_str() {
+                    acct.workstation = Some(ws.to_string());
+                }
+                if let Some(ws) = account["activity-as"].as_str() {
+                    acct.activity_as = Some(ws.to_string());
+                }
+
+                set_bool(
+                    &account,
+                    "checkin-block-on-checked-out",
+                    &mut acct.checkin_block_on_checked_out,
+                );
+
+                self.accounts.insert(username.to_string(), acct);
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Load SIP setting groups and accounts from the Evergreen
+    /// database, i.e. the sip.setting_group, sip.setting, and
+    /// sip.account tables.
+    ///
+    /// Setting groups and accounts defined in the YAML configuration
+    /// take precedence over database entries with the same name.
+    pub fn add_database_accounts(&mut self, editor: &mut eg::Editor) -> EgResult<()> {
+        let query = eg::hash! {"id": {"!=": EgValue::Null}};
+
+        // Map setting group IDs to names for linking accounts.
+        let mut group_names: HashMap<i64, String> = HashMap::new();
+
+        for sgroup in editor.search("sipsetg", query)? {
+            let group_id = sgroup.id()?;
+            let label = sgroup["label"].string()?;
+
+            group_names.insert(group_id, label.clone());
+
+            if self.setting_groups.contains_key(&label) {
+                log::debug!(
+                    "Setting group '{label}' is defined in YAML; skipping database version"
+                );
+                continue;
+            }
+
+            let settings = editor.search("sipset", eg::hash! {"setting_group": group_id})?;
+
+            let yaml_group = Config::db_settings_to_yaml(&sgroup, &settings)?;
+
+            self.add_setting_group(&yaml_group);
+        }
+
+        let query = eg::hash! {"enabled": "t"};
+
+        let flesh = eg::hash! {
+            "flesh": 1,
+            "flesh_fields": {"sipacc": ["usr", "workstation"]}
+        };
+
+        for account in editor.search_with_ops("sipacc", query, flesh)? {
+            let username = account["sip_username"].string()?;
+
+            let in_yaml = self.accounts.contains_key(&username)
+                || self.account_engines.contains_key(&username);
+
+            if in_yaml {
+                log::debug!(
+                    "SIP account '{username}' is defined in YAML; skipping database version"
+                );
+                continue;
+            }
+
+            let group_id = account["setting_group"].int()?;
+
+            let sgroup = match group_names
+                .get(&group_id)
+                .and_then(|name| self.setting_groups.get(name))
+            {
+                Some(g) => g,
+                None => {
+                    log::error!("SIP account '{username}' has no valid setting group");
+                    continue;
+                }
+            };
+
+            let acct = SipAccount {
+                settings: sgroup.clone(),
+                sip_username: username.to_string(),
+                sip_password: None,
+                ils_username: account["usr"]["usrname"].string()?,
+                ils_user_id: Some(account["usr"].id()?),
+                workstation: account["workstation"]["name"].to_string(),
+                activity_as: account["activity_who"].to_string(),
+                checkin_block_on_checked_out: false,
+            };
+
+            log::debug!("Adding SIP account '{username}' from the database");
+
+            // Database accounts carry native engine settings, so they
+            // use the native engine regardless of the default.
+            self.account_engines
+                .insert(username.to_string(), EngineType::Native);
+
+            self.accounts.insert(username, acct);
+        }
+
+        Ok(())
+    }
+
+    /// Translate a sip.setting_group and its sip.setting values into
+    /// the YAML structure used by the configuration file so both
+    /// sources share a single parser.
+    ///
+    /// Setting names use underscores in the database and dashes in
+    /// the YAML file.  Per-event override settings take the form
+    /// "checkout.override.COPY_ALERT_MESSAGE".
+    fn db_settings_to_yaml(sgroup: &EgValue, settings: &[EgValue]) -> EgResult<Yaml> {
+        let mut hash = yaml::Hash::new();
+        let mut checkin_override = Vec::new();
+        let mut checkout_override = Vec::new();
+
+        hash.insert(
+            Yaml::String("name".to_string()),
+            Yaml::String(sgroup["label"].string()?),
+        );
+        hash.insert(
+            Yaml::String("institution".to_string()),
+            Yaml::String(sgroup["institution"].string()?),
+        );
+
+        for setting in settings {
+            let name = setting["name"].str()?;
+
+            // Setting values are stored as JSON strings.
+            let value = match setting["value"].as_str() {
+                Some(v) => EgValue::parse(v).unwrap_or_else(|_| EgValue::from(v)),
+                None => continue,
+            };
+
+            if let Some(code) = name.strip_prefix("checkin.override.") {
+                if value.boolish() {
+                    checkin_override.push(Yaml::String(code.to_string()));
+                }
+            } else if let Some(code) = name.strip_prefix("checkout.override.") {
+                if value.boolish() {
+                    checkout_override.push(Yaml::String(code.to_string()));
+                }
+            } else {
+                hash.insert(
+                    Yaml::String(name.replace("_", "-")),
+                    eg_value_to_yaml(&value),
+                );
+            }
+        }
+
+        if !checkin_override.is_empty() {
+            hash.insert(
+                Yaml::String("checkin-override".to_string()),
+                Yaml::Array(checkin_override),
+            );
+        }
+        if !checkout_override.is_empty() {
+            hash.insert(
+                Yaml::String("checkout-override".to_string()),
+                Yaml::Array(checkout_override),
+            );
+        }
+
+        Ok(Yaml::Hash(hash))
+    }
+}
+
+/// Translate a JSON-ish EgValue into its YAML equivalent.
+fn eg_value_to_yaml(value: &EgValue) -> Yaml {
+    match value {
+        EgValue::Boolean(b) => Yaml::Boolean(*b),
+        EgValue::String(s) => Yaml::String(s.to_string()),
+        EgValue::Number(_) => match value.as_i64() {
+            Some(i) => Yaml::Integer(i),
+            None => Yaml::Real(value.dump()),
+        },
+        EgValue::Array(_) => Yaml::Array(value.members().map(eg_value_to_yaml).collect()),
+        EgValue::Hash(_) => {
+            let mut hash = yaml::Hash::new();
+            for (k, v) in value.entries() {
+                hash.insert(Yaml::String(k.to_string()), eg_value_to_yaml(v));
+            }
+            Yaml::Hash(hash)
+        }
+        _ => Yaml::Null,
+    }
 }
@@ -0,0 +1,20 @@
+use eg::EgResult;
+use evergreen as eg;
+use sip2;
+
+/// Processes SIP requests on behalf of a SIP client session.
+pub trait Engine {
+    /// Process a single SIP request, returning the response to
+    /// deliver to the SIP client.
+    ///
+    /// Returns Err if the session cannot continue.
+    fn handle_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message>;
+
+    /// Called when the SIP client disconnects or the session moves
+    /// to a different engine.
+    fn end_session(&mut self) -> EgResult<()>;
+
+    /// Returns our OpenSRF bus connection, if we have one, so it may
+    /// be reused.
+    fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus>;
+}
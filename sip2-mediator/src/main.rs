@@ -4,7 +4,11 @@ use mptc;
 use std::env;
 use std::path::Path;
 
+mod backend;
 mod conf;
+mod engine;
+mod native;
+mod relay;
 mod server;
 mod session;
 
@@ -13,36 +17,67 @@ const DEFAULT_CONFIG_2: &str = "./sip2-mediator/conf/eg-sip2-mediator.yml";
 const DEFAULT_CONFIG_3: &str = "/usr/local/etc/eg-sip2-mediator.example.yml";
 const DEFAULT_CONFIG_4: &str = "./sip2-mediator/conf/eg-sip2-mediator.example.yml";
 
-fn load_config() -> EgResult<conf::Config> {
-    if let Ok(ref file) = env::var("EG_SIP2_MEDIATOR_CONFIG") {
-        conf::Config::from_yaml(&file)
+const APPNAME: &str = "sip2-mediator";
+
+fn config_file() -> EgResult<String> {
+    if let Ok(file) = env::var("EG_SIP2_MEDIATOR_CONFIG") {
+        Ok(file)
     } else if Path::new(DEFAULT_CONFIG_1).exists() {
-        conf::Config::from_yaml(DEFAULT_CONFIG_1)
+        Ok(DEFAULT_CONFIG_1.to_string())
     } else if Path::new(DEFAULT_CONFIG_2).exists() {
-        conf::Config::from_yaml(DEFAULT_CONFIG_2)
+        Ok(DEFAULT_CONFIG_2.to_string())
     } else if Path::new(DEFAULT_CONFIG_3).exists() {
-        conf::Config::from_yaml(DEFAULT_CONFIG_3)
+        Ok(DEFAULT_CONFIG_3.to_string())
     } else if Path::new(DEFAULT_CONFIG_4).exists() {
-        conf::Config::from_yaml(DEFAULT_CONFIG_4)
+        Ok(DEFAULT_CONFIG_4.to_string())
     } else {
         Err(format!("sip2-mediator requires a configuration file").into())
     }
 }
 
+/// Setup syslog for backends that run without an OpenSRF configuration.
+fn init_syslog(conf: &conf::Config) -> EgResult<()> {
+    let mut options = eg::osrf::conf::LogOptions::syslog();
+
+    if let Some(facility) = conf.syslog_facility.as_deref() {
+        options.set_syslog_facility(facility)?;
+    }
+
+    if let Some(level) = conf.syslog_level.as_deref() {
+        options.set_log_level(level);
+    }
+
+    let mut logger = eg::Logger::new(&options)?;
+    logger.set_application(APPNAME);
+
+    logger
+        .init()
+        .map_err(|e| format!("Error initializing logger: {e}").into())
+}
+
 fn main() -> EgResult<()> {
-    let conf = load_config()?;
+    let config_file = config_file()?;
+    let conf = conf::Config::from_yaml(&config_file)?;
     let max_workers = conf.max_clients;
     let min_workers = conf.min_workers;
 
-    let options = eg::init::InitOptions {
-        skip_logging: false,
-        skip_host_settings: true,
-        appname: Some("sip2-mediator".to_string()),
+    let client = if conf.needs_opensrf() {
+        let options = eg::init::InitOptions {
+            skip_logging: false,
+            skip_host_settings: true,
+            appname: Some(APPNAME.to_string()),
+        };
+
+        Some(eg::init::with_options(&options)?)
+    } else {
+        // No OpenSRF here.  Log to syslog using our own settings.
+        init_syslog(&conf)?;
+        None
     };
 
-    let ctx = eg::init::with_options(&options)?;
+    log::info!("SIP2 Mediator starting with config {config_file}");
 
-    let stream = server::Server::setup(conf, ctx)?;
+    let stream = server::Server::setup(&config_file, client)?;
 
     let mut s = mptc::Server::new(Box::new(stream));
 
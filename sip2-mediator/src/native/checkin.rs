@@ -1,5 +1,5 @@
 use super::item;
-use super::session::Session;
+use super::NativeEngine;
 use chrono::NaiveDateTime;
 use eg::common::circulator::Circulator;
 use eg::constants as C;
@@ -56,7 +56,7 @@ pub struct CheckinResult {
     hold_patron_barcode: Option<String>,
 }
 
-impl Session {
+impl NativeEngine {
     pub fn handle_checkin(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
         self.set_authtoken()?;
 
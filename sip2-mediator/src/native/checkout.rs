@@ -1,6 +1,6 @@
 use super::item::Item;
 use super::patron::Patron;
-use super::session::Session;
+use super::NativeEngine;
 use eg::common::circulator::Circulator;
 use eg::date;
 use eg::result::EgResult;
@@ -34,7 +34,7 @@ impl CheckoutResult {
     }
 }
 
-impl Session {
+impl NativeEngine {
     pub fn handle_checkout(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
         self.set_authtoken()?;
 
@@ -1,4 +1,4 @@
-use super::session::Session;
+use super::NativeEngine;
 use eg::constants as C;
 use eg::date;
 use eg::result::EgResult;
@@ -27,7 +27,7 @@ pub struct Item {
     pub circ_patron_id: Option<i64>,
 }
 
-impl Session {
+impl NativeEngine {
     /// Collect a pile of data for a copy by barcode
     pub fn get_item_details(&mut self, barcode: &str) -> EgResult<Option<Item>> {
         let search = eg::hash! {
@@ -177,7 +177,7 @@ impl Session {
                 ("AQ", &item.permanent_loc),
                 ("BG", &item.owning_loc),
                 ("CT", &item.destination_loc),
-                ("BH", self.sip_config().currency()),
+                ("BH", self.sip_config().currency.as_str()),
                 ("BV", &format!("{:.2}", item.deposit_amount)),
                 ("CF", &format!("{}", item.hold_queue_length)),
                 ("CK", &item.media_type),
@@ -1,16 +1,22 @@
 use super::conf;
-use eg::auth;
-use eg::auth::AuthSession;
+use super::engine::Engine;
+use eg::common::auth;
+use eg::common::user;
 use eg::result::EgResult;
 use eg::EgValue;
 use evergreen as eg;
 use sip2;
 use std::collections::HashMap;
 use std::fmt;
-use std::net;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+mod checkin;
+mod checkout;
+mod item;
+mod patron;
+mod payment;
+mod util;
+
 /* --------------------------------------------------------- */
 // By order of appearance in the INSTITUTION_SUPPORTS string:
 // patron status request
@@ -32,15 +38,8 @@ use std::sync::Arc;
 const INSTITUTION_SUPPORTS: &str = "YYYNYNYYNYYNNNYN";
 /* --------------------------------------------------------- */
 
-/// Manages a single SIP client connection.
-///
-/// May process multiple connections over time.
-pub struct Session {
-    sip_connection: sip2::Connection,
-
-    /// If true, the server is shutting down, so we should exit.
-    shutdown: Arc<AtomicBool>,
-
+/// Handles SIP requests directly, using the Evergreen API and database.
+pub struct NativeEngine {
     sip_config: Arc<conf::Config>,
 
     /// Created in worker_start.
@@ -58,42 +57,25 @@ pub struct Session {
     org_cache: HashMap<i64, EgValue>,
 }
 
-impl Session {
+impl NativeEngine {
     pub fn new(
         sip_config: Arc<conf::Config>,
         osrf_bus: eg::osrf::bus::Bus,
-        stream: net::TcpStream,
-        shutdown: Arc<AtomicBool>,
         org_cache: HashMap<i64, EgValue>,
     ) -> Self {
-        if let Ok(a) = stream.peer_addr() {
-            log::info!("New SIP connection from {a}");
-        }
-
-        let mut con = sip2::Connection::from_stream(stream);
-        con.set_ascii(sip_config.ascii());
-
         let osrf_client = eg::Client::from_bus(osrf_bus);
 
         let editor = eg::Editor::new(&osrf_client);
 
-        Session {
+        NativeEngine {
             editor,
-            shutdown,
             sip_config,
             osrf_client,
             org_cache,
             account: None,
-            sip_connection: con,
         }
     }
 
-    /// Panics if our client has no bus.  Use with caution and only
-    /// after this Session has completed.
-    pub fn take_bus(&mut self) -> eg::osrf::bus::Bus {
-        self.osrf_client.take_bus()
-    }
-
     pub fn org_cache(&self) -> &HashMap<i64, EgValue> {
         &self.org_cache
     }
@@ -144,7 +126,7 @@ impl Session {
                 return Ok(());
             } else {
                 // Stale authtoken.  Remove it.
-                AuthSession::logout(&self.osrf_client, self.authtoken()?)?;
+                auth::Session::logout(&self.osrf_client, self.authtoken()?)?;
             }
         }
 
@@ -189,7 +171,7 @@ impl Session {
     /// Create a internal auth session in the ILS
     fn login(&mut self) -> EgResult<()> {
         let ils_user_id = self.get_ils_user_id()?;
-        let mut args = auth::AuthInternalLoginArgs::new(ils_user_id, "staff");
+        let mut args = auth::InternalLoginArgs::new(ils_user_id, auth::LoginType::Staff);
 
         if self.has_account() {
             if let Some(w) = self.account().workstation() {
@@ -197,7 +179,7 @@ impl Session {
             }
         }
 
-        let auth_ses = match AuthSession::internal_session(&self.osrf_client, &args)? {
+        let auth_ses = match auth::Session::internal_session_api(&self.osrf_client, &args)? {
             Some(s) => s,
             None => Err(format!("Internal Login failed"))?,
         };
@@ -210,68 +192,6 @@ impl Session {
         Ok(())
     }
 
-    /// Wait for SIP requests in a loop and send replies.
-    ///
-    /// Exits when the shutdown signal is set or on unrecoverable error.
-    pub fn start(&mut self) -> EgResult<()> {
-        log::debug!("{self} starting");
-
-        loop {
-            if self.shutdown.load(Ordering::Relaxed) {
-                log::debug!("{self} Shutdown notice received, exiting listen loop");
-                break;
-            }
-
-            let sip_req_op = match self
-                .sip_connection
-                .recv_with_timeout(conf::SIP_SHUTDOWN_POLL_INTERVAL)
-            {
-                Ok(r) => r,
-                Err(e) => {
-                    log::info!("{self} client disconnected: {e}. Session exiting");
-                    break;
-                }
-            };
-
-            log::trace!("{self} waking from SIP message receive poll");
-
-            let sip_req = match sip_req_op {
-                Some(r) => r,
-                None => continue,
-            };
-
-            log::trace!("{self} Read SIP message: {:?}", sip_req);
-
-            let mut sip_resp = self.handle_sip_request(&sip_req)?;
-
-            log::trace!("{self} server replying with {sip_resp:?}");
-
-            self.redact_sip_response(&mut sip_resp);
-
-            log::trace!("{self} server response after redaction: {sip_resp:?}");
-
-            // Send the SIP response back to the SIP client
-            self.sip_connection
-                .send(&sip_resp)
-                .or_else(|e| Err(format!("SIP send failed: {e}")))?;
-
-            log::debug!("{self} Successfully relayed response back to SIP client");
-        }
-
-        log::info!("{self} shutting down");
-
-        self.sip_connection.disconnect().ok();
-
-        if self.authtoken().is_ok() {
-            AuthSession::logout(&self.osrf_client, self.authtoken()?).ok();
-        }
-
-        // Remove any cruft we may have left on the bus.
-        self.osrf_client.clear()?;
-
-        Ok(())
-    }
-
     fn redact_sip_response(&self, resp: &mut sip2::Message) {
         if !self.has_account() {
             // Can happen if this is a pre-log SC response.
@@ -305,7 +225,7 @@ impl Session {
     }
 
     /// Process a single SIP request.
-    fn handle_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
+    fn process_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
         let code = msg.spec().code;
 
         if code.eq("99") {
@@ -344,9 +264,10 @@ impl Session {
                 // Caller sent enough values to attempt login
 
                 if let Some(account) = self.sip_config().get_account(&username) {
-                    if account.sip_password().eq(password) {
+                    let account = account.clone();
+                    if self.verify_account_password(&account, password)? {
                         login_ok = "1";
-                        self.account = Some(account.clone());
+                        self.account = Some(account);
                     }
                 } else {
                     log::warn!("No such SIP account: {username}");
@@ -361,8 +282,33 @@ impl Session {
         Ok(sip2::Message::from_ff_values(&sip2::spec::M_LOGIN_RESP, &[login_ok]).unwrap())
     }
 
+    /// Returns true if the provided password matches the password
+    /// for the SIP account.
+    ///
+    /// Accounts loaded from the database have no local password.
+    /// Their passwords are verified by the database.
+    fn verify_account_password(
+        &mut self,
+        account: &conf::SipAccount,
+        password: &str,
+    ) -> EgResult<bool> {
+        if let Some(pass) = account.sip_password() {
+            return Ok(pass.eq(password));
+        }
+
+        let user_id = match account.ils_user_id() {
+            Some(id) => id,
+            None => Err(format!(
+                "SIP account {} has no ILS user",
+                account.sip_username()
+            ))?,
+        };
+
+        user::verify_password(self.editor_mut(), user_id, password, user::PW_TYPE_SIP2)
+    }
+
     fn handle_sc_status(&mut self, _msg: &sip2::Message) -> EgResult<sip2::Message> {
-        if self.account.is_none() && !self.sip_config().sc_status_before_login() {
+        if self.account.is_none() && !self.sip_config().sc_status_before_login {
             Err(format!("SC Status before login disabled"))?;
         }
 
@@ -403,7 +349,32 @@ impl Session {
     }
 }
 
-impl fmt::Display for Session {
+impl Engine for NativeEngine {
+    fn handle_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
+        let mut resp = self.process_sip_request(msg)?;
+
+        log::trace!("{self} server replying with {resp:?}");
+
+        self.redact_sip_response(&mut resp);
+
+        Ok(resp)
+    }
+
+    /// Logout of the ILS and remove any cruft we may have left on the bus.
+    fn end_session(&mut self) -> EgResult<()> {
+        if self.authtoken().is_ok() {
+            auth::Session::logout(&self.osrf_client, self.authtoken()?).ok();
+        }
+
+        self.osrf_client.clear()
+    }
+
+    fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus> {
+        Some(self.osrf_client.take_bus())
+    }
+}
+
+impl fmt::Display for NativeEngine {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(ref acct) = self.account {
             write!(f, "SIPSession({})", acct.sip_username())
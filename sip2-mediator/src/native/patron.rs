@@ -1,5 +1,5 @@
-use super::conf;
-use super::session::Session;
+use super::NativeEngine;
+use crate::conf;
 use eg::date;
 use eg::result::EgResult;
 use eg::EgValue;
@@ -67,12 +67,8 @@ pub struct Patron {
     pub renew_denied: bool,
     pub recall_denied: bool,
     pub holds_denied: bool,
-    pub card_lost: bool,
     pub max_overdue: bool,
     pub max_fines: bool,
-    pub recall_overdue: bool,
-    pub max_bills: bool,
-    pub valid: bool,
     pub card_active: bool,
     pub balance_owed: f64,
     pub password_verified: bool,
@@ -110,12 +106,8 @@ impl Patron {
             renew_denied: false,
             recall_denied: false,
             holds_denied: false,
-            card_lost: false,
             max_overdue: false,
             max_fines: false,
-            recall_overdue: false,
-            max_bills: false,
-            valid: false,
             card_active: false,
             balance_owed: 0.0,
             password_verified: false,
@@ -142,7 +134,7 @@ impl Patron {
     }
 }
 
-impl Session {
+impl NativeEngine {
     pub fn get_patron_details(
         &mut self,
         barcode: &str,
@@ -1015,7 +1007,7 @@ impl Session {
                 ("AO", self.account().settings().institution()),
                 ("AA", barcode),
                 ("AE", &patron.name),
-                ("BH", self.sip_config().currency()),
+                ("BH", self.sip_config().currency.as_str()),
                 ("BL", sip2::util::sip_bool(true)), // valid patron
                 ("BV", &format!("{:.2}", patron.balance_owed)),
                 ("CQ", sip2::util::sip_bool(patron.password_verified)),
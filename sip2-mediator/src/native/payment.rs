@@ -1,5 +1,5 @@
 use super::patron::Patron;
-use super::session::Session;
+use super::NativeEngine;
 use eg::result::EgResult;
 use eg::EgValue;
 use evergreen as eg;
@@ -20,7 +20,7 @@ impl PaymentResult {
     }
 }
 
-impl Session {
+impl NativeEngine {
     pub fn handle_payment(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
         self.set_authtoken()?;
 
@@ -0,0 +1,265 @@
+use super::backend::{Backend, HttpBackend, OsrfBackend};
+use super::conf::{self, BackendType};
+use super::engine::Engine;
+use eg::EgResult;
+use evergreen as eg;
+use sip2;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Screen message included in offline checkin/checkout responses.
+const OFFLINE_SCREEN_MSG: &str = "Service temporarily unavailable.  Please try again later.";
+
+/// Relays SIP requests to the ILS via the configured backend.
+pub struct RelayEngine {
+    /// Session key shared with the ILS.
+    key: String,
+
+    /// Relays our SIP requests to the ILS.
+    backend: Box<dyn Backend>,
+
+    sip_config: Arc<conf::Config>,
+
+    /// Most recent ACS Status response from the ILS and when we got it.
+    ///
+    /// Used for answering SC Status requests locally.
+    acs_status: Option<(sip2::Message, Instant)>,
+}
+
+impl RelayEngine {
+    /// Create a new relay engine for the session identified by 'key'.
+    ///
+    /// The OpenSRF backend requires a bus connection.
+    pub fn new(
+        key: &str,
+        sip_config: Arc<conf::Config>,
+        osrf_bus: Option<eg::osrf::bus::Bus>,
+    ) -> EgResult<RelayEngine> {
+        let backend: Box<dyn Backend> = match sip_config.backend {
+            BackendType::Opensrf => match osrf_bus {
+                Some(bus) => Box::new(OsrfBackend::new(bus)),
+                None => return Err("OpenSRF backend requires a bus connection".into()),
+            },
+            BackendType::Http => Box::new(HttpBackend::new(&sip_config)?),
+        };
+
+        Ok(RelayEngine {
+            key: key.to_string(),
+            backend,
+            sip_config,
+            acs_status: None,
+        })
+    }
+
+    /// Relay a SIP request to the ILS, answering locally where
+    /// possible and when the ILS fails to respond.
+    fn relay_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
+        let is_sc_status = msg.spec() == &sip2::spec::M_SC_STATUS;
+
+        if is_sc_status {
+            if let Some(resp) = self.local_acs_status(false) {
+                log::debug!("{self} answering SC Status from cache");
+                return Ok(resp);
+            }
+        }
+
+        let error = match self.ils_round_trip(msg) {
+            Ok(resp) => {
+                if resp.spec() == &sip2::spec::M_ACS_STATUS {
+                    self.cache_acs_status(&resp);
+                }
+                return Ok(resp);
+            }
+            Err(e) => e,
+        };
+
+        if !self.sip_config.offline_responses {
+            return Err(error);
+        }
+
+        log::warn!(
+            "{self} ILS failed to handle message {}; sending offline response: {error}",
+            msg.spec().code
+        );
+
+        if is_sc_status {
+            if let Some(resp) = self.local_acs_status(true) {
+                return Ok(resp);
+            }
+        }
+
+        Ok(self.offline_response(msg))
+    }
+
+    /// Store a copy of an ACS Status response for later reuse.
+    fn cache_acs_status(&mut self, resp: &sip2::Message) {
+        if self.sip_config.sc_status_cache_ttl == 0 {
+            return;
+        }
+
+        // Round-trip through SIP text to get a copy of the message.
+        if let Ok(msg) = sip2::Message::from_sip(&resp.to_sip()) {
+            self.acs_status = Some((msg, Instant::now()));
+        }
+    }
+
+    /// Build an ACS Status response from our cached copy, updating its
+    /// date/time sync value.
+    ///
+    /// Returns None if we have no cached ACS Status or the cached
+    /// copy has expired, unless 'allow_expired' is true.
+    fn local_acs_status(&self, allow_expired: bool) -> Option<sip2::Message> {
+        let (cached, cache_time) = self.acs_status.as_ref()?;
+
+        if !allow_expired && cache_time.elapsed().as_secs() >= self.sip_config.sc_status_cache_ttl {
+            return None;
+        }
+
+        let date = sip2::util::sip_date_now();
+
+        let ff_values: Vec<&str> = cached
+            .fixed_fields()
+            .iter()
+            .map(|ff| {
+                if ff.spec() == &sip2::spec::FF_DATETIME_SYNC {
+                    date.as_str()
+                } else {
+                    ff.value()
+                }
+            })
+            .collect();
+
+        let fields: Vec<(&str, &str)> = cached
+            .fields()
+            .iter()
+            .map(|f| (f.code(), f.value()))
+            .collect();
+
+        sip2::Message::from_values(&sip2::spec::M_ACS_STATUS, &ff_values, &fields).ok()
+    }
+
+    /// Create a well-formed response to a SIP request for cases where
+    /// the ILS could not provide one.
+    ///
+    /// Checkin and checkout requests receive a failed response with an
+    /// explanatory screen message.  SC Status requests receive an
+    /// offline ACS Status.  Other requests receive a Request SC Resend
+    /// message, prompting the SIP client to try again.
+    fn offline_response(&self, msg: &sip2::Message) -> sip2::Message {
+        let date = sip2::util::sip_date_now();
+        let inst = msg.get_field_value("AO").unwrap_or("");
+        let item = msg.get_field_value("AB").unwrap_or("");
+
+        let resp = match msg.spec().code {
+            "99" => sip2::Message::from_values(
+                &sip2::spec::M_ACS_STATUS,
+                &[
+                    "N",   // online status
+                    "N",   // checkin ok
+                    "N",   // checkout ok
+                    "N",   // renewal policy
+                    "N",   // status update
+                    "N",   // offline ok
+                    "999", // timeout
+                    "999", // max retries
+                    &date,
+                    sip2::spec::SIP_PROTOCOL_VERSION,
+                ],
+                &[("AO", inst), ("BX", "NNNNNNNNNNNNNNNN")],
+            ),
+            "09" => sip2::Message::from_values(
+                &sip2::spec::M_CHECKIN_RESP,
+                &["0", "N", "U", "N", &date],
+                &[
+                    ("AO", inst),
+                    ("AB", item),
+                    ("AQ", ""),
+                    ("AF", OFFLINE_SCREEN_MSG),
+                ],
+            ),
+            "11" => sip2::Message::from_values(
+                &sip2::spec::M_CHECKOUT_RESP,
+                &["0", "N", "U", "N", &date],
+                &[
+                    ("AO", inst),
+                    ("AA", msg.get_field_value("AA").unwrap_or("")),
+                    ("AB", item),
+                    ("AJ", ""),
+                    ("AF", OFFLINE_SCREEN_MSG),
+                ],
+            ),
+            _ => Ok(sip2::Message::new(
+                &sip2::spec::M_REQUEST_SC_RESEND,
+                vec![],
+                vec![],
+            )),
+        };
+
+        // Fixed field values above are all valid for their specs.
+        resp.unwrap()
+    }
+
+    /// Send the final End Session (XS) message to the ILS.
+    ///
+    /// Response and errors are ignored since this is the final step
+    /// in the session shuting down.
+    fn send_end_session(&mut self) -> EgResult<()> {
+        log::debug!("{self} sending end of session message to the ILS");
+
+        let msg_spec = sip2::spec::Message::from_code("XS").unwrap();
+
+        let msg = sip2::Message::new(&msg_spec, vec![], vec![]);
+
+        self.ils_round_trip(&msg).map(|_| ())
+    }
+
+    /// Send a SIP client request to the ILS backend for processing.
+    ///
+    /// Blocks waiting for a response.
+    fn ils_round_trip(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
+        let msg_json = match msg.to_json_value() {
+            Ok(m) => m,
+            Err(e) => {
+                return Err(format!("{self} Failed translating SIP message to JSON: {e}").into())
+            }
+        };
+
+        log::debug!("{self} posting message: {msg_json}");
+
+        let timeout = self.sip_config.request_timeout_for(msg.spec().code);
+
+        let response = self
+            .backend
+            .round_trip(&self.key, msg_json, timeout)
+            .map_err(|e| format!("{self} {e}"))?;
+
+        log::debug!("{self} ILS response JSON: {response}");
+
+        match sip2::Message::from_json_value(&response) {
+            Ok(m) => Ok(m),
+            Err(e) => Err(format!("{self} error translating JSON to SIP: {e}").into()),
+        }
+    }
+}
+
+impl Engine for RelayEngine {
+    fn handle_sip_request(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
+        self.relay_sip_request(msg)
+    }
+
+    /// Tell the ILS our session is done.
+    fn end_session(&mut self) -> EgResult<()> {
+        self.send_end_session()
+    }
+
+    fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus> {
+        self.backend.take_bus()
+    }
+}
+
+impl fmt::Display for RelayEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Relay {}", self.key)
+    }
+}
@@ -2,9 +2,12 @@ use super::conf::Config;
 use super::session::Session;
 use eg::osrf;
 use eg::Client;
+use eg::EgResult;
+use eg::EgValue;
 use evergreen as eg;
 use mptc;
 use std::any::Any;
+use std::collections::HashMap;
 use std::net::TcpListener;
 use std::net::TcpStream;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -40,11 +43,19 @@ pub struct SessionFactory {
 
     /// OpenSRF bus.
     osrf_bus: Option<eg::osrf::bus::Bus>,
+
+    /// Cache of org unit shortnames and IDs.
+    org_cache: HashMap<i64, EgValue>,
 }
 
-impl mptc::RequestHandler for SessionFactory {
-    fn worker_start(&mut self) -> Result<(), String> {
-        // Connect to Evergreen when each thread first starts.
+impl SessionFactory {
+    /// Connect to OpenSRF if our configuration requires it and we
+    /// have no bus connection.
+    fn connect_bus(&mut self) -> Result<(), String> {
+        if self.osrf_bus.is_some() || !self.sip_config.needs_opensrf() {
+            return Ok(());
+        }
+
         let bus = eg::osrf::bus::Bus::new(osrf::conf::config().client())?;
         self.osrf_bus = Some(bus);
 
@@ -52,6 +63,13 @@ impl mptc::RequestHandler for SessionFactory {
 
         Ok(())
     }
+}
+
+impl mptc::RequestHandler for SessionFactory {
+    fn worker_start(&mut self) -> Result<(), String> {
+        // Connect to Evergreen when each thread first starts.
+        self.connect_bus()
+    }
 
     fn worker_end(&mut self) -> Result<(), String> {
         log::debug!("SessionFactory worker_end()");
@@ -66,16 +84,23 @@ impl mptc::RequestHandler for SessionFactory {
 
         let shutdown = self.shutdown.clone();
 
-        // Set in worker_start
-        let osrf_bus = self.osrf_bus.take().unwrap();
-
         let sip_config = self.sip_config.clone();
 
+        // Normally set in worker_start, but a previous session may
+        // have lost its connection.
+        self.connect_bus()?;
+
         // request.stream is set in the call to next() that produced
         // this request.
         let stream = request.stream.take().unwrap();
 
-        let mut session = Session::new(sip_config, osrf_bus, stream, shutdown)?;
+        let mut session = Session::new(
+            sip_config,
+            self.osrf_bus.take(),
+            self.org_cache.clone(),
+            stream,
+            shutdown,
+        )?;
 
         if let Err(e) = session.start() {
             // This is not necessarily an error.  The client may simply
@@ -86,7 +111,10 @@ impl mptc::RequestHandler for SessionFactory {
 
         // Take our bus back so we don't have to reconnect in between
         // SIP clients.  This SIP Session is done with it.
-        let mut bus = session.take_bus();
+        let mut bus = match session.take_bus() {
+            Some(b) => b,
+            None => return Ok(()), // Not using OpenSRF or bus was lost.
+        };
 
         // Remove any trailing data on the Bus.
         bus.clear_bus()?;
@@ -105,11 +133,18 @@ impl mptc::RequestHandler for SessionFactory {
 /// Listens for SIP client connections and passes them off to mptc:: for
 /// relaying to a Session worker.
 pub struct Server {
-    client: Client,
+    /// OpenSRF client.  None if our backend does not use OpenSRF.
+    client: Option<Client>,
 
     /// Parsed config
     sip_config: Arc<Config>,
 
+    /// Path the SIP config so it can be reloaded on request.
+    sip_config_file: String,
+
+    /// Cache of org unit shortnames and IDs.
+    org_cache: HashMap<i64, EgValue>,
+
     /// Set to true of the mptc::Server tells us it's time to shutdown.
     ///
     /// Read by our Sessions
@@ -149,12 +184,25 @@ impl mptc::RequestStream for Server {
             shutdown: self.shutdown.clone(),
             sip_config: self.sip_config.clone(),
             osrf_bus: None, // set in worker_start
+            org_cache: self.org_cache.clone(),
         };
 
         Box::new(sf)
     }
 
     fn reload(&mut self) -> Result<(), String> {
+        match Server::load_config(&self.sip_config_file, self.client.as_ref()) {
+            Ok(c) => self.sip_config = Arc::new(c),
+            Err(e) => log::error!("Error reloading config.  Using old config. {e}"),
+        }
+
+        // Fails if we cannot talk to OpenSRF.
+        self.precache()?;
+
+        // No need to inform our worker sessions that we're reloading.
+        // mptc will clear/reload idle workers, and there's no need to
+        // force-exit a connected session.
+
         Ok(())
     }
 
@@ -166,25 +214,78 @@ impl mptc::RequestStream for Server {
         log::info!("Server received mptc shutdown request");
 
         self.shutdown.store(true, Ordering::Relaxed);
-        self.client.clear().ok();
+
+        if let Some(client) = self.client.as_ref() {
+            client.clear().ok();
+        }
     }
 }
 
 impl Server {
-    pub fn setup(config: Config, client: Client) -> Result<Server, String> {
+    /// Create our listener from the configuration loaded from
+    /// 'config_file'.
+    ///
+    /// 'client' is required when any part of our configuration uses
+    /// OpenSRF.
+    pub fn setup(config_file: &str, client: Option<Client>) -> EgResult<Server> {
+        let config = Server::load_config(config_file, client.as_ref())?;
+
         let tcp_listener = eg::util::tcp_listener(
             &config.sip_address,
             config.sip_port,
             SIP_SHUTDOWN_POLL_INTERVAL,
         )?;
 
-        let server = Server {
+        let mut server = Server {
             client,
             tcp_listener,
             sip_config: Arc::new(config),
+            sip_config_file: config_file.to_string(),
+            org_cache: HashMap::new(),
             shutdown: Arc::new(AtomicBool::new(false)),
         };
 
+        server.precache()?;
+
         Ok(server)
     }
+
+    /// Load the YAML configuration, plus any SIP accounts stored
+    /// in the database when so configured.
+    fn load_config(filename: &str, client: Option<&Client>) -> EgResult<Config> {
+        let mut sip_conf = Config::from_yaml(filename)?;
+
+        if sip_conf.database_accounts {
+            let client = client.ok_or("Database accounts require OpenSRF")?;
+            let mut editor = eg::Editor::new(client);
+            sip_conf.add_database_accounts(&mut editor)?;
+        }
+
+        Ok(sip_conf)
+    }
+
+    /// Pre-cache data that's universally useful to the native engine.
+    fn precache(&mut self) -> EgResult<()> {
+        let Some(client) = self.client.as_ref() else {
+            return Ok(());
+        };
+
+        let mut e = eg::Editor::new(client);
+
+        let search = eg::hash! {
+            "id": {"!=": EgValue::Null},
+        };
+
+        let mut orgs = e.search("aou", search)?;
+
+        let mut map = HashMap::new();
+
+        for org in orgs.drain(..) {
+            map.insert(org.id()?, org);
+        }
+
+        self.org_cache = map;
+
+        Ok(())
+    }
 }
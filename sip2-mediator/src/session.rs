@@ -1,9 +1,13 @@
-use super::conf;
-use eg::EgEvent;
+use super::conf::{self, BackendType, EngineType};
+use super::engine::Engine;
+use super::native::NativeEngine;
+use super::relay::RelayEngine;
 use eg::EgResult;
 use eg::EgValue;
 use evergreen as eg;
 use sip2;
+use sip2::transcript::{Direction, Transcript};
+use std::collections::HashMap;
 use std::fmt;
 use std::net;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -13,7 +17,8 @@ use std::sync::Arc;
 /// for shutdown, etc. signals.
 const SIG_POLL_INTERVAL: u64 = 5;
 
-/// Manages the connection between a SIP client and the Evergreen backend.
+/// Manages the connection between a SIP client and the engine which
+/// processes its requests.
 pub struct Session {
     sip_connection: sip2::Connection,
 
@@ -23,11 +28,24 @@ pub struct Session {
     /// SIP login; useful or logging.
     sip_user: Option<String>,
 
-    /// OpenSRF client.
-    client: eg::Client,
+    /// Processes our SIP requests.  Selected by the SIP account
+    /// once the client logs in.
+    engine: Option<(EngineType, Box<dyn Engine>)>,
+
+    /// OpenSRF bus, held here while no engine is using it.
+    osrf_bus: Option<eg::osrf::bus::Bus>,
+
+    /// Cache of org unit shortnames and IDs for the native engine.
+    org_cache: HashMap<i64, EgValue>,
 
     /// If true, we're shutting down.
     shutdown: Arc<AtomicBool>,
+
+    sip_config: Arc<conf::Config>,
+
+    /// Records the messages exchanged with the SIP client when
+    /// transcripts are enabled.
+    transcript: Option<Transcript>,
 }
 
 impl Session {
@@ -36,7 +54,8 @@ impl Session {
     /// At this point we are already running within our own thread.
     pub fn new(
         sip_config: Arc<conf::Config>,
-        osrf_bus: eg::osrf::bus::Bus,
+        osrf_bus: Option<eg::osrf::bus::Bus>,
+        org_cache: HashMap<i64, EgValue>,
         stream: net::TcpStream,
         shutdown: Arc<AtomicBool>,
     ) -> EgResult<Session> {
@@ -51,12 +70,19 @@ impl Session {
         let mut con = sip2::Connection::from_stream(stream);
         con.set_ascii(sip_config.ascii);
 
-        let client = eg::Client::from_bus(osrf_bus);
+        let transcript = match sip_config.transcript_dir.as_deref() {
+            Some(dir) => open_transcript(dir, &key),
+            None => None,
+        };
 
         let ses = Session {
             key,
+            transcript,
             shutdown,
-            client,
+            osrf_bus,
+            org_cache,
+            sip_config,
+            engine: None,
             sip_connection: con,
             sip_user: None,
         };
@@ -98,6 +124,8 @@ impl Session {
 
             log::trace!("{} Read SIP message: {:?}", self, sip_req);
 
+            self.record(Direction::Inbound, &sip_req);
+
             if sip_req.spec() == &sip2::spec::M_LOGIN {
                 // If this is a login request, capture the SIP username
                 // for improved session logging.
@@ -106,18 +134,28 @@ impl Session {
                 }
             }
 
-            // Relay the request to the Evergreen backend and wait for a
-            // response.  If an error occurs, all we can do is exit and
-            // cleanup, since SIP has no concept of an error response.
-            let sip_resp = match self.osrf_round_trip(&sip_req) {
+            // The SIP account determines which engine handles our
+            // requests.  Requests which arrive before login use the
+            // default engine.
+            let engine_type = match self.sip_user.as_deref() {
+                Some(u) => self.sip_config.engine_for(u),
+                None => self.sip_config.default_engine,
+            };
+
+            // Pass the request to our engine and wait for a response.
+            // If an error occurs, all we can do is exit and cleanup,
+            // since SIP has no concept of an error response.
+            let sip_resp = match self.handle_sip_request(engine_type, &sip_req) {
                 Ok(r) => r,
                 Err(e) => {
-                    log::error!("{self} error routing ILS message: {e}");
+                    log::error!("{self} error processing SIP message: {e}");
                     break;
                 }
             };
 
-            log::trace!("{self} EG server replied with {sip_resp:?}");
+            log::trace!("{self} engine replied with {sip_resp:?}");
+
+            self.record(Direction::Outbound, &sip_resp);
 
             // Send the response back to the SIP client as a SIP message.
             // If there's an error, exit and cleanup.
@@ -139,63 +177,125 @@ impl Session {
         // Might already be disconnected
         self.sip_connection.disconnect().ok();
 
-        // Tell the Evergreen server our session is done.
-        self.send_end_session()
-    }
+        // Tell our engine the session is done.
+        self.end_engine();
 
-    /// Send the final End Session (XS) message to the ILS.
-    ///
-    /// Response and errors are ignored since this is the final step
-    /// in the session shuting down.
-    fn send_end_session(&mut self) -> EgResult<()> {
-        log::debug!("{self} sending end of session message to the ILS");
+        Ok(())
+    }
 
-        let msg_spec = sip2::spec::Message::from_code("XS").unwrap();
+    /// Pass a SIP request to the requested engine, starting the
+    /// engine first if needed.
+    fn handle_sip_request(
+        &mut self,
+        engine_type: EngineType,
+        msg: &sip2::Message,
+    ) -> EgResult<sip2::Message> {
+        let current = self.engine.as_ref().map(|(t, _)| *t);
+
+        if current != Some(engine_type) {
+            self.end_engine();
+            let engine = self.start_engine(engine_type)?;
+            self.engine = Some((engine_type, engine));
+        }
 
-        let msg = sip2::Message::new(&msg_spec, vec![], vec![]);
+        // Set above
+        let (_, engine) = self.engine.as_mut().unwrap();
 
-        self.osrf_round_trip(&msg).map(|_| ())
+        engine.handle_sip_request(msg)
     }
 
-    /// Send a SIP client request to the ILS backend for processing.
-    ///
-    /// Blocks waiting for a response.
-    fn osrf_round_trip(&mut self, msg: &sip2::Message) -> EgResult<sip2::Message> {
-        let msg_json = match msg.to_json_value() {
-            Ok(m) => m,
-            Err(e) => {
-                return Err(format!("{self} Failed translating SIP message to JSON: {e}").into())
+    /// Create an engine of the requested type, handing it our bus
+    /// connection when it needs one.
+    fn start_engine(&mut self, engine_type: EngineType) -> EgResult<Box<dyn Engine>> {
+        log::debug!("{self} starting {engine_type:?} engine");
+
+        let engine: Box<dyn Engine> = match engine_type {
+            EngineType::Native => {
+                let bus = match self.osrf_bus.take() {
+                    Some(b) => b,
+                    None => return Err("Native engine requires OpenSRF".into()),
+                };
+
+                Box::new(NativeEngine::new(
+                    self.sip_config.clone(),
+                    bus,
+                    self.org_cache.clone(),
+                ))
             }
-        };
-
-        log::debug!("{self} posting message: {msg_json}");
+            EngineType::Relay => {
+                let bus = match self.sip_config.backend {
+                    BackendType::Opensrf => self.osrf_bus.take(),
+                    BackendType::Http => None,
+                };
 
-        let msg_val = EgValue::from_json_value(msg_json)?;
+                Box::new(RelayEngine::new(&self.key, self.sip_config.clone(), bus)?)
+            }
+        };
 
-        let params = vec![EgValue::from(self.key.as_str()), msg_val];
+        Ok(engine)
+    }
 
-        // Uses the default request timeout (probably 60 seconds).
-        let response = self
-            .client
-            .send_recv_one("open-ils.sip2", "open-ils.sip2.request", params)?
-            .ok_or_else(|| format!("{self} no response received"))?;
+    /// Tell our engine, if we have one, that our session is done and
+    /// take back its bus connection.
+    ///
+    /// Errors are logged and otherwise ignored, since the engine is
+    /// going away.
+    fn end_engine(&mut self) {
+        let Some((engine_type, mut engine)) = self.engine.take() else {
+            return;
+        };
 
-        log::debug!("{self} ILS response JSON: {response}");
+        if let Err(e) = engine.end_session() {
+            log::debug!("{self} error ending {engine_type:?} engine: {e}");
+        }
 
-        if let Some(evt) = EgEvent::parse(&response) {
-            return Err(format!("SIP request failed with event: {evt}").into());
+        if let Some(mut bus) = engine.take_bus() {
+            bus.clear_bus().ok();
+            self.osrf_bus = Some(bus);
         }
+    }
+
+    /// Add a message to our transcript, if we have one.
+    ///
+    /// Transcript failures are logged and end the transcript, but
+    /// have no effect on the SIP session.
+    fn record(&mut self, direction: Direction, msg: &sip2::Message) {
+        let Some(transcript) = self.transcript.as_mut() else {
+            return;
+        };
 
-        match sip2::Message::from_json_value(&response.into()) {
-            Ok(m) => Ok(m),
-            Err(e) => Err(format!("{self} error translating JSON to SIP: {e}").into()),
+        if let Err(e) = transcript.record(direction, msg) {
+            log::error!("{self} disabling transcript: {e}");
+            self.transcript = None;
         }
     }
 
     /// Gives the bus connection back to the worker thread so it may be
     /// reused by another session.
-    pub fn take_bus(&mut self) -> eg::osrf::bus::Bus {
-        self.client.take_bus()
+    ///
+    /// Returns None if we are not using OpenSRF.
+    pub fn take_bus(&mut self) -> Option<eg::osrf::bus::Bus> {
+        self.end_engine();
+        self.osrf_bus.take()
+    }
+}
+
+/// Open a transcript file for the session identified by 'key'.
+///
+/// Returns None, logging the error, if the file cannot be opened.
+fn open_transcript(dir: &str, key: &str) -> Option<Transcript> {
+    let date = eg::date::now_local().format("%Y%m%d%H%M%S");
+    let path = format!("{dir}/sip-{date}-{key}.jsonl");
+
+    match Transcript::open(&path) {
+        Ok(t) => {
+            log::info!("Ses {key} writing transcript to {path}");
+            Some(t)
+        }
+        Err(e) => {
+            log::error!("Ses {key} {e}");
+            None
+        }
     }
 }
 
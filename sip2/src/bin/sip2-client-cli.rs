@@ -35,6 +35,18 @@ Parameters:
     --quiet
         Print only summary information
 
+    --replay <transcript-file>
+        Re-send the client requests captured in a SIP session transcript
+        and compare each response field-by-field with the response
+        recorded in the transcript.  Date/time fixed fields are not
+        compared.  Requires the "json" feature.
+
+        When set, --sip-user and --sip-pass replace the login credentials
+        found in the transcript and --patron-password replaces redacted
+        patron passwords.  Login passwords are redacted in transcripts,
+        so --sip-pass is needed to replay a login.  Message parameters
+        are otherwise ignored.
+
 Message Parameters:
     --institution <institution>
     --patron-barcode <barcode>
@@ -62,12 +74,17 @@ fn main() {
         return;
     }
 
-    let sip_params = setup_params(&options);
-
     let host = options
         .opt_str("sip-host")
         .unwrap_or(DEFAULT_HOST.to_string());
 
+    if let Some(transcript) = options.opt_str("replay") {
+        replay(&host, &transcript, &options);
+        return;
+    }
+
+    let sip_params = setup_params(&options);
+
     let quiet = options.opt_present("quiet");
     let repeat = options.opt_get_default("repeat", 1).expect("Valid Repeat Option");
     let parallel = options.opt_get_default("parallel", 1).expect("Valid Parallel Option");
@@ -155,6 +172,90 @@ fn run_one_thread(
     }
 }
 
+/// Re-send the inbound messages from a session transcript and compare
+/// the responses to those captured in the transcript.
+#[cfg(feature = "json")]
+fn replay(host: &str, filename: &str, options: &getopts::Matches) {
+    use sip2::transcript::{self, Direction, Transcript};
+
+    let entries = Transcript::read_entries(filename).expect("Valid Transcript File");
+
+    let sip_user = options.opt_str("sip-user");
+    let sip_pass = options.opt_str("sip-pass");
+    let patron_pwd = options.opt_str("patron-password");
+
+    let mut con = Connection::new(host).expect("Cannot Connect");
+
+    let mut sent = 0;
+    let mut mismatched = 0;
+    let mut entries = entries.iter().peekable();
+
+    while let Some(entry) = entries.next() {
+        if entry.direction != Direction::Inbound {
+            continue;
+        }
+
+        let mut msg = entry.sip_message().expect("Valid Transcript Message");
+        let is_login = msg.spec() == &sip2::spec::M_LOGIN;
+
+        for field in msg.fields_mut().iter_mut() {
+            let value = match field.code() {
+                "CN" if is_login => sip_user.as_deref(),
+                "CO" if is_login => sip_pass.as_deref(),
+                "AD" => patron_pwd.as_deref(),
+                _ => None,
+            };
+
+            if let Some(v) = value {
+                field.set_value(v);
+            }
+        }
+
+        // The response recorded for this request, if any, is the next
+        // outbound entry.
+        let expected = match entries.peek() {
+            Some(e) if e.direction == Direction::Outbound => Some(
+                entries
+                    .next()
+                    .unwrap()
+                    .sip_message()
+                    .expect("Valid Transcript Message"),
+            ),
+            _ => None,
+        };
+
+        let resp = con.sendrecv(&msg).expect("Replay Request Sent");
+        sent += 1;
+
+        println!("{:.<35} sent", msg.spec().label);
+
+        let Some(expected) = expected else {
+            println!("    no response recorded in transcript");
+            continue;
+        };
+
+        let diffs = transcript::diff_messages(&expected, &resp);
+
+        if diffs.is_empty() {
+            println!("    response matches");
+        } else {
+            mismatched += 1;
+            for diff in diffs {
+                println!("    {diff}");
+            }
+        }
+    }
+
+    con.disconnect().ok();
+
+    println!("{sent} requests replayed; {mismatched} responses differed from the transcript");
+}
+
+#[cfg(not(feature = "json"))]
+fn replay(_host: &str, _filename: &str, _options: &getopts::Matches) {
+    eprintln!("--replay requires sip2 to be built with the \"json\" feature");
+}
+
 /// Read the command line arguments
 fn read_options() -> getopts::Matches {
     let args: Vec<String> = env::args().collect();
@@ -171,6 +272,7 @@ fn read_options() -> getopts::Matches {
     opts.optopt("", "location-code", "Location Code", "");
     opts.optopt("", "repeat", "Repeat Count", "");
     opts.optopt("", "parallel", "Parallel Count", "");
+    opts.optopt("", "replay", "Replay Transcript File", "");
 
     opts.optflag("h", "help", "");
     opts.optflag("q", "quiet", "");
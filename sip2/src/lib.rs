@@ -19,5 +19,8 @@ mod params;
 #[cfg(feature = "json")]
 mod message_json;
 
+#[cfg(feature = "json")]
+pub mod transcript;
+
 #[cfg(test)]
 mod tests;
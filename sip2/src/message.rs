@@ -291,10 +291,16 @@ impl Message {
         s
     }
 
-    /// Same as to_sip() but replaces the patron password 'AD' value
-    /// with redacted text.
+    /// Same as to_sip() but replaces the patron password 'AD' and
+    /// SC login password 'CO' values with redacted text.
     ///
     /// Useful for logging.
+    ///
+    /// ```
+    /// use sip2::Message;
+    /// let msg = Message::from_sip("9300CNsip_username|COsip_password|").unwrap();
+    /// assert_eq!(msg.to_sip_redacted(), "9300CNsip_username|COREDACTED|");
+    /// ```
     pub fn to_sip_redacted(&self) -> String {
         let mut s = self.spec.code.to_string();
 
@@ -303,7 +309,7 @@ impl Message {
         }
 
         for f in self.fields.iter() {
-            if f.code() == spec::F_PATRON_PWD.code {
+            if f.code() == spec::F_PATRON_PWD.code || f.code() == spec::F_LOGIN_PWD.code {
                 s += f.code();
                 s += PASSWORD_REDACTED;
                 s += "|";
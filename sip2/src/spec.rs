@@ -192,6 +192,7 @@ impl Message {
             m if m == M_END_SESSION_RESP.code => Some(&M_END_SESSION_RESP),
             m if m == M_BLOCK_PATRON.code => Some(&M_BLOCK_PATRON),
             m if m == M_REQUEST_ACS_RESEND.code => Some(&M_REQUEST_ACS_RESEND),
+            m if m == M_REQUEST_SC_RESEND.code => Some(&M_REQUEST_SC_RESEND),
             _ => None,
         }
     }
@@ -931,6 +932,13 @@ pub const M_REQUEST_ACS_RESEND: Message = Message {
     fixed_fields: &[],
 };
 
+/// Message 96
+pub const M_REQUEST_SC_RESEND: Message = Message {
+    code: "96",
+    label: "Request SC Resend",
+    fixed_fields: &[],
+};
+
 /// Message 01
 pub const M_BLOCK_PATRON: Message = Message {
     code: "01",
@@ -51,3 +51,27 @@ fn fixed_field_to_str() {
     let ff = FixedField::new(&spec::FF_MAX_PRINT_WIDTH, "999").unwrap();
     assert_eq!(ff.to_sip(), "999");
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn transcript_redacts_passwords() {
+    use super::transcript::{Direction, Transcript};
+
+    let path = std::env::temp_dir().join(format!("sip2-transcript-{}.jsonl", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let msg = Message::from_sip("9300CNsip_username|COsip_password|").unwrap();
+
+    let mut transcript = Transcript::open(path).unwrap();
+    transcript.record(Direction::Inbound, &msg).unwrap();
+
+    let entries = Transcript::read_entries(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(entries.len(), 1);
+    assert!(!entries[0].message.contains("sip_password"));
+
+    let logged = entries[0].sip_message().unwrap();
+    assert_eq!(logged.get_field_value("CN"), Some("sip_username"));
+    assert_eq!(logged.get_field_value("CO"), Some("REDACTED"));
+}
@@ -0,0 +1,256 @@
+//! Record SIP sessions as JSON lines and compare replayed responses.
+//!
+//! Each line of a transcript file is a JSON object like:
+//!
+//! {"timestamp":"2024-01-01T12:00:00.000-05:00","direction":"inbound","message":"9900302.00"}
+//!
+//! Directions are relative to the SIP server:  "inbound" messages
+//! arrive from the SIP client, "outbound" messages are responses sent
+//! back to the client.  Messages are stored in redacted form, with
+//! patron and SIP login passwords masked.
+use super::spec;
+use super::Message;
+use json;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+
+/// Which way a message was traveling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Inbound => "inbound",
+            Direction::Outbound => "outbound",
+        }
+    }
+}
+
+impl TryFrom<&str> for Direction {
+    type Error = String;
+    fn try_from(s: &str) -> Result<Direction, Self::Error> {
+        match s {
+            "inbound" => Ok(Direction::Inbound),
+            "outbound" => Ok(Direction::Outbound),
+            _ => Err(format!("Invalid transcript direction: {s}")),
+        }
+    }
+}
+
+/// One message captured within a transcript.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub timestamp: String,
+    pub direction: Direction,
+
+    /// Redacted SIP message text, minus the line terminator.
+    pub message: String,
+}
+
+impl Entry {
+    pub fn new(direction: Direction, msg: &Message) -> Entry {
+        Entry {
+            direction,
+            timestamp: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+            message: msg.to_sip_redacted(),
+        }
+    }
+
+    /// Translate the entry into a single line of JSON.
+    ///
+    /// ```
+    /// use sip2::transcript::{Direction, Entry};
+    ///
+    /// let entry = Entry {
+    ///     timestamp: "2024-01-01T12:00:00.000-05:00".to_string(),
+    ///     direction: Direction::Inbound,
+    ///     message: "9900302.00".to_string(),
+    /// };
+    ///
+    /// let line = entry.to_json_line();
+    /// let entry2 = Entry::from_json_line(&line).unwrap();
+    ///
+    /// assert_eq!(entry2.direction, Direction::Inbound);
+    /// assert_eq!(entry2.message, "9900302.00");
+    /// ```
+    pub fn to_json_line(&self) -> String {
+        json::object! {
+            "timestamp": self.timestamp.as_str(),
+            "direction": self.direction.as_str(),
+            "message": self.message.as_str(),
+        }
+        .dump()
+    }
+
+    pub fn from_json_line(line: &str) -> Result<Entry, String> {
+        let value =
+            json::parse(line).map_err(|e| format!("Invalid transcript line: {e} [{line}]"))?;
+
+        let direction = value["direction"]
+            .as_str()
+            .ok_or_else(|| format!("Transcript line has no direction: {line}"))?;
+
+        let message = value["message"]
+            .as_str()
+            .ok_or_else(|| format!("Transcript line has no message: {line}"))?;
+
+        Ok(Entry {
+            direction: Direction::try_from(direction)?,
+            timestamp: value["timestamp"].as_str().unwrap_or("").to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    /// Parse our message text into a SIP Message.
+    pub fn sip_message(&self) -> Result<Message, String> {
+        Message::from_sip(&self.message)
+            .map_err(|e| format!("Invalid SIP message in transcript: {e} [{}]", self.message))
+    }
+}
+
+/// Appends transcript entries to a file.
+pub struct Transcript {
+    file: fs::File,
+    path: String,
+}
+
+impl Transcript {
+    /// Open (or create) the transcript file at 'path' for appending.
+    pub fn open(path: &str) -> Result<Transcript, String> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Cannot open transcript file {path}: {e}"))?;
+
+        Ok(Transcript {
+            file,
+            path: path.to_string(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Add a message to the transcript.
+    pub fn record(&mut self, direction: Direction, msg: &Message) -> Result<(), String> {
+        let line = Entry::new(direction, msg).to_json_line() + "\n";
+
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("Cannot write to transcript file {}: {e}", self.path))
+    }
+
+    /// Read all entries from a transcript file.
+    pub fn read_entries(path: &str) -> Result<Vec<Entry>, String> {
+        let file =
+            fs::File::open(path).map_err(|e| format!("Cannot open transcript file {path}: {e}"))?;
+
+        let mut entries = Vec::new();
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Cannot read transcript file {path}: {e}"))?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            entries.push(Entry::from_json_line(&line)?);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Fixed fields whose values change with every request and are
+/// ignored when comparing messages.
+const VOLATILE_FIXED_FIELDS: &[&spec::FixedField] = &[&spec::FF_DATE, &spec::FF_DATETIME_SYNC];
+
+/// Compare two messages field by field, returning a description of
+/// each difference found.
+///
+/// Fixed fields are compared by position, skipping date/time values.
+/// Variable fields are compared by code, in order of appearance for
+/// repeating fields.
+///
+/// ```
+/// use sip2::Message;
+/// use sip2::transcript::diff_messages;
+///
+/// let expected = Message::from_sip("941AOexample|").unwrap();
+/// let actual = Message::from_sip("940AOexample|AFLogin failed|").unwrap();
+///
+/// let diffs = diff_messages(&expected, &actual);
+/// assert_eq!(diffs.len(), 2);
+///
+/// assert!(diff_messages(&expected, &expected).is_empty());
+/// ```
+pub fn diff_messages(expected: &Message, actual: &Message) -> Vec<String> {
+    let mut diffs = Vec::new();
+
+    if expected.spec().code != actual.spec().code {
+        diffs.push(format!(
+            "message code: expected {} got {}",
+            expected.spec().code,
+            actual.spec().code
+        ));
+        return diffs;
+    }
+
+    for (idx, ff) in expected.fixed_fields().iter().enumerate() {
+        if VOLATILE_FIXED_FIELDS.contains(&ff.spec()) {
+            continue;
+        }
+
+        let value = actual.fixed_fields().get(idx).map(|f| f.value());
+
+        if value != Some(ff.value()) {
+            diffs.push(format!(
+                "{}: expected {:?} got {:?}",
+                ff.spec().label,
+                ff.value(),
+                value.unwrap_or("")
+            ));
+        }
+    }
+
+    let mut codes: Vec<&str> = Vec::new();
+    for field in expected.fields().iter().chain(actual.fields().iter()) {
+        if !codes.contains(&field.code()) {
+            codes.push(field.code());
+        }
+    }
+
+    for code in codes {
+        let expect_values = field_values(expected, code);
+        let actual_values = field_values(actual, code);
+
+        for idx in 0..expect_values.len().max(actual_values.len()) {
+            let ev = expect_values.get(idx);
+            let av = actual_values.get(idx);
+
+            if ev != av {
+                diffs.push(format!(
+                    "{code}: expected {} got {}",
+                    ev.map(|v| format!("{v:?}")).unwrap_or("<none>".to_string()),
+                    av.map(|v| format!("{v:?}")).unwrap_or("<none>".to_string()),
+                ));
+            }
+        }
+    }
+
+    diffs
+}
+
+fn field_values<'a>(msg: &'a Message, code: &str) -> Vec<&'a str> {
+    msg.fields()
+        .iter()
+        .filter(|f| f.code() == code)
+        .map(|f| f.value())
+        .collect()
+}